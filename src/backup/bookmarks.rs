@@ -0,0 +1,71 @@
+use anyhow::Result;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+/// Browser-independent bookmark tree used for Netscape HTML export.
+#[derive(Debug)]
+pub enum BookmarkNode {
+    Link {
+        title: Option<String>,
+        url: String,
+    },
+    Folder {
+        title: Option<String>,
+        children: Vec<BookmarkNode>,
+    },
+    Separator,
+}
+
+/// Writes bookmarks in the Netscape bookmark file format understood by every major browser.
+pub fn write_bookmarks_html(output_path: &Path, nodes: &[BookmarkNode]) -> Result<()> {
+    let mut output = File::create(output_path)?;
+    writeln!(output, "<!DOCTYPE NETSCAPE-Bookmark-file-1>")?;
+    writeln!(
+        output,
+        "<META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">"
+    )?;
+    writeln!(output, "<TITLE>Bookmarks</TITLE>")?;
+    writeln!(output, "<H1>Bookmarks</H1>")?;
+    writeln!(output, "<DL><p>")?;
+    write_bookmark_nodes(&mut output, nodes, 1)?;
+    writeln!(output, "</DL><p>")?;
+
+    Ok(())
+}
+
+fn write_bookmark_nodes(output: &mut File, nodes: &[BookmarkNode], depth: usize) -> Result<()> {
+    let indent = "    ".repeat(depth);
+    for node in nodes {
+        match node {
+            BookmarkNode::Link { title, url } => {
+                let title = title.as_deref().unwrap_or(url);
+                writeln!(
+                    output,
+                    "{}<DT><A HREF=\"{}\">{}</A>",
+                    indent,
+                    escape_html(url),
+                    escape_html(title)
+                )?;
+            }
+            BookmarkNode::Folder { title, children } => {
+                let title = title.as_deref().unwrap_or("Bookmarks");
+                writeln!(output, "{}<DT><H3>{}</H3>", indent, escape_html(title))?;
+                writeln!(output, "{}<DL><p>", indent)?;
+                write_bookmark_nodes(output, children, depth + 1)?;
+                writeln!(output, "{}</DL><p>", indent)?;
+            }
+            BookmarkNode::Separator => writeln!(output, "{}<HR>", indent)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,318 @@
+use crate::backup::bookmarks::{write_bookmarks_html, BookmarkNode};
+use crate::backup::sqlite;
+use anyhow::{anyhow, Result};
+use log::info;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// User data directories, relative to the platform config dir, of supported browsers.
+const BROWSER_DIRS: [(&str, &str); 4] = [
+    ("google-chrome", "google-chrome"),
+    ("chromium", "chromium"),
+    ("brave", "BraveSoftware/Brave-Browser"),
+    ("edge", "microsoft-edge"),
+];
+
+/// A browser profile found by auto-detection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChromiumProfile {
+    pub browser: String,
+    pub name: String,
+    pub dir: PathBuf,
+}
+
+/// Finds Chrome, Chromium, Brave, and Edge profiles in the platform config directory.
+pub fn detect_profiles() -> Vec<ChromiumProfile> {
+    dirs::config_dir()
+        .map(|config_dir| detect_profiles_in(&config_dir))
+        .unwrap_or_default()
+}
+
+/// Finds browser profiles below `config_dir`, such as `google-chrome/Default`.
+pub fn detect_profiles_in(config_dir: &Path) -> Vec<ChromiumProfile> {
+    let mut profiles = Vec::new();
+
+    for (browser, relative_dir) in BROWSER_DIRS {
+        let user_data_dir = config_dir.join(relative_dir);
+        let Ok(entries) = std::fs::read_dir(&user_data_dir) else {
+            continue;
+        };
+
+        let mut found: Vec<_> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| is_profile_dir(path))
+            .collect();
+        found.sort();
+
+        for dir in found {
+            let name = dir
+                .file_name()
+                .and_then(|name| name.to_str())
+                .unwrap_or_default()
+                .to_string();
+            profiles.push(ChromiumProfile {
+                browser: browser.to_string(),
+                name,
+                dir,
+            });
+        }
+    }
+
+    profiles
+}
+
+fn is_profile_dir(path: &Path) -> bool {
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    (name == "Default" || name.starts_with("Profile ")) && path.join("Preferences").is_file()
+}
+
+/// Backs up the configured profile, or every detected profile when `profile_dir` is empty.
+pub async fn export_chromium_source(profile_dir: &Path, temp_dir: &Path) -> Result<()> {
+    if !profile_dir.as_os_str().is_empty() {
+        return export_chromium_data(profile_dir, temp_dir).await;
+    }
+
+    let profiles = detect_profiles();
+    if profiles.is_empty() {
+        return Err(anyhow!("No Chromium-family browser profiles found"));
+    }
+
+    for profile in profiles {
+        info!(
+            "Backing up {} profile {} from {:?}",
+            profile.browser, profile.name, profile.dir
+        );
+        let dest = temp_dir.join(format!("{}_{}", profile.browser, profile.name));
+        export_chromium_data(&profile.dir, &dest).await?;
+    }
+
+    Ok(())
+}
+
+pub async fn export_chromium_data(profile_dir: &Path, temp_dir: &Path) -> Result<()> {
+    if !profile_dir.exists() {
+        return Err(anyhow!(
+            "Chromium profile directory does not exist: {:?}",
+            profile_dir
+        ));
+    }
+
+    fs::create_dir_all(temp_dir).await?;
+
+    export_bookmarks(profile_dir, temp_dir).await?;
+
+    let login_data = profile_dir.join("Login Data");
+    if login_data.exists() {
+        let dest = temp_dir.join("Login Data");
+        let source_size = fs::metadata(&login_data).await?.len();
+        info!(
+            "Snapshotting Chromium file Login Data ({} bytes)",
+            source_size
+        );
+        tokio::task::spawn_blocking(move || sqlite::snapshot_sqlite(&login_data, &dest)).await??;
+    } else {
+        info!("Chromium file Login Data not found, skipping");
+    }
+
+    let preferences = profile_dir.join("Preferences");
+    if preferences.exists() {
+        let dest_size = fs::copy(&preferences, temp_dir.join("Preferences")).await?;
+        info!("Copied Chromium file Preferences ({} bytes)", dest_size);
+    } else {
+        info!("Chromium file Preferences not found, skipping");
+    }
+
+    Ok(())
+}
+
+async fn export_bookmarks(profile_dir: &Path, temp_dir: &Path) -> Result<()> {
+    let bookmarks_src = profile_dir.join("Bookmarks");
+    if !bookmarks_src.exists() {
+        info!("Chromium file Bookmarks not found, skipping bookmark export");
+        return Ok(());
+    }
+
+    let content = fs::read_to_string(&bookmarks_src).await?;
+    let bookmarks_path = temp_dir.join("bookmarks.html");
+    let bookmarks_path_for_export = bookmarks_path.clone();
+    tokio::task::spawn_blocking(move || {
+        export_bookmarks_html(&content, &bookmarks_path_for_export)
+    })
+    .await??;
+
+    let bookmarks_size = fs::metadata(&bookmarks_path).await?.len();
+    info!(
+        "Exported Chromium bookmarks to bookmarks.html ({} bytes)",
+        bookmarks_size
+    );
+
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct BookmarksFile {
+    roots: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct ChromiumBookmark {
+    #[serde(rename = "type")]
+    item_type: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    children: Vec<ChromiumBookmark>,
+}
+
+fn export_bookmarks_html(content: &str, output_path: &Path) -> Result<()> {
+    let bookmarks: BookmarksFile = serde_json::from_str(content)?;
+
+    // Roots are objects keyed by location; non-node entries such as sync metadata are skipped
+    let mut nodes = Vec::new();
+    for key in ["bookmark_bar", "other", "synced"] {
+        let Some(root) = bookmarks.roots.get(key) else {
+            continue;
+        };
+        if let Ok(root) = serde_json::from_value::<ChromiumBookmark>(root.clone()) {
+            nodes.extend(convert_bookmark(root));
+        }
+    }
+
+    write_bookmarks_html(output_path, &nodes)
+}
+
+fn convert_bookmark(bookmark: ChromiumBookmark) -> Option<BookmarkNode> {
+    match bookmark.item_type.as_str() {
+        "url" => bookmark.url.map(|url| BookmarkNode::Link {
+            title: bookmark.name,
+            url,
+        }),
+        "folder" => Some(BookmarkNode::Folder {
+            title: bookmark.name,
+            children: bookmark
+                .children
+                .into_iter()
+                .filter_map(convert_bookmark)
+                .collect(),
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::Connection;
+
+    const BOOKMARKS: &str = r#"{
+        "checksum": "abc",
+        "roots": {
+            "bookmark_bar": {
+                "type": "folder",
+                "name": "Bookmarks bar",
+                "children": [
+                    { "type": "url", "name": "Example & Co", "url": "https://example.com/?a=1&b=2" },
+                    {
+                        "type": "folder",
+                        "name": "Work",
+                        "children": [
+                            { "type": "url", "name": "Docs", "url": "https://docs.example.com" }
+                        ]
+                    }
+                ]
+            },
+            "other": { "type": "folder", "name": "Other bookmarks", "children": [] },
+            "synced": { "type": "folder", "name": "Mobile bookmarks", "children": [] }
+        },
+        "version": 1
+    }"#;
+
+    #[test]
+    fn test_export_bookmarks_html() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output = temp_dir.path().join("bookmarks.html");
+
+        export_bookmarks_html(BOOKMARKS, &output).unwrap();
+
+        let html = std::fs::read_to_string(&output).unwrap();
+        assert!(html.starts_with("<!DOCTYPE NETSCAPE-Bookmark-file-1>"));
+        assert!(html.contains("    <DT><H3>Bookmarks bar</H3>"));
+        assert!(html.contains(
+            "        <DT><A HREF=\"https://example.com/?a=1&amp;b=2\">Example &amp; Co</A>"
+        ));
+        assert!(html.contains("            <DT><A HREF=\"https://docs.example.com\">Docs</A>"));
+        assert!(html.contains("<DT><H3>Mobile bookmarks</H3>"));
+    }
+
+    #[test]
+    fn test_detect_profiles_in() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_dir = temp_dir.path();
+
+        for profile in [
+            "google-chrome/Default",
+            "google-chrome/Profile 1",
+            "BraveSoftware/Brave-Browser/Default",
+        ] {
+            let dir = config_dir.join(profile);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("Preferences"), "{}").unwrap();
+        }
+        // Not a profile: no Preferences file
+        std::fs::create_dir_all(config_dir.join("chromium/Default")).unwrap();
+        // Not a profile: system directory
+        std::fs::create_dir_all(config_dir.join("google-chrome/Crashpad")).unwrap();
+
+        let profiles = detect_profiles_in(config_dir);
+        let names: Vec<_> = profiles
+            .iter()
+            .map(|profile| format!("{}/{}", profile.browser, profile.name))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "google-chrome/Default",
+                "google-chrome/Profile 1",
+                "brave/Default"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_chromium_data_snapshots_login_data() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let profile_dir = temp_dir.path().join("Default");
+        let staging_dir = temp_dir.path().join("staging");
+        std::fs::create_dir_all(&profile_dir).unwrap();
+        std::fs::write(profile_dir.join("Bookmarks"), BOOKMARKS).unwrap();
+        std::fs::write(profile_dir.join("Preferences"), "{\"profile\":{}}").unwrap();
+
+        let connection = Connection::open(profile_dir.join("Login Data")).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE logins (origin_url TEXT, username_value TEXT);
+                 INSERT INTO logins VALUES ('https://example.com', 'alice');",
+            )
+            .unwrap();
+        drop(connection);
+
+        export_chromium_data(&profile_dir, &staging_dir)
+            .await
+            .unwrap();
+
+        assert!(staging_dir.join("bookmarks.html").exists());
+        assert!(staging_dir.join("Preferences").exists());
+        let snapshot = Connection::open(staging_dir.join("Login Data")).unwrap();
+        let username: String = snapshot
+            .query_row("SELECT username_value FROM logins", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(username, "alice");
+    }
+}
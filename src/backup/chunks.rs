@@ -0,0 +1,542 @@
+use crate::backup::incremental::EntryKind;
+use crate::backup::{compress, pins, retention};
+use crate::crypto::encrypt;
+use crate::models::backup_file::{BackupName, SourceType};
+use crate::models::config::RetentionPolicy;
+use anyhow::{anyhow, Result};
+use fastcdc::v2020::StreamCDC;
+use hmac::{Hmac, Mac};
+use log::info;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashSet;
+use std::fs::{File, Metadata};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+/// Directory under the data directory that holds the chunk repository.
+pub const REPOSITORY_DIR: &str = "repository";
+const CHUNKS_DIR: &str = "chunks";
+const SNAPSHOTS_DIR: &str = "snapshots";
+pub const SNAPSHOT_EXTENSION: &str = "snapshot";
+
+const MIN_CHUNK_SIZE: u32 = 16 * 1024;
+const AVG_CHUNK_SIZE: u32 = 64 * 1024;
+const MAX_CHUNK_SIZE: u32 = 256 * 1024;
+
+/// Encrypted manifest of one backup, `snapshots/<Source>_<timestamp>.snapshot`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub entries: Vec<SnapshotEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotEntry {
+    pub name: String,
+    pub kind: EntryKind,
+    pub mode: u32,
+    pub mtime_ns: u64,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>, // Link target of symlinks
+    /// Ids of the chunks that make up a file's content, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<String>,
+}
+
+/// Counts reported after a snapshot is written.
+#[derive(Debug, Default)]
+pub struct SnapshotStats {
+    pub new_chunks: u64,
+    pub reused_chunks: u64,
+    pub new_bytes: u64,
+}
+
+/// Content-addressed store of encrypted chunks.
+///
+/// Chunk ids are HMAC-SHA256 of the plaintext under a key derived from the encryption key, so
+/// identical content is stored once without ids revealing plain content hashes.
+pub struct Repository {
+    root: PathBuf,
+    key: [u8; 32],
+    id_key: [u8; 32],
+}
+
+impl Repository {
+    /// Opens (creating if needed) the repository in `data_dir`.
+    pub fn open(data_dir: &Path, key: &[u8; 32]) -> Result<Self> {
+        let root = data_dir.join(REPOSITORY_DIR);
+        std::fs::create_dir_all(root.join(CHUNKS_DIR))?;
+        std::fs::create_dir_all(root.join(SNAPSHOTS_DIR))?;
+        Ok(Self::at(root, key))
+    }
+
+    /// Handle on the repository in `data_dir` without creating it, for read-only use.
+    pub fn existing(data_dir: &Path, key: &[u8; 32]) -> Self {
+        Self::at(data_dir.join(REPOSITORY_DIR), key)
+    }
+
+    fn at(root: PathBuf, key: &[u8; 32]) -> Self {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC takes any key size");
+        mac.update(b"briefcase chunk id");
+        Self {
+            root,
+            key: *key,
+            id_key: mac.finalize().into_bytes().into(),
+        }
+    }
+
+    fn chunk_id(&self, data: &[u8]) -> String {
+        let mut mac =
+            <Hmac<Sha256> as Mac>::new_from_slice(&self.id_key).expect("HMAC takes any key size");
+        mac.update(data);
+        format!("{:x}", mac.finalize().into_bytes())
+    }
+
+    fn chunk_path(&self, id: &str) -> PathBuf {
+        self.root.join(CHUNKS_DIR).join(&id[..2]).join(id)
+    }
+
+    pub fn snapshots_dir(&self) -> PathBuf {
+        self.root.join(SNAPSHOTS_DIR)
+    }
+
+    /// Chunks every entry under `roots`, stores chunks the repository does not have yet, then
+    /// writes the encrypted snapshot manifest as `filename`. Chunks are written first, so an
+    /// interrupted backup only leaves unreferenced chunks for garbage collection.
+    pub fn write_snapshot(
+        &self,
+        roots: &[(&Path, &str)],
+        filename: &str,
+    ) -> Result<(PathBuf, SnapshotStats)> {
+        let mut stats = SnapshotStats::default();
+        let mut entries = Vec::new();
+
+        for (dir, prefix) in roots {
+            for (path, name, metadata) in compress::collect_entries(dir, prefix)? {
+                let mut entry = SnapshotEntry {
+                    name,
+                    kind: EntryKind::File,
+                    mode: mode(&metadata),
+                    mtime_ns: mtime_ns(&metadata),
+                    size: metadata.len(),
+                    target: None,
+                    chunks: Vec::new(),
+                };
+                if metadata.is_dir() {
+                    entry.kind = EntryKind::Directory;
+                } else if metadata.file_type().is_symlink() {
+                    entry.kind = EntryKind::Symlink;
+                    entry.target = Some(std::fs::read_link(&path)?.to_string_lossy().into_owned());
+                } else {
+                    entry.chunks = self.store_file(&path, &mut stats)?;
+                }
+                entries.push(entry);
+            }
+        }
+
+        let manifest = Zeroizing::new(serde_json::to_vec(&Snapshot { entries })?);
+        let snapshot_path = self.snapshots_dir().join(filename);
+        encrypt::encrypt_to_file_with_derived_key(&manifest, &snapshot_path, &self.key)?;
+
+        Ok((snapshot_path, stats))
+    }
+
+    fn store_file(&self, path: &Path, stats: &mut SnapshotStats) -> Result<Vec<String>> {
+        let mut ids = Vec::new();
+        let chunker = StreamCDC::new(
+            File::open(path)?,
+            MIN_CHUNK_SIZE,
+            AVG_CHUNK_SIZE,
+            MAX_CHUNK_SIZE,
+        );
+        for chunk in chunker {
+            let chunk = chunk.map_err(|e| anyhow!("Failed to chunk {:?}: {}", path, e))?;
+            let data = Zeroizing::new(chunk.data);
+            let id = self.chunk_id(&data);
+            let chunk_path = self.chunk_path(&id);
+            if chunk_path.exists() {
+                stats.reused_chunks += 1;
+            } else {
+                std::fs::create_dir_all(chunk_path.parent().unwrap_or(&self.root))?;
+                encrypt::encrypt_to_file_with_derived_key(&data, &chunk_path, &self.key)?;
+                stats.new_chunks += 1;
+                stats.new_bytes += data.len() as u64;
+            }
+            ids.push(id);
+        }
+        Ok(ids)
+    }
+
+    fn read_chunk(&self, id: &str) -> Result<Zeroizing<Vec<u8>>> {
+        let data = encrypt::decrypt_from_file_with_derived_key(&self.chunk_path(id), &self.key)
+            .map_err(|e| anyhow!("Failed to read chunk {}: {}", id, e))?;
+        if self.chunk_id(&data) != id {
+            return Err(anyhow!("Chunk {} does not match its id", id));
+        }
+        Ok(data)
+    }
+
+    pub fn read_snapshot(&self, path: &Path) -> Result<Snapshot> {
+        let manifest = encrypt::decrypt_from_file_with_derived_key(path, &self.key)?;
+        Ok(serde_json::from_slice(&manifest)?)
+    }
+
+    /// Applies the source's retention policy to the snapshots `host` took of it, then removes
+    /// chunks no remaining snapshot references.
+    pub fn enforce_retention(
+        &self,
+        source_type: SourceType,
+        host: &str,
+        policy: &RetentionPolicy,
+    ) -> Result<()> {
+        for path in self.expired_snapshots(source_type, host, policy, false)? {
+            std::fs::remove_file(path)?;
+        }
+
+        let removed = self.collect_garbage()?;
+        if removed > 0 {
+            info!("Removed {} unreferenced chunks", removed);
+        }
+        Ok(())
+    }
+
+    /// The unpinned snapshots `host` took of the source that the policy does not keep, counting one
+    /// more snapshot taken now when `pending` is set.
+    ///
+    /// Snapshots share chunks, so they have no size of their own and `max_total_size` does not
+    /// apply to them.
+    pub fn expired_snapshots(
+        &self,
+        source_type: SourceType,
+        host: &str,
+        policy: &RetentionPolicy,
+        pending: bool,
+    ) -> Result<Vec<PathBuf>> {
+        let snapshots = if self.snapshots_dir().exists() {
+            self.list_snapshots()?
+        } else {
+            Vec::new()
+        };
+        let mut chains: Vec<Vec<retention::Archive>> = snapshots
+            .into_iter()
+            .filter_map(|path| {
+                let name = BackupName::parse(path.file_name()?.to_str()?)?;
+                (name.source_type == source_type && name.is_from_host(host)).then(|| {
+                    vec![retention::Archive {
+                        pinned: pins::is_pinned(&path),
+                        path,
+                        timestamp: name.timestamp,
+                        size: 0,
+                    }]
+                })
+            })
+            .collect();
+
+        let now = chrono::Local::now().naive_local();
+        if pending {
+            // The newest snapshot is never removed, so the placeholder is never returned
+            chains.push(vec![retention::Archive {
+                path: PathBuf::new(),
+                timestamp: now,
+                size: 0,
+                pinned: false,
+            }]);
+        }
+        Ok(retention::expired(
+            chains,
+            &RetentionPolicy {
+                max_total_size: None,
+                ..policy.clone()
+            },
+            now,
+        ))
+    }
+
+    /// Deletes chunks that no snapshot references. Every snapshot must be readable; otherwise
+    /// nothing is deleted, since the unreadable snapshot's chunks cannot be told apart.
+    pub fn collect_garbage(&self) -> Result<usize> {
+        let mut referenced = HashSet::new();
+        for path in self.list_snapshots()? {
+            let snapshot = self.read_snapshot(&path).map_err(|e| {
+                anyhow!(
+                    "Not collecting garbage, snapshot {:?} is unreadable: {}",
+                    path,
+                    e
+                )
+            })?;
+            referenced.extend(snapshot.entries.into_iter().flat_map(|entry| entry.chunks));
+        }
+
+        let mut removed = 0;
+        for bucket in std::fs::read_dir(self.root.join(CHUNKS_DIR))? {
+            let bucket = bucket?.path();
+            if !bucket.is_dir() {
+                continue;
+            }
+            for chunk in std::fs::read_dir(&bucket)? {
+                let chunk = chunk?;
+                if !referenced.contains(chunk.file_name().to_string_lossy().as_ref()) {
+                    std::fs::remove_file(chunk.path())?;
+                    removed += 1;
+                }
+            }
+            if std::fs::read_dir(&bucket)?.next().is_none() {
+                std::fs::remove_dir(&bucket)?;
+            }
+        }
+        Ok(removed)
+    }
+
+    fn list_snapshots(&self) -> Result<Vec<PathBuf>> {
+        Ok(std::fs::read_dir(self.snapshots_dir())?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension().and_then(|ext| ext.to_str()) == Some(SNAPSHOT_EXTENSION)
+            })
+            .collect())
+    }
+}
+
+/// Directory holding the snapshot manifests of the repository in `data_dir`.
+pub fn snapshots_dir(data_dir: &Path) -> PathBuf {
+    data_dir.join(REPOSITORY_DIR).join(SNAPSHOTS_DIR)
+}
+
+/// Whether `path` is a snapshot manifest rather than a 7z archive.
+pub fn is_snapshot(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()) == Some(SNAPSHOT_EXTENSION)
+}
+
+/// Restores a snapshot into `output_dir`, reading chunks from the repository that contains it.
+pub fn restore_snapshot(snapshot_path: &Path, output_dir: &Path, key: &[u8; 32]) -> Result<()> {
+    let root = snapshot_path
+        .parent()
+        .and_then(Path::parent)
+        .ok_or_else(|| anyhow!("Snapshot {:?} is not inside a repository", snapshot_path))?;
+    let repository = Repository::at(root.to_path_buf(), key);
+    let snapshot = repository.read_snapshot(snapshot_path)?;
+
+    std::fs::create_dir_all(output_dir)?;
+    let mut directories = Vec::new();
+    for entry in &snapshot.entries {
+        let path = compress::output_path(output_dir, &entry.name)?;
+        let modified = UNIX_EPOCH + Duration::from_nanos(entry.mtime_ns);
+        if entry.kind == EntryKind::Directory {
+            std::fs::create_dir_all(&path)?;
+            directories.push((path, Some(entry.mode), Some(modified)));
+            continue;
+        }
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        match std::fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(&path)?,
+            Ok(_) => std::fs::remove_file(&path)?,
+            Err(_) => {}
+        }
+
+        if entry.kind == EntryKind::Symlink {
+            let target = entry.target.as_deref().unwrap_or_default();
+            compress::create_symlink(target.as_bytes(), &path)?;
+            filetime::set_symlink_file_times(
+                &path,
+                filetime::FileTime::now(),
+                filetime::FileTime::from_system_time(modified),
+            )?;
+            continue;
+        }
+
+        let mut file = File::create(&path)?;
+        for id in &entry.chunks {
+            file.write_all(&repository.read_chunk(id)?)?;
+        }
+        drop(file);
+        compress::apply_metadata(&path, Some(entry.mode), Some(modified))?;
+    }
+
+    compress::apply_directory_metadata(directories)
+}
+
+fn mtime_ns(metadata: &Metadata) -> u64 {
+    metadata
+        .modified()
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos() as u64)
+}
+
+#[cfg(unix)]
+fn mode(metadata: &Metadata) -> u32 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.mode()
+}
+
+#[cfg(not(unix))]
+fn mode(_metadata: &Metadata) -> u32 {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    const KEY: [u8; 32] = [9u8; 32];
+
+    fn chunk_count(data_dir: &Path) -> usize {
+        std::fs::read_dir(data_dir.join(REPOSITORY_DIR).join(CHUNKS_DIR))
+            .unwrap()
+            .map(|bucket| std::fs::read_dir(bucket.unwrap().path()).unwrap().count())
+            .sum()
+    }
+
+    fn pseudo_random(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_snapshots_share_chunks_and_restore() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let source = temp_dir.path().join("source");
+        std::fs::create_dir_all(source.join("nested")).unwrap();
+        let big = pseudo_random(1024 * 1024, 1);
+        std::fs::write(source.join("big.bin"), &big).unwrap();
+        std::fs::write(source.join("nested").join("note.txt"), "note").unwrap();
+        std::fs::set_permissions(
+            source.join("nested").join("note.txt"),
+            std::fs::Permissions::from_mode(0o600),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink("big.bin", source.join("link")).unwrap();
+
+        let repository = Repository::open(&data_dir, &KEY).unwrap();
+        let roots = [(source.as_path(), "sensitive")];
+        let (first, stats) = repository
+            .write_snapshot(&roots, "Folder_2026-01-01_00-00-00.snapshot")
+            .unwrap();
+        assert!(stats.new_chunks > 4);
+        assert_eq!(stats.reused_chunks, 0);
+        let chunks_after_first = chunk_count(&data_dir);
+
+        // A small edit in the middle of the big file only adds a few chunks
+        let mut edited = big.clone();
+        edited[512 * 1024] ^= 0xff;
+        std::fs::write(source.join("big.bin"), &edited).unwrap();
+        let (second, stats) = repository
+            .write_snapshot(&roots, "Folder_2026-01-02_00-00-00.snapshot")
+            .unwrap();
+        assert!(stats.new_chunks <= 2);
+        assert!(stats.reused_chunks > 2);
+        assert!(chunk_count(&data_dir) <= chunks_after_first + 2);
+
+        let output = temp_dir.path().join("first");
+        restore_snapshot(&first, &output, &KEY).unwrap();
+        assert_eq!(
+            std::fs::read(output.join("sensitive").join("big.bin")).unwrap(),
+            big
+        );
+        let note = output.join("sensitive").join("nested").join("note.txt");
+        assert_eq!(std::fs::read_to_string(&note).unwrap(), "note");
+        assert_eq!(
+            std::fs::metadata(&note).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        assert_eq!(
+            std::fs::read_link(output.join("sensitive").join("link")).unwrap(),
+            PathBuf::from("big.bin")
+        );
+
+        let output = temp_dir.path().join("second");
+        restore_snapshot(&second, &output, &KEY).unwrap();
+        assert_eq!(
+            std::fs::read(output.join("sensitive").join("big.bin")).unwrap(),
+            edited
+        );
+
+        // Chunk files hold ciphertext only
+        for bucket in std::fs::read_dir(data_dir.join(REPOSITORY_DIR).join(CHUNKS_DIR)).unwrap() {
+            for chunk in std::fs::read_dir(bucket.unwrap().path()).unwrap() {
+                let content = std::fs::read(chunk.unwrap().path()).unwrap();
+                assert!(!content.windows(32).any(|window| window == &big[..32]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_retention_collects_unreferenced_chunks() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let source = temp_dir.path().join("source");
+        std::fs::create_dir_all(&source).unwrap();
+        let repository = Repository::open(&data_dir, &KEY).unwrap();
+        let roots = [(source.as_path(), "")];
+
+        for (day, seed) in [(1, 1), (2, 2), (3, 3)] {
+            std::fs::write(source.join("data.bin"), pseudo_random(100_000, seed)).unwrap();
+            repository
+                .write_snapshot(&roots, &format!("Files_2026-01-0{}_00-00-00.snapshot", day))
+                .unwrap();
+        }
+        let before = chunk_count(&data_dir);
+
+        repository
+            .enforce_retention(SourceType::Files, "laptop", &RetentionPolicy::keep_last(1))
+            .unwrap();
+
+        let remaining: Vec<_> = std::fs::read_dir(repository.snapshots_dir())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        assert_eq!(remaining, vec!["Files_2026-01-03_00-00-00.snapshot"]);
+        assert!(chunk_count(&data_dir) < before);
+
+        let output = temp_dir.path().join("output");
+        restore_snapshot(
+            &repository
+                .snapshots_dir()
+                .join("Files_2026-01-03_00-00-00.snapshot"),
+            &output,
+            &KEY,
+        )
+        .unwrap();
+        assert_eq!(
+            std::fs::read(output.join("data.bin")).unwrap(),
+            pseudo_random(100_000, 3)
+        );
+    }
+
+    #[test]
+    fn test_garbage_collection_refuses_with_unreadable_snapshot() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let source = temp_dir.path().join("source");
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::write(source.join("a.txt"), "a").unwrap();
+
+        let repository = Repository::open(&data_dir, &KEY).unwrap();
+        repository
+            .write_snapshot(
+                &[(source.as_path(), "")],
+                "Files_2026-01-01_00-00-00.snapshot",
+            )
+            .unwrap();
+        let other = Repository::open(&data_dir, &[1u8; 32]).unwrap();
+
+        assert!(other.collect_garbage().is_err());
+        assert_eq!(chunk_count(&data_dir), 1);
+    }
+}
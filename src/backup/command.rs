@@ -0,0 +1,164 @@
+use crate::models::config::CommandOutput;
+use anyhow::{anyhow, Result};
+use log::info;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::fs;
+use tokio::process::Command;
+
+/// Directory inside the archive that holds captured command output.
+pub const ARCHIVE_DIR: &str = "commands";
+
+/// Runs each command through `sh -c` and stores its stdout as `commands/<output>`.
+///
+/// A command that exits non-zero or outlives its timeout fails the whole source.
+pub async fn capture_command_outputs(
+    commands: &[CommandOutput],
+    default_timeout_secs: u64,
+    temp_dir: &Path,
+) -> Result<()> {
+    if commands.is_empty() {
+        return Err(anyhow!("No commands configured for command source"));
+    }
+
+    let dest_dir = temp_dir.join(ARCHIVE_DIR);
+    fs::create_dir_all(&dest_dir).await?;
+
+    for command in commands {
+        let timeout = Duration::from_secs(command.timeout_secs.unwrap_or(default_timeout_secs));
+        info!("Running backup command for {}", command.output);
+
+        let stdout = run_command(&command.command, &[], timeout).await?;
+        fs::write(dest_dir.join(&command.output), &stdout).await?;
+        info!(
+            "Captured command output {} ({} bytes)",
+            command.output,
+            stdout.len()
+        );
+    }
+
+    Ok(())
+}
+
+/// Runs `command` through `sh -c` with extra environment variables, returning its stdout.
+pub(crate) async fn run_command(
+    command: &str,
+    envs: &[(&str, &OsStr)],
+    timeout: Duration,
+) -> Result<Vec<u8>> {
+    let mut child = Command::new("sh");
+    child.arg("-c").arg(command).envs(envs.iter().copied());
+    run_with_timeout(child, &format!("Command `{}`", command), timeout).await
+}
+
+/// Runs a prepared command without stdin, returning its stdout. The command is killed when it
+/// outlives `timeout`; `description` names it in errors.
+pub(crate) async fn run_with_timeout(
+    mut command: Command,
+    description: &str,
+    timeout: Duration,
+) -> Result<Vec<u8>> {
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| anyhow!("Failed to spawn {}: {}", description, e))?;
+
+    let output = tokio::time::timeout(timeout, child.wait_with_output())
+        .await
+        .map_err(|_| {
+            anyhow!(
+                "{} timed out after {} seconds",
+                description,
+                timeout.as_secs()
+            )
+        })??;
+
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(anyhow!(
+            "{} failed with {}: {}",
+            description,
+            output.status,
+            stderr.trim()
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(command: &str, output: &str) -> CommandOutput {
+        CommandOutput {
+            command: command.to_string(),
+            output: output.to_string(),
+            timeout_secs: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_capture_command_outputs_writes_stdout() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        capture_command_outputs(
+            &[
+                command("echo '0 3 * * * backup'", "crontab.txt"),
+                command("printf 'a\\nb' | tr a-z A-Z", "upper.txt"),
+            ],
+            10,
+            temp_dir.path(),
+        )
+        .await
+        .unwrap();
+
+        let dir = temp_dir.path().join(ARCHIVE_DIR);
+        assert_eq!(
+            std::fs::read_to_string(dir.join("crontab.txt")).unwrap(),
+            "0 3 * * * backup\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.join("upper.txt")).unwrap(),
+            "A\nB"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_capture_command_outputs_fails_on_non_zero_exit() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let error = capture_command_outputs(
+            &[command("echo 'not logged in' >&2; exit 3", "vault.json")],
+            10,
+            temp_dir.path(),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(error.to_string().contains("not logged in"));
+        assert!(!temp_dir
+            .path()
+            .join(ARCHIVE_DIR)
+            .join("vault.json")
+            .exists());
+    }
+
+    #[tokio::test]
+    async fn test_capture_command_outputs_times_out() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut slow = command("sleep 5", "slow.txt");
+        slow.timeout_secs = Some(1);
+
+        let error = capture_command_outputs(&[slow], 10, temp_dir.path())
+            .await
+            .unwrap_err();
+
+        assert!(error.to_string().contains("timed out"));
+    }
+}
@@ -1,71 +1,1191 @@
-use anyhow::Result;
+use crate::backup::sevenz;
+use crate::models::config::{ArchiveSettings, Compression, Container};
+use anyhow::{anyhow, Result};
+use log::warn;
 use sevenz_rust::*;
-use std::path::Path;
+use std::collections::HashMap;
+use std::fs::{File, Metadata};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
 
-/// Compresses a directory into a 7Zip archive with optional password protection.
+/// Set in 7z attributes when the high 16 bits carry a Unix `st_mode` (p7zip convention).
+const UNIX_EXTENSION: u32 = 0x8000;
+const DIRECTORY_ATTRIBUTE: u32 = 0x10;
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Largest amount of data packed into one solid block.
+const MAX_BLOCK_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+
+/// Compresses a directory into an archive in the container `settings` selects.
+///
+/// Entries keep their Unix mode bits and mtimes, and tar.zst entries their owner. Symlinks are stored as links (their target
+/// is the entry content) and are never followed; sockets, FIFOs, and devices are skipped.
+/// Archives are not password protected; backups are encrypted with AES-256-GCM afterwards.
 ///
 /// # Arguments
 /// * `input_dir` - Directory to compress
-/// * `output_file` - Path for the output 7Zip file
-/// * `password` - Optional password for AES encryption of the archive
+/// * `output_file` - Path for the output archive
+/// * `settings` - Container and compression, both recorded in the archive for extraction
 ///
 /// # Returns
 /// Returns `Ok(())` on success, or an error if compression fails
 pub fn compress_directory(
     input_dir: &Path,
     output_file: &Path,
-    _password: Option<&str>,
+    settings: ArchiveSettings,
 ) -> Result<()> {
-    let mut sz = SevenZWriter::create(output_file)?;
+    compress_to_writer(
+        &[(input_dir, "")],
+        &|_| true,
+        settings,
+        File::create(output_file)?,
+    )?;
+    Ok(())
+}
 
-    // Set compression options
-    // Note: 7Zip password protection is not used here since we rely on external AES-256-GCM encryption
-    // for stronger security and better compatibility
+/// Compresses several directories into `writer`, storing only entries whose archive name
+/// passes `include`, and returns the writer.
+///
+/// Each root is `(dir, prefix)`: the contents of `dir` are stored under `prefix/`, or at the top
+/// level when `prefix` is empty. Backups pass a [`crate::crypto::stream::Encryptor`], so the
+/// plaintext archive is encrypted as it is written and never held whole in memory.
+pub fn compress_to_writer<W: Write + Seek>(
+    roots: &[(&Path, &str)],
+    include: &dyn Fn(&str) -> bool,
+    settings: ArchiveSettings,
+    writer: W,
+) -> Result<W> {
+    match settings.container {
+        Container::SevenZ => compress_roots(roots, include, settings, writer),
+        Container::TarZst => compress_tar(roots, include, settings, writer),
+    }
+}
 
-    sz.push_source_path(input_dir, |_| true)?;
-    sz.finish()?;
+/// Whether a file name carries the extension of one of the archive containers.
+pub fn is_archive(filename: &str) -> bool {
+    archive_stem(filename).is_some()
+}
 
-    Ok(())
+/// A file name without its archive container extension.
+pub fn archive_stem(filename: &str) -> Option<&str> {
+    Container::ALL.into_iter().find_map(|container| {
+        filename
+            .strip_suffix(container.extension())
+            .and_then(|stem| stem.strip_suffix('.'))
+    })
+}
+
+fn compress_roots<W: Write + Seek>(
+    roots: &[(&Path, &str)],
+    include: &dyn Fn(&str) -> bool,
+    settings: ArchiveSettings,
+    writer: W,
+) -> Result<W> {
+    let mut sz = ArchiveWriter::new(writer, settings.compression, settings.level)?;
+
+    let mut entries = Vec::new();
+    let mut sources = Vec::new();
+    let mut block_size = 0;
+    for (dir, prefix) in roots {
+        for (path, name, metadata) in collect_entries(dir, prefix)? {
+            if !include(&name) {
+                continue;
+            }
+            let mut entry = SevenZArchiveEntry::new();
+            entry.name = name;
+            set_entry_metadata(&mut entry, &metadata);
+
+            if metadata.is_dir() {
+                entry.is_directory = true;
+                sz.push_entry(entry)?;
+                continue;
+            }
+
+            let source = if metadata.file_type().is_symlink() {
+                let target = std::fs::read_link(&path)?;
+                EntrySource::Bytes(Cursor::new(target.into_os_string().into_encoded_bytes()))
+            } else {
+                EntrySource::File(path, None)
+            };
+
+            if block_size + metadata.len() >= MAX_BLOCK_SIZE && !entries.is_empty() {
+                sz.push_block(std::mem::take(&mut entries), std::mem::take(&mut sources))?;
+                block_size = 0;
+            }
+            block_size += metadata.len();
+            entry.has_stream = true;
+            entries.push(entry);
+            sources.push(source);
+        }
+    }
+    if !entries.is_empty() {
+        sz.push_block(entries, sources)?;
+    }
+
+    sz.finish()
+}
+
+/// LZMA2 archives are written by sevenz-rust, store-only ones by [`sevenz`]. Zstandard is only
+/// offered through the tar.zst container, which every zstd-capable `tar` can read, rather than
+/// through a 7z coder that mainline 7-Zip lacks.
+enum ArchiveWriter<W: Write + Seek> {
+    Lzma2(SevenZWriter<W>),
+    Store(sevenz::BlockWriter<W>),
+}
+
+impl<W: Write + Seek> ArchiveWriter<W> {
+    fn new(writer: W, compression: Compression, level: u32) -> Result<Self> {
+        Ok(match compression {
+            Compression::Lzma2 => {
+                let mut sz = SevenZWriter::new(writer)?;
+                sz.set_content_methods(vec![lzma::LZMA2Options::with_preset(level).into()]);
+                ArchiveWriter::Lzma2(sz)
+            }
+            Compression::Zstd => {
+                return Err(anyhow!(
+                    "Zstd compression needs the TarZst container; 7z archives use Lzma2 or Store"
+                ))
+            }
+            Compression::Store => ArchiveWriter::Store(sevenz::BlockWriter::new(writer)?),
+        })
+    }
+
+    fn push_entry(&mut self, entry: SevenZArchiveEntry) -> Result<()> {
+        match self {
+            ArchiveWriter::Lzma2(sz) => {
+                sz.push_archive_entry::<File>(entry, None)?;
+            }
+            ArchiveWriter::Store(writer) => writer.push_entry(entry),
+        }
+        Ok(())
+    }
+
+    fn push_block(
+        &mut self,
+        entries: Vec<SevenZArchiveEntry>,
+        sources: Vec<EntrySource>,
+    ) -> Result<()> {
+        match self {
+            ArchiveWriter::Lzma2(sz) => {
+                let sources = sources.into_iter().map(SourceReader::new).collect();
+                sz.push_archive_entries(entries, SeqReader::new(sources))?;
+            }
+            ArchiveWriter::Store(writer) => writer.push_block(entries, sources)?,
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<W> {
+        Ok(match self {
+            ArchiveWriter::Lzma2(sz) => sz.finish()?,
+            ArchiveWriter::Store(writer) => writer.finish()?,
+        })
+    }
+}
+
+/// Writes a Zstandard-compressed tar stream. Tar has no other compression here, so the level
+/// comes from `settings` only when its method is Zstd.
+fn compress_tar<W: Write>(
+    roots: &[(&Path, &str)],
+    include: &dyn Fn(&str) -> bool,
+    settings: ArchiveSettings,
+    writer: W,
+) -> Result<W> {
+    let level = match settings.compression {
+        Compression::Zstd => settings.level,
+        _ => Compression::Zstd.default_level(),
+    };
+    let mut builder = tar::Builder::new(zstd::Encoder::new(writer, level as i32)?);
+    let mut owner_names = OwnerNames::default();
+
+    for (dir, prefix) in roots {
+        for (path, name, metadata) in collect_entries(dir, prefix)? {
+            if !include(&name) {
+                continue;
+            }
+            let mut header = tar::Header::new_gnu();
+            header.set_mode(unix_mode(&metadata).unwrap_or(0o644) & 0o7777);
+            if let Ok(modified) = metadata.modified() {
+                let seconds = modified
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .map_or(0, |duration| duration.as_secs());
+                header.set_mtime(seconds);
+            }
+            owner_names.set_owner(&mut header, &metadata)?;
+
+            if metadata.is_dir() {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_size(0);
+                builder.append_data(&mut header, &name, std::io::empty())?;
+            } else if metadata.file_type().is_symlink() {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_size(0);
+                builder.append_link(&mut header, &name, std::fs::read_link(&path)?)?;
+            } else {
+                let file = File::open(&path)?;
+                let size = file.metadata()?.len();
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_size(size);
+                let mut content = SizedContent {
+                    file,
+                    path: &path,
+                    remaining: size,
+                    grew: false,
+                };
+                builder.append_data(&mut header, &name, &mut content)?;
+                if content.grew {
+                    warn!(
+                        "{:?} grew while being archived; only its first {} bytes were stored",
+                        path, size
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(builder.into_inner()?.finish()?)
+}
+
+/// User and group names looked up while writing tar headers, cached by id.
+#[derive(Default)]
+struct OwnerNames {
+    users: HashMap<u32, Option<String>>,
+    groups: HashMap<u32, Option<String>>,
+}
+
+impl OwnerNames {
+    /// Records the owner of an entry by id and, where they resolve, by user and group name.
+    #[cfg(unix)]
+    fn set_owner(&mut self, header: &mut tar::Header, metadata: &Metadata) -> Result<()> {
+        use nix::unistd::{Gid, Group, Uid, User};
+        use std::os::unix::fs::MetadataExt;
+
+        let (uid, gid) = (metadata.uid(), metadata.gid());
+        header.set_uid(u64::from(uid));
+        header.set_gid(u64::from(gid));
+        let user = self.users.entry(uid).or_insert_with(|| {
+            User::from_uid(Uid::from_raw(uid))
+                .ok()
+                .flatten()
+                .map(|user| user.name)
+        });
+        if let Some(user) = user {
+            header.set_username(user)?;
+        }
+        let group = self.groups.entry(gid).or_insert_with(|| {
+            Group::from_gid(Gid::from_raw(gid))
+                .ok()
+                .flatten()
+                .map(|group| group.name)
+        });
+        if let Some(group) = group {
+            header.set_groupname(group)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    fn set_owner(&mut self, _header: &mut tar::Header, _metadata: &Metadata) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The `size` bytes of a file that a tar header already promised. A file that shrinks in the
+/// meantime fails the archive instead of being padded, and one that grows is cut off at `size`.
+struct SizedContent<'a> {
+    file: File,
+    path: &'a Path,
+    remaining: u64,
+    grew: bool,
+}
+
+impl Read for SizedContent<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.remaining == 0 {
+            self.grew = self.grew || self.file.read(&mut [0u8])? > 0;
+            return Ok(0);
+        }
+        let limit = buf
+            .len()
+            .min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let read = self.file.read(&mut buf[..limit])?;
+        if read == 0 && limit > 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                format!(
+                    "{:?} shrank by {} bytes while being archived",
+                    self.path, self.remaining
+                ),
+            ));
+        }
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+/// Content of a file entry, opened only when the writer reaches it.
+enum EntrySource {
+    File(PathBuf, Option<File>),
+    Bytes(Cursor<Vec<u8>>),
+}
+
+impl Read for EntrySource {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            EntrySource::File(path, file) => {
+                if file.is_none() {
+                    *file = Some(File::open(path.as_path())?);
+                }
+                file.as_mut().map_or(Ok(0), |file| file.read(buf))
+            }
+            EntrySource::Bytes(bytes) => bytes.read(buf),
+        }
+    }
 }
 
-/// Extracts a 7Zip archive to a directory.
+/// Walks `input_dir` without following symlinks, returning entries named relative to it and
+/// placed under `prefix`. A non-empty prefix gets its own entry carrying `input_dir`'s metadata.
+pub(crate) fn collect_entries(
+    input_dir: &Path,
+    prefix: &str,
+) -> Result<Vec<(PathBuf, String, Metadata)>> {
+    let mut entries = Vec::new();
+    let mut stack = vec![input_dir.to_path_buf()];
+    if !prefix.is_empty() {
+        entries.push((
+            input_dir.to_path_buf(),
+            prefix.to_string(),
+            std::fs::metadata(input_dir)?,
+        ));
+    }
+
+    while let Some(dir) = stack.pop() {
+        let mut children: Vec<_> = std::fs::read_dir(&dir)?.collect::<Result<_, _>>()?;
+        children.sort_by_key(|entry| entry.file_name());
+
+        for child in children {
+            let path = child.path();
+            let metadata = std::fs::symlink_metadata(&path)?;
+            let file_type = metadata.file_type();
+            if !(file_type.is_dir() || file_type.is_file() || file_type.is_symlink()) {
+                warn!("Skipping special file {:?} (socket, FIFO, or device)", path);
+                continue;
+            }
+
+            let relative = path
+                .strip_prefix(input_dir)?
+                .to_string_lossy()
+                .replace('\\', "/");
+            let name = if prefix.is_empty() {
+                relative
+            } else {
+                format!("{}/{}", prefix, relative)
+            };
+            if file_type.is_dir() {
+                stack.push(path.clone());
+            }
+            entries.push((path, name, metadata));
+        }
+    }
+
+    Ok(entries)
+}
+
+fn set_entry_metadata(entry: &mut SevenZArchiveEntry, metadata: &Metadata) {
+    if let Ok(modified) = metadata.modified() {
+        if let Ok(date) = nt_time::FileTime::try_from(modified) {
+            entry.last_modified_date = date;
+            entry.has_last_modified_date = true;
+        }
+    }
+
+    entry.has_windows_attributes = true;
+    entry.windows_attributes = unix_mode(metadata).map_or(0, |mode| UNIX_EXTENSION | (mode << 16))
+        | if metadata.is_dir() {
+            DIRECTORY_ATTRIBUTE
+        } else {
+            0
+        };
+}
+
+#[cfg(unix)]
+fn unix_mode(metadata: &Metadata) -> Option<u32> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.mode() & 0xffff)
+}
+
+#[cfg(not(unix))]
+fn unix_mode(_metadata: &Metadata) -> Option<u32> {
+    None
+}
+
+fn entry_unix_mode(entry: &SevenZArchiveEntry) -> Option<u32> {
+    (entry.has_windows_attributes && entry.windows_attributes & UNIX_EXTENSION != 0)
+        .then_some(entry.windows_attributes >> 16)
+}
+
+/// Zstandard frame magic number, which starts every tar.zst archive.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// The container of an archive, told apart by its leading bytes rather than its file name.
+pub fn detect_container(header: &[u8]) -> Container {
+    if header.starts_with(&ZSTD_MAGIC) {
+        Container::TarZst
+    } else {
+        Container::SevenZ
+    }
+}
+
+/// Extracts a 7Zip or tar.zst archive to a directory.
 ///
 /// Note: This function extracts archives created by compress_directory.
 /// Password-protected archives are not currently supported.
 ///
+/// Recorded Unix modes and mtimes are reapplied and symlinks are recreated as links. Owners
+/// recorded in tar.zst archives are restored when running as root.
+/// Directory metadata is applied last so read-only directories can still be filled.
+///
 /// # Arguments
-/// * `input_file` - Path to the 7Zip archive
+/// * `input_file` - Path to the archive
 /// * `output_dir` - Directory to extract files to
 ///
 /// # Returns
 /// Returns `Ok(())` on success, or an error if extraction fails
 pub fn extract_archive(input_file: &Path, output_dir: &Path) -> Result<()> {
-    use sevenz_rust::Password;
+    extract_from_reader(File::open(input_file)?, output_dir)
+}
+
+/// Extracts an archive read from `archive`, such as a backup being decrypted.
+pub fn extract_from_reader<R: Read + Seek>(archive: R, output_dir: &Path) -> Result<()> {
+    let mut directories = Vec::new();
+    extract_into(archive, output_dir, &|_| true, &mut directories)?;
+    apply_directory_metadata(directories)
+}
+
+/// Mode and mtime of an extracted directory, applied once everything inside it is written.
+pub type DirectoryMetadata = (PathBuf, Option<u32>, Option<SystemTime>);
 
-    let mut sz = SevenZReader::open(input_file, Password::empty())?;
+/// Extracts the entries of `archive` whose names pass `include` over `output_dir`, without
+/// applying directory metadata yet.
+///
+/// Existing files are replaced. Directory modes and mtimes are appended to `directories` so
+/// several archives can be layered before [`apply_directory_metadata`] runs.
+pub fn extract_into<R: Read + Seek>(
+    mut archive: R,
+    output_dir: &Path,
+    include: &dyn Fn(&str) -> bool,
+    directories: &mut Vec<DirectoryMetadata>,
+) -> Result<()> {
+    match probe(&mut archive)? {
+        (Container::SevenZ, len) => {
+            let reader = SevenZReader::new(archive, len, Password::empty())?;
+            extract_entries(reader, output_dir, include, directories)
+        }
+        (Container::TarZst, _) => extract_tar_entries(archive, output_dir, include, directories),
+    }
+}
 
+/// Reads the content of the file entry called `name` without extracting anything.
+pub fn read_entry<R: Read + Seek>(mut archive: R, name: &str) -> Result<Option<Vec<u8>>> {
+    let mut content = None;
+    match probe(&mut archive)? {
+        (Container::SevenZ, len) => {
+            SevenZReader::new(archive, len, Password::empty())?.for_each_entries(
+                |entry, reader| {
+                    if content.is_some() {
+                        return Ok(false);
+                    }
+                    if entry.name() == name && !entry.is_directory() {
+                        let mut buffer = Vec::new();
+                        reader.read_to_end(&mut buffer)?;
+                        content = Some(buffer);
+                        return Ok(false);
+                    }
+                    // Entries of a solid block must be read through to reach the next one
+                    std::io::copy(reader, &mut std::io::sink())?;
+                    Ok(true)
+                },
+            )?;
+        }
+        (Container::TarZst, _) => {
+            let mut archive = tar::Archive::new(zstd::Decoder::new(archive)?);
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                if entry.header().entry_type().is_file() && entry.path()? == Path::new(name) {
+                    let mut buffer = Vec::new();
+                    entry.read_to_end(&mut buffer)?;
+                    content = Some(buffer);
+                    break;
+                }
+            }
+        }
+    }
+    Ok(content)
+}
+
+/// The container of a seekable archive and its length, leaving it rewound.
+fn probe<R: Read + Seek>(archive: &mut R) -> Result<(Container, u64)> {
+    archive.rewind()?;
+    let mut header = Vec::with_capacity(ZSTD_MAGIC.len());
+    archive
+        .by_ref()
+        .take(ZSTD_MAGIC.len() as u64)
+        .read_to_end(&mut header)?;
+    let len = archive.seek(SeekFrom::End(0))?;
+    archive.rewind()?;
+    Ok((detect_container(&header), len))
+}
+
+/// Applies deferred directory metadata; later entries for the same path win.
+pub fn apply_directory_metadata(mut directories: Vec<DirectoryMetadata>) -> Result<()> {
+    // Deepest directories first, so a parent's mtime is not bumped by its children. The sort is
+    // stable, so repeated paths are applied in extraction order and the latest archive wins.
+    directories.sort_by(|a, b| b.0.cmp(&a.0));
+    for (path, mode, modified) in directories {
+        apply_metadata(&path, mode, modified)?;
+    }
+    Ok(())
+}
+
+fn extract_entries<R: Read + Seek>(
+    mut sz: SevenZReader<R>,
+    output_dir: &Path,
+    include: &dyn Fn(&str) -> bool,
+    directories: &mut Vec<DirectoryMetadata>,
+) -> Result<()> {
     // Create output directory if it doesn't exist
     std::fs::create_dir_all(output_dir)?;
 
+    let mut result = Ok(());
+
     // Extract all entries to the output directory
     sz.for_each_entries(|entry, reader| {
-        let entry_path = entry.name();
-        let output_path = output_dir.join(entry_path);
+        // Stopping only ends the current block, so later blocks see the error here
+        if result.is_err() {
+            return Ok(false);
+        }
+        if !include(entry.name()) {
+            // Entries of a solid block must be read through to reach the next one
+            std::io::copy(reader, &mut std::io::sink())?;
+            return Ok(true);
+        }
+        let info = EntryInfo {
+            name: entry.name(),
+            is_directory: entry.is_directory(),
+            mode: entry_unix_mode(entry),
+            modified: entry
+                .has_last_modified_date
+                .then(|| SystemTime::from(entry.last_modified_date())),
+            owner: None,
+        };
+        let outcome = extract_entry(output_dir, &info, reader, directories);
+        if let Err(error) = outcome {
+            result = Err(error);
+            return Ok(false);
+        }
+        Ok(true)
+    })?;
+    result
+}
+
+fn extract_tar_entries<R: Read>(
+    archive: R,
+    output_dir: &Path,
+    include: &dyn Fn(&str) -> bool,
+    directories: &mut Vec<DirectoryMetadata>,
+) -> Result<()> {
+    std::fs::create_dir_all(output_dir)?;
+
+    let mut archive = tar::Archive::new(zstd::Decoder::new(archive)?);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        if !include(&name) {
+            continue;
+        }
+        let header = entry.header();
+        let mut mode = header.mode()?;
+        let modified = header
+            .mtime()
+            .ok()
+            .map(|seconds| SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(seconds));
+        let entry_type = header.entry_type();
 
-        if entry.is_directory() {
-            std::fs::create_dir_all(&output_path)?;
+        let mut info = EntryInfo {
+            name: &name,
+            is_directory: entry_type.is_dir(),
+            mode: None,
+            modified,
+            owner: tar_owner(header),
+        };
+        if entry_type.is_symlink() {
+            let target = entry
+                .link_name_bytes()
+                .ok_or_else(|| anyhow!("Symlink {} has no target", name))?
+                .into_owned();
+            mode |= S_IFLNK;
+            info.mode = Some(mode);
+            extract_entry(output_dir, &info, &mut Cursor::new(target), directories)?;
+        } else if entry_type.is_dir() || entry_type.is_file() {
+            info.mode = Some(mode);
+            extract_entry(output_dir, &info, &mut entry, directories)?;
         } else {
-            if let Some(parent) = output_path.parent() {
-                std::fs::create_dir_all(parent)?;
-            }
+            warn!("Skipping unsupported tar entry {}", name);
+        }
+    }
+    Ok(())
+}
 
-            let mut buffer = Vec::new();
-            reader.read_to_end(&mut buffer)?;
-            std::fs::write(&output_path, buffer)?;
+/// What extraction needs to know about an entry, whichever container it came from.
+struct EntryInfo<'a> {
+    name: &'a str,
+    is_directory: bool,
+    mode: Option<u32>,
+    modified: Option<SystemTime>,
+    /// Recorded uid and gid; only tar archives carry them.
+    owner: Option<(u32, u32)>,
+}
+
+/// The owner a tar entry was archived with. User and group names that exist here win over the
+/// recorded ids, so ownership follows the account when ids differ between machines.
+#[cfg(unix)]
+fn tar_owner(header: &tar::Header) -> Option<(u32, u32)> {
+    use nix::unistd::{Group, User};
+
+    let uid = match header.username().ok().flatten() {
+        Some(name) => User::from_name(name)
+            .ok()
+            .flatten()
+            .map(|user| user.uid.as_raw()),
+        None => None,
+    }
+    .or_else(|| header.uid().ok().and_then(|uid| u32::try_from(uid).ok()))?;
+    let gid = match header.groupname().ok().flatten() {
+        Some(name) => Group::from_name(name)
+            .ok()
+            .flatten()
+            .map(|group| group.gid.as_raw()),
+        None => None,
+    }
+    .or_else(|| header.gid().ok().and_then(|gid| u32::try_from(gid).ok()))?;
+    Some((uid, gid))
+}
+
+#[cfg(not(unix))]
+fn tar_owner(_header: &tar::Header) -> Option<(u32, u32)> {
+    None
+}
+
+fn extract_entry(
+    output_dir: &Path,
+    info: &EntryInfo,
+    reader: &mut dyn Read,
+    directories: &mut Vec<DirectoryMetadata>,
+) -> Result<()> {
+    let output_path = output_path(output_dir, info.name)?;
+    let (mode, modified) = (info.mode, info.modified);
+
+    let existing = std::fs::symlink_metadata(&output_path);
+    if info.is_directory {
+        if existing.is_ok_and(|metadata| !metadata.is_dir()) {
+            std::fs::remove_file(&output_path)?;
         }
+        std::fs::create_dir_all(&output_path)?;
+        apply_owner(&output_path, info.owner)?;
+        directories.push((output_path, mode, modified));
+        return Ok(());
+    }
 
-        Ok(true)
-    })?;
+    if let Some(parent) = output_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let mut buffer = Vec::new();
+    reader.read_to_end(&mut buffer)?;
+
+    // Never write through a link left by an earlier archive, and let files replace directories
+    match existing {
+        Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(&output_path)?,
+        Ok(_) => std::fs::remove_file(&output_path)?,
+        Err(_) => {}
+    }
 
+    if mode.is_some_and(|mode| mode & S_IFMT == S_IFLNK) {
+        create_symlink(&buffer, &output_path)?;
+        apply_owner(&output_path, info.owner)?;
+        if let Some(modified) = modified {
+            filetime::set_symlink_file_times(
+                &output_path,
+                filetime::FileTime::now(),
+                filetime::FileTime::from_system_time(modified),
+            )?;
+        }
+        return Ok(());
+    }
+
+    std::fs::write(&output_path, buffer)?;
+    // Before the mode, since changing the owner clears setuid and setgid bits
+    apply_owner(&output_path, info.owner)?;
+    apply_metadata(&output_path, mode, modified)
+}
+
+/// Gives `path` its recorded owner, without following symlinks. Only root may change owners,
+/// so other users keep extracted files as their own.
+#[cfg(unix)]
+fn apply_owner(path: &Path, owner: Option<(u32, u32)>) -> Result<()> {
+    if let (Some((uid, gid)), true) = (owner, nix::unistd::geteuid().is_root()) {
+        std::os::unix::fs::lchown(path, Some(uid), Some(gid))?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn apply_owner(_path: &Path, _owner: Option<(u32, u32)>) -> Result<()> {
     Ok(())
 }
+
+/// Joins an entry name onto `output_dir`, rejecting names that escape it either directly or
+/// through a symlink extracted earlier.
+pub(crate) fn output_path(output_dir: &Path, name: &str) -> Result<PathBuf> {
+    let mut path = output_dir.to_path_buf();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => {
+                if path != output_dir
+                    && std::fs::symlink_metadata(&path).is_ok_and(|m| m.file_type().is_symlink())
+                {
+                    return Err(anyhow!("Archive entry {} is inside a symlink", name));
+                }
+                path.push(part);
+            }
+            Component::CurDir => {}
+            _ => return Err(anyhow!("Archive entry has an unsafe path: {}", name)),
+        }
+    }
+    Ok(path)
+}
+
+#[cfg(unix)]
+pub(crate) fn create_symlink(target: &[u8], link: &Path) -> Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    std::os::unix::fs::symlink(std::ffi::OsStr::from_bytes(target), link)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn create_symlink(_target: &[u8], link: &Path) -> Result<()> {
+    warn!(
+        "Skipping symlink {:?}; symlinks are not supported here",
+        link
+    );
+    Ok(())
+}
+
+pub(crate) fn apply_metadata(
+    path: &Path,
+    mode: Option<u32>,
+    modified: Option<SystemTime>,
+) -> Result<()> {
+    if let Some(modified) = modified {
+        filetime::set_file_mtime(path, filetime::FileTime::from_system_time(modified))?;
+    }
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode & 0o7777))?;
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn mode(path: &Path) -> u32 {
+        std::fs::symlink_metadata(path)
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o7777
+    }
+
+    fn mtime(path: &Path) -> i64 {
+        filetime::FileTime::from_last_modification_time(&std::fs::symlink_metadata(path).unwrap())
+            .unix_seconds()
+    }
+
+    #[test]
+    fn test_round_trip_keeps_modes_mtimes_and_symlinks() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let input = temp_dir.path().join("input");
+        let archive = temp_dir.path().join("archive.7z");
+        let output = temp_dir.path().join("output");
+
+        std::fs::create_dir_all(input.join("bin")).unwrap();
+        std::fs::create_dir_all(input.join("empty")).unwrap();
+        std::fs::write(input.join("bin").join("run.sh"), "#!/bin/sh\n").unwrap();
+        std::fs::write(input.join("secret.txt"), "secret").unwrap();
+        std::fs::write(input.join("empty.txt"), "").unwrap();
+        std::os::unix::fs::symlink("secret.txt", input.join("link")).unwrap();
+        std::os::unix::fs::symlink("..", input.join("bin").join("loop")).unwrap();
+        std::fs::set_permissions(
+            input.join("bin").join("run.sh"),
+            std::fs::Permissions::from_mode(0o750),
+        )
+        .unwrap();
+        std::fs::set_permissions(
+            input.join("secret.txt"),
+            std::fs::Permissions::from_mode(0o600),
+        )
+        .unwrap();
+        std::fs::set_permissions(input.join("bin"), std::fs::Permissions::from_mode(0o711))
+            .unwrap();
+        for path in [input.join("secret.txt"), input.join("bin")] {
+            filetime::set_file_mtime(&path, filetime::FileTime::from_unix_time(1_600_000_000, 0))
+                .unwrap();
+        }
+        let _listener = std::os::unix::net::UnixListener::bind(input.join("agent.sock")).unwrap();
+
+        compress_directory(&input, &archive, ArchiveSettings::default()).unwrap();
+        extract_archive(&archive, &output).unwrap();
+
+        assert_eq!(mode(&output.join("bin").join("run.sh")), 0o750);
+        assert_eq!(mode(&output.join("secret.txt")), 0o600);
+        assert_eq!(mode(&output.join("bin")), 0o711);
+        assert_eq!(mtime(&output.join("secret.txt")), 1_600_000_000);
+        assert_eq!(mtime(&output.join("bin")), 1_600_000_000);
+        assert_eq!(
+            std::fs::read_link(output.join("link")).unwrap(),
+            PathBuf::from("secret.txt")
+        );
+        assert_eq!(
+            std::fs::read_link(output.join("bin").join("loop")).unwrap(),
+            PathBuf::from("..")
+        );
+        assert!(output.join("empty").is_dir());
+        assert_eq!(std::fs::read(output.join("empty.txt")).unwrap(), b"");
+        assert!(!output.join("agent.sock").exists());
+    }
+
+    #[test]
+    fn test_compress_to_writer_places_roots_under_prefix() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let staging = temp_dir.path().join("staging");
+        let source = temp_dir.path().join("source");
+        let output = temp_dir.path().join("output");
+        std::fs::create_dir_all(&staging).unwrap();
+        std::fs::create_dir_all(source.join("nested")).unwrap();
+        std::fs::write(staging.join("manifest.txt"), "staged").unwrap();
+        std::fs::write(source.join("nested").join("key.pem"), "key").unwrap();
+        std::fs::set_permissions(&source, std::fs::Permissions::from_mode(0o750)).unwrap();
+        std::os::unix::fs::symlink("..", source.join("nested").join("parent")).unwrap();
+
+        let archive = compress_to_writer(
+            &[(&staging, ""), (&source, "sensitive")],
+            &|_| true,
+            ArchiveSettings::default(),
+            Cursor::new(Vec::new()),
+        )
+        .unwrap();
+        extract_from_reader(archive, &output).unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(output.join("manifest.txt")).unwrap(),
+            "staged"
+        );
+        assert_eq!(
+            std::fs::read_to_string(output.join("sensitive").join("nested").join("key.pem"))
+                .unwrap(),
+            "key"
+        );
+        assert_eq!(mode(&output.join("sensitive")), 0o750);
+        assert_eq!(
+            std::fs::read_link(output.join("sensitive").join("nested").join("parent")).unwrap(),
+            PathBuf::from("..")
+        );
+    }
+
+    #[test]
+    fn test_each_compression_method_round_trips_and_is_recorded() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let input = temp_dir.path().join("input");
+        std::fs::create_dir_all(input.join("nested").join("empty")).unwrap();
+        std::fs::write(input.join("notes.txt"), "notes ".repeat(1000)).unwrap();
+        std::fs::write(input.join("nested").join("blank.txt"), "").unwrap();
+        std::fs::set_permissions(
+            input.join("notes.txt"),
+            std::fs::Permissions::from_mode(0o600),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink("../notes.txt", input.join("nested").join("link")).unwrap();
+
+        for (compression, level, method) in [
+            (Compression::Lzma2, 0, SevenZMethod::LZMA2),
+            (Compression::Store, 0, SevenZMethod::COPY),
+        ] {
+            let settings = ArchiveSettings {
+                container: Container::SevenZ,
+                compression,
+                level,
+            };
+            let archive = compress_to_writer(
+                &[(&input, "")],
+                &|_| true,
+                settings,
+                Cursor::new(Vec::new()),
+            )
+            .unwrap()
+            .into_inner();
+            let reader = SevenZReader::new(
+                Cursor::new(archive.as_slice()),
+                archive.len() as u64,
+                Password::empty(),
+            )
+            .unwrap();
+            assert_eq!(
+                reader.archive().folders[0].coders[0].decompression_method_id(),
+                method.id()
+            );
+
+            let output = temp_dir.path().join(format!("{:?}", compression));
+            extract_from_reader(Cursor::new(archive), &output).unwrap();
+            assert_eq!(
+                std::fs::read_to_string(output.join("notes.txt")).unwrap(),
+                "notes ".repeat(1000)
+            );
+            assert_eq!(mode(&output.join("notes.txt")), 0o600);
+            assert_eq!(
+                std::fs::read(output.join("nested").join("blank.txt")).unwrap(),
+                b""
+            );
+            assert!(output.join("nested").join("empty").is_dir());
+            assert_eq!(
+                std::fs::read_link(output.join("nested").join("link")).unwrap(),
+                PathBuf::from("../notes.txt")
+            );
+        }
+
+        let zstd = ArchiveSettings {
+            container: Container::SevenZ,
+            compression: Compression::Zstd,
+            level: 3,
+        };
+        let error = compress_to_writer(&[(&input, "")], &|_| true, zstd, Cursor::new(Vec::new()))
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("TarZst"));
+    }
+
+    #[test]
+    fn test_tar_zst_round_trip_is_detected_from_content() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let input = temp_dir.path().join("input");
+        let archive = temp_dir.path().join("archive.tar.zst");
+        let output = temp_dir.path().join("output");
+        let long_name = "n".repeat(150);
+        std::fs::create_dir_all(input.join("bin")).unwrap();
+        std::fs::write(input.join("bin").join("run.sh"), "#!/bin/sh\n").unwrap();
+        std::fs::write(input.join(&long_name), "long").unwrap();
+        std::os::unix::fs::symlink(&long_name, input.join("link")).unwrap();
+        std::fs::set_permissions(
+            input.join("bin").join("run.sh"),
+            std::fs::Permissions::from_mode(0o750),
+        )
+        .unwrap();
+        std::fs::set_permissions(input.join("bin"), std::fs::Permissions::from_mode(0o711))
+            .unwrap();
+        filetime::set_file_mtime(
+            input.join("bin"),
+            filetime::FileTime::from_unix_time(1_600_000_000, 0),
+        )
+        .unwrap();
+
+        let settings = ArchiveSettings {
+            container: Container::TarZst,
+            ..ArchiveSettings::default()
+        };
+        compress_directory(&input, &archive, settings).unwrap();
+        assert_eq!(
+            detect_container(&std::fs::read(&archive).unwrap()),
+            Container::TarZst
+        );
+        extract_archive(&archive, &output).unwrap();
+
+        assert_eq!(mode(&output.join("bin").join("run.sh")), 0o750);
+        assert_eq!(mode(&output.join("bin")), 0o711);
+        assert_eq!(mtime(&output.join("bin")), 1_600_000_000);
+        assert_eq!(
+            std::fs::read_to_string(output.join(&long_name)).unwrap(),
+            "long"
+        );
+        assert_eq!(
+            std::fs::read_link(output.join("link")).unwrap(),
+            PathBuf::from(&long_name)
+        );
+
+        let in_memory = compress_to_writer(
+            &[(&input, "")],
+            &|_| true,
+            settings,
+            Cursor::new(Vec::new()),
+        )
+        .unwrap();
+        let from_memory = temp_dir.path().join("from-memory");
+        extract_from_reader(in_memory, &from_memory).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(from_memory.join("bin").join("run.sh")).unwrap(),
+            "#!/bin/sh\n"
+        );
+    }
+
+    #[test]
+    fn test_tar_zst_round_trip_restores_owners_as_root() {
+        use std::os::unix::fs::MetadataExt;
+        // Only root can give files away, so there is nothing to observe otherwise
+        if !nix::unistd::geteuid().is_root() {
+            return;
+        }
+        let temp_dir = tempfile::tempdir().unwrap();
+        let input = temp_dir.path().join("input");
+        let output = temp_dir.path().join("output");
+        std::fs::create_dir_all(input.join("shared")).unwrap();
+        std::fs::write(input.join("shared").join("notes.txt"), "notes").unwrap();
+        std::os::unix::fs::symlink("notes.txt", input.join("shared").join("link")).unwrap();
+        // Ids without an account, so they are restored by number
+        for name in ["shared", "shared/notes.txt", "shared/link"] {
+            std::os::unix::fs::lchown(input.join(name), Some(54321), Some(54322)).unwrap();
+        }
+        std::fs::set_permissions(
+            input.join("shared").join("notes.txt"),
+            std::fs::Permissions::from_mode(0o2750),
+        )
+        .unwrap();
+
+        let settings = ArchiveSettings {
+            container: Container::TarZst,
+            ..ArchiveSettings::default()
+        };
+        let archive = compress_to_writer(
+            &[(&input, "")],
+            &|_| true,
+            settings,
+            Cursor::new(Vec::new()),
+        )
+        .unwrap();
+        extract_from_reader(archive, &output).unwrap();
+
+        for name in ["shared", "shared/notes.txt", "shared/link"] {
+            let metadata = std::fs::symlink_metadata(output.join(name)).unwrap();
+            assert_eq!((metadata.uid(), metadata.gid()), (54321, 54322), "{}", name);
+        }
+        assert_eq!(mode(&output.join("shared").join("notes.txt")), 0o2750);
+    }
+
+    #[test]
+    fn test_archives_larger_than_a_segment_round_trip_through_encryption() {
+        use crate::crypto::stream;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let input = temp_dir.path().join("input");
+        std::fs::create_dir_all(&input).unwrap();
+        // Incompressible, so every archive spans several encryption segments
+        let mut state = 1u32;
+        let noise: Vec<u8> = (0..5 * stream::SEGMENT_LEN)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect();
+        std::fs::write(input.join("noise.bin"), &noise).unwrap();
+        std::fs::write(input.join("notes.txt"), "notes").unwrap();
+
+        let key = [3u8; 32];
+        for (container, compression) in [
+            (Container::SevenZ, Compression::Lzma2),
+            (Container::SevenZ, Compression::Store),
+            (Container::TarZst, Compression::Zstd),
+        ] {
+            let settings = ArchiveSettings {
+                container,
+                compression,
+                level: compression.default_level(),
+            };
+            let encryptor = stream::Encryptor::new(Cursor::new(Vec::new()), &key).unwrap();
+            let encrypted = compress_to_writer(&[(&input, "")], &|_| true, settings, encryptor)
+                .unwrap()
+                .finish()
+                .unwrap();
+
+            let output = temp_dir
+                .path()
+                .join(format!("{:?}-{:?}", container, compression));
+            let decryptor = stream::Decryptor::new(encrypted, &key).unwrap();
+            extract_from_reader(decryptor, &output).unwrap();
+            assert_eq!(std::fs::read(output.join("noise.bin")).unwrap(), noise);
+            assert_eq!(
+                std::fs::read_to_string(output.join("notes.txt")).unwrap(),
+                "notes"
+            );
+        }
+    }
+
+    #[test]
+    fn test_tar_content_must_keep_the_size_in_its_header() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("log.txt");
+        std::fs::write(&path, "0123456789").unwrap();
+        let content = |size| SizedContent {
+            file: File::open(&path).unwrap(),
+            path: &path,
+            remaining: size,
+            grew: false,
+        };
+
+        let mut grown = content(4);
+        let mut data = Vec::new();
+        grown.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"0123");
+        assert!(grown.grew);
+
+        let mut exact = content(10);
+        exact.read_to_end(&mut Vec::new()).unwrap();
+        assert!(!exact.grew);
+
+        let error = content(16).read_to_end(&mut Vec::new()).unwrap_err();
+        assert!(error.to_string().contains("shrank by 6 bytes"));
+    }
+
+    #[test]
+    fn test_archive_stem_strips_either_container_extension() {
+        assert_eq!(
+            archive_stem("Folder_2026-04-22_10-00-00_inc.7z"),
+            Some("Folder_2026-04-22_10-00-00_inc")
+        );
+        assert_eq!(
+            archive_stem("Folder_2026-04-22_10-00-00.tar.zst"),
+            Some("Folder_2026-04-22_10-00-00")
+        );
+        assert_eq!(archive_stem("Folder.index"), None);
+        assert_eq!(archive_stem("Folder_2026-04-22_10-00-00.zst"), None);
+    }
+
+    #[test]
+    fn test_output_path_rejects_escapes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let output = temp_dir.path();
+        std::os::unix::fs::symlink("/etc", output.join("link")).unwrap();
+
+        assert_eq!(
+            output_path(output, "a/b.txt").unwrap(),
+            output.join("a").join("b.txt")
+        );
+        assert!(output_path(output, "../b.txt").is_err());
+        assert!(output_path(output, "/etc/passwd").is_err());
+        assert!(output_path(output, "link/passwd").is_err());
+    }
+}
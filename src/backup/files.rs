@@ -0,0 +1,297 @@
+use crate::models::config::OpenDatabase;
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::fs;
+
+/// Directory inside the archive that holds the listed files.
+pub const ARCHIVE_DIR: &str = "files";
+
+/// KeePass 2.x signature: `0x9AA2D903` followed by `0xB54BFB67` (or `0xB54BFB66` for pre-release
+/// files), both little-endian.
+const KDBX_SIGNATURE_1: [u8; 4] = [0x03, 0xd9, 0xa2, 0x9a];
+const KDBX_SIGNATURES_2: [[u8; 4]; 2] = [[0x67, 0xfb, 0x4b, 0xb5], [0x66, 0xfb, 0x4b, 0xb5]];
+
+/// Copies each file into `files/`, waiting until it is no longer being written.
+///
+/// A file counts as stable once its size, mtime, and KeePassXC lock file are unchanged across
+/// `window` both before and after the copy, so a database opened or closed mid-copy is copied
+/// again. Files still changing after `timeout` fail the source. A database open in KeePassXC
+/// is handled as `open_database` says. Copied `.kdbx` files must start with the KeePass
+/// signature.
+pub async fn export_files(
+    paths: &[PathBuf],
+    window: Duration,
+    timeout: Duration,
+    open_database: OpenDatabase,
+    temp_dir: &Path,
+) -> Result<()> {
+    if paths.is_empty() {
+        return Err(anyhow!("No files configured for files source"));
+    }
+
+    let dest_dir = temp_dir.join(ARCHIVE_DIR);
+    fs::create_dir_all(&dest_dir).await?;
+
+    let mut used_names: HashMap<String, usize> = HashMap::new();
+    for path in paths {
+        if !path.is_file() {
+            return Err(anyhow!("File does not exist: {:?}", path));
+        }
+
+        let dest = dest_dir.join(archive_name(path, &mut used_names));
+        let state = copy_when_stable(path, &dest, window, timeout).await?;
+        if let Some(lock) = &state.lock {
+            match open_database {
+                OpenDatabase::Warn => warn!(
+                    "{:?} is open in KeePassXC ({:?}); copied it after it stayed unchanged",
+                    path, lock
+                ),
+                OpenDatabase::Fail => {
+                    return Err(anyhow!(
+                        "{:?} is open in KeePassXC ({:?}); close it and back up again",
+                        path,
+                        lock
+                    ))
+                }
+            }
+        }
+
+        if is_kdbx(path) {
+            verify_kdbx_header(&dest)
+                .map_err(|e| anyhow!("Copy of {:?} is not a valid database: {}", path, e))?;
+        }
+
+        info!("Copied {:?} ({} bytes)", path, state.size);
+    }
+
+    Ok(())
+}
+
+/// Returns the lock file KeePassXC keeps next to an open database, if any.
+fn keepassxc_lock_file(path: &Path) -> Option<PathBuf> {
+    let name = path.file_name()?.to_string_lossy();
+    let candidates = [format!(".{}.lock", name), format!("{}.lock", name)];
+    candidates
+        .into_iter()
+        .map(|candidate| path.with_file_name(candidate))
+        .find(|candidate| candidate.exists())
+}
+
+/// What has to stay unchanged for a copy to count as stable.
+#[derive(Debug, PartialEq, Eq)]
+struct FileState {
+    size: u64,
+    modified: SystemTime,
+    lock: Option<PathBuf>,
+}
+
+async fn copy_when_stable(
+    src: &Path,
+    dest: &Path,
+    window: Duration,
+    timeout: Duration,
+) -> Result<FileState> {
+    let deadline = Instant::now() + timeout;
+    let mut before = file_state(src).await?;
+
+    loop {
+        tokio::time::sleep(window).await;
+        let current = file_state(src).await?;
+        if current == before {
+            fs::copy(src, dest).await?;
+            let after = file_state(src).await?;
+            if after == current {
+                return Ok(current);
+            }
+            warn!("{:?} changed while it was being copied; retrying", src);
+            before = after;
+        } else {
+            before = current;
+        }
+
+        if Instant::now() >= deadline {
+            return Err(anyhow!(
+                "{:?} did not stop changing within {} seconds",
+                src,
+                timeout.as_secs()
+            ));
+        }
+    }
+}
+
+async fn file_state(path: &Path) -> Result<FileState> {
+    let metadata = fs::metadata(path).await?;
+    Ok(FileState {
+        size: metadata.len(),
+        modified: metadata.modified()?,
+        lock: keepassxc_lock_file(path),
+    })
+}
+
+fn is_kdbx(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("kdbx"))
+}
+
+fn verify_kdbx_header(path: &Path) -> Result<()> {
+    let mut header = [0u8; 8];
+    std::fs::File::open(path)?
+        .read_exact(&mut header)
+        .map_err(|_| anyhow!("file is shorter than the KeePass header"))?;
+
+    if header[..4] != KDBX_SIGNATURE_1 || !KDBX_SIGNATURES_2.iter().any(|sig| header[4..] == *sig) {
+        return Err(anyhow!("missing KeePass signature"));
+    }
+    Ok(())
+}
+
+/// Uses the file name, adding a numeric suffix before the extension when two files share it.
+fn archive_name(path: &Path, used_names: &mut HashMap<String, usize>) -> String {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "file".to_string());
+
+    let count = used_names.entry(name.clone()).or_insert(0);
+    *count += 1;
+    if *count == 1 {
+        return name;
+    }
+
+    let path = Path::new(&name);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => format!(
+            "{}-{}.{}",
+            stem.to_string_lossy(),
+            count,
+            extension.to_string_lossy()
+        ),
+        _ => format!("{}-{}", name, count),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KDBX_HEADER: [u8; 12] = [
+        0x03, 0xd9, 0xa2, 0x9a, 0x67, 0xfb, 0x4b, 0xb5, 0x01, 0x00, 0x04, 0x00,
+    ];
+
+    const WINDOW: Duration = Duration::from_millis(50);
+
+    #[tokio::test]
+    async fn test_export_files_copies_and_renames_duplicates() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let first = temp_dir.path().join("a").join("vault.kdbx");
+        let second = temp_dir.path().join("b").join("vault.kdbx");
+        for path in [&first, &second] {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, KDBX_HEADER).unwrap();
+        }
+        std::fs::write(temp_dir.path().join("a").join(".vault.kdbx.lock"), "").unwrap();
+
+        let staging_dir = temp_dir.path().join("staging");
+        export_files(
+            &[first, second],
+            WINDOW,
+            Duration::from_secs(5),
+            OpenDatabase::Warn,
+            &staging_dir,
+        )
+        .await
+        .unwrap();
+
+        let dir = staging_dir.join(ARCHIVE_DIR);
+        assert_eq!(std::fs::read(dir.join("vault.kdbx")).unwrap(), KDBX_HEADER);
+        assert_eq!(
+            std::fs::read(dir.join("vault-2.kdbx")).unwrap(),
+            KDBX_HEADER
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_files_rejects_invalid_kdbx() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let database = temp_dir.path().join("vault.kdbx");
+        std::fs::write(&database, b"partially written").unwrap();
+
+        let error = export_files(
+            &[database],
+            WINDOW,
+            Duration::from_secs(5),
+            OpenDatabase::Warn,
+            &temp_dir.path().join("staging"),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("not a valid database"));
+    }
+
+    #[tokio::test]
+    async fn test_export_files_times_out_while_file_keeps_changing() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let database = temp_dir.path().join("vault.kdbx");
+        std::fs::write(&database, KDBX_HEADER).unwrap();
+
+        let writer_path = database.clone();
+        let writer = tokio::spawn(async move {
+            for i in 0..40u8 {
+                let mut data = KDBX_HEADER.to_vec();
+                data.resize(data.len() + i as usize + 1, i);
+                std::fs::write(&writer_path, data).unwrap();
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        });
+
+        let error = export_files(
+            &[database],
+            WINDOW,
+            Duration::from_millis(200),
+            OpenDatabase::Warn,
+            &temp_dir.path().join("staging"),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("did not stop changing"));
+        writer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_export_files_fails_for_open_database_when_configured() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let database = temp_dir.path().join("vault.kdbx");
+        std::fs::write(&database, KDBX_HEADER).unwrap();
+        std::fs::write(temp_dir.path().join(".vault.kdbx.lock"), "").unwrap();
+
+        let error = export_files(
+            &[database],
+            WINDOW,
+            Duration::from_secs(5),
+            OpenDatabase::Fail,
+            &temp_dir.path().join("staging"),
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("open in KeePassXC"));
+    }
+
+    #[tokio::test]
+    async fn test_file_state_changes_when_database_is_opened() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let database = temp_dir.path().join("vault.kdbx");
+        std::fs::write(&database, KDBX_HEADER).unwrap();
+
+        let closed = file_state(&database).await.unwrap();
+        assert_eq!(closed.lock, None);
+        let lock = temp_dir.path().join(".vault.kdbx.lock");
+        std::fs::write(&lock, "").unwrap();
+        let open = file_state(&database).await.unwrap();
+        assert_eq!(open.lock, Some(lock));
+        assert_ne!(open, closed);
+    }
+}
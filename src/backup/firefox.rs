@@ -1,11 +1,19 @@
+use crate::backup::bookmarks::{write_bookmarks_html, BookmarkNode};
+use crate::crypto::nss;
+use crate::models::config::FirefoxSource;
 use anyhow::{anyhow, Result};
-use log::info;
+use log::{info, warn};
 use rusqlite::Connection;
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tokio::fs;
+use zeroize::Zeroizing;
+
+/// Environment variable that supplies the Firefox primary password.
+pub const PRIMARY_PASSWORD_ENV: &str = "BRIEFCASE_FIREFOX_PRIMARY_PASSWORD";
 
 pub async fn export_firefox_data(profile_dir: &Path, temp_dir: &Path) -> Result<()> {
     if !profile_dir.exists() {
@@ -21,25 +29,193 @@ pub async fn export_firefox_data(profile_dir: &Path, temp_dir: &Path) -> Result<
     export_bookmarks(profile_dir, temp_dir).await?;
 
     // Copy essential Firefox files
-    let files_to_copy = ["logins.json", "key4.db", "prefs.js"];
+    copy_profile_files(
+        "Firefox",
+        profile_dir,
+        temp_dir,
+        &["logins.json", "key4.db", "prefs.js"],
+    )
+    .await?;
 
-    for file in &files_to_copy {
+    Ok(())
+}
+
+/// Copies the listed files from a Mozilla profile, skipping any that are missing.
+pub async fn copy_profile_files(
+    app: &str,
+    profile_dir: &Path,
+    temp_dir: &Path,
+    files: &[&str],
+) -> Result<()> {
+    for file in files {
         let src = profile_dir.join(file);
         if src.exists() {
             let dest = temp_dir.join(file);
             let source_size = fs::metadata(&src).await?.len();
-            info!("Copying Firefox file {} ({} bytes)", file, source_size);
+            info!("Copying {} file {} ({} bytes)", app, file, source_size);
             fs::copy(&src, &dest).await?;
             let dest_size = fs::metadata(&dest).await?.len();
-            info!("Copied Firefox file {} ({} bytes)", file, dest_size);
+            info!("Copied {} file {} ({} bytes)", app, file, dest_size);
         } else {
-            info!("Firefox file {} not found, skipping", file);
+            info!("{} file {} not found, skipping", app, file);
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolves the default profile listed in a Mozilla `profiles.ini`.
+///
+/// Prefers the per-install default (`[Install...]`), then the profile marked
+/// `Default=1`, then the first profile listed.
+pub fn find_default_profile(profiles_root: &Path) -> Result<PathBuf> {
+    let ini_path = profiles_root.join("profiles.ini");
+    let content = std::fs::read_to_string(&ini_path)
+        .map_err(|e| anyhow!("Failed to read {:?}: {}", ini_path, e))?;
+
+    let mut sections: Vec<(String, HashMap<String, String>)> = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            sections.push((name.to_string(), HashMap::new()));
+        } else if let Some((key, value)) = line.split_once('=') {
+            if let Some((_, values)) = sections.last_mut() {
+                values.insert(key.trim().to_string(), value.trim().to_string());
+            }
         }
     }
 
+    let install_default = sections
+        .iter()
+        .filter(|(name, _)| name.starts_with("Install"))
+        .find_map(|(_, values)| values.get("Default"))
+        .map(|path| profiles_root.join(path));
+    if let Some(path) = install_default {
+        return Ok(path);
+    }
+
+    let profiles: Vec<_> = sections
+        .iter()
+        .filter(|(name, values)| name.starts_with("Profile") && values.contains_key("Path"))
+        .map(|(_, values)| values)
+        .collect();
+    let profile = profiles
+        .iter()
+        .find(|values| values.get("Default").is_some_and(|value| value == "1"))
+        .or(profiles.first())
+        .ok_or_else(|| anyhow!("No profiles listed in {:?}", ini_path))?;
+
+    let path = PathBuf::from(&profile["Path"]);
+    if profile.get("IsRelative").is_some_and(|value| value == "1") {
+        Ok(profiles_root.join(path))
+    } else {
+        Ok(path)
+    }
+}
+
+/// The primary password for `source`, taken from `BRIEFCASE_FIREFOX_PRIMARY_PASSWORD`, then the
+/// first line of `primary_password_file`, then the plain-text `primary_password`.
+pub fn primary_password(source: &FirefoxSource) -> Result<Option<Zeroizing<String>>> {
+    if let Ok(password) = std::env::var(PRIMARY_PASSWORD_ENV) {
+        return Ok(Some(Zeroizing::new(password)));
+    }
+    if let Some(path) = &source.primary_password_file {
+        let contents = Zeroizing::new(std::fs::read_to_string(path).map_err(|e| {
+            anyhow!(
+                "Could not read Firefox primary password file {:?}: {}",
+                path,
+                e
+            )
+        })?);
+        let password = contents.lines().next().unwrap_or_default();
+        return Ok(Some(Zeroizing::new(password.to_string())));
+    }
+    Ok(source.primary_password.clone().map(Zeroizing::new))
+}
+
+/// Decrypts the saved logins copied into `temp_dir` and writes them to `logins.csv`.
+///
+/// Expects `logins.json` and `key4.db` to have been copied by `export_firefox_data`.
+pub async fn export_logins_csv(temp_dir: &Path, primary_password: Option<&str>) -> Result<()> {
+    let logins_path = temp_dir.join("logins.json");
+    let key4_path = temp_dir.join("key4.db");
+    if !logins_path.exists() || !key4_path.exists() {
+        info!("Firefox logins.json or key4.db not found, skipping login export");
+        return Ok(());
+    }
+
+    let csv_path = temp_dir.join("logins.csv");
+    let primary_password = primary_password.unwrap_or_default().to_string();
+    let count = tokio::task::spawn_blocking(move || {
+        write_logins_csv(&logins_path, &key4_path, &primary_password, &csv_path)
+    })
+    .await??;
+    info!("Exported {} Firefox logins to logins.csv", count);
+
     Ok(())
 }
 
+#[derive(Deserialize)]
+struct LoginsFile {
+    #[serde(default)]
+    logins: Vec<LoginEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LoginEntry {
+    hostname: String,
+    encrypted_username: String,
+    encrypted_password: String,
+}
+
+fn write_logins_csv(
+    logins_path: &Path,
+    key4_path: &Path,
+    primary_password: &str,
+    output_path: &Path,
+) -> Result<usize> {
+    let logins: LoginsFile = serde_json::from_str(&std::fs::read_to_string(logins_path)?)?;
+    let key = nss::unlock_key4(key4_path, primary_password)?;
+
+    let mut output = File::create(output_path)?;
+    writeln!(output, "url,username,password")?;
+
+    let mut count = 0;
+    for login in &logins.logins {
+        let decrypted =
+            nss::decrypt_login_field(&key, &login.encrypted_username).and_then(|username| {
+                nss::decrypt_login_field(&key, &login.encrypted_password)
+                    .map(|password| (username, password))
+            });
+        match decrypted {
+            Ok((username, password)) => {
+                writeln!(
+                    output,
+                    "{},{},{}",
+                    escape_csv(&login.hostname),
+                    escape_csv(&username),
+                    escape_csv(&password)
+                )?;
+                count += 1;
+            }
+            Err(e) => warn!("Skipping Firefox login for {}: {}", login.hostname, e),
+        }
+    }
+
+    Ok(count)
+}
+
+/// Quotes a CSV field when it needs it. Fields starting with a spreadsheet formula character
+/// are quoted too, but never altered, so the file still imports the exact saved values.
+fn escape_csv(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) || value.starts_with(['=', '+', '-', '@', '\t']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
 async fn export_bookmarks(profile_dir: &Path, temp_dir: &Path) -> Result<()> {
     let places_src = profile_dir.join("places.sqlite");
     if !places_src.exists() {
@@ -109,70 +285,131 @@ fn export_bookmarks_html(database_path: &Path, output_path: &Path) -> Result<()>
         by_parent.entry(parent).or_default().push(entry);
     }
 
-    let mut output = File::create(output_path)?;
-    writeln!(output, "<!DOCTYPE NETSCAPE-Bookmark-file-1>")?;
-    writeln!(
-        output,
-        "<META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">"
-    )?;
-    writeln!(output, "<TITLE>Bookmarks</TITLE>")?;
-    writeln!(output, "<H1>Bookmarks</H1>")?;
-    writeln!(output, "<DL><p>")?;
-    write_bookmark_children(&mut output, &by_parent, 1, 1)?;
-    writeln!(output, "</DL><p>")?;
-
-    Ok(())
+    let nodes = build_bookmark_nodes(&mut by_parent, 1);
+    write_bookmarks_html(output_path, &nodes)
 }
 
-fn write_bookmark_children(
-    output: &mut File,
-    by_parent: &HashMap<i64, Vec<BookmarkEntry>>,
+fn build_bookmark_nodes(
+    by_parent: &mut HashMap<i64, Vec<BookmarkEntry>>,
     parent: i64,
-    depth: usize,
-) -> Result<()> {
-    let Some(entries) = by_parent.get(&parent) else {
-        return Ok(());
+) -> Vec<BookmarkNode> {
+    let Some(entries) = by_parent.remove(&parent) else {
+        return Vec::new();
     };
 
-    let indent = "    ".repeat(depth);
+    let mut nodes = Vec::new();
     for entry in entries {
         match entry.item_type {
             1 => {
-                let Some(url) = &entry.url else {
+                let Some(url) = entry.url else {
                     continue;
                 };
-                let title = entry.title.as_deref().unwrap_or(url);
-                writeln!(
-                    output,
-                    "{}<DT><A HREF=\"{}\">{}</A>",
-                    indent,
-                    escape_html(url),
-                    escape_html(title)
-                )?;
+                nodes.push(BookmarkNode::Link {
+                    title: entry.title,
+                    url,
+                });
             }
             2 => {
                 if parent == 1 && entry.title.as_deref() == Some("tags") {
                     continue;
                 }
 
-                let title = entry.title.as_deref().unwrap_or("Bookmarks");
-                writeln!(output, "{}<DT><H3>{}</H3>", indent, escape_html(title))?;
-                writeln!(output, "{}<DL><p>", indent)?;
-                write_bookmark_children(output, by_parent, entry.id, depth + 1)?;
-                writeln!(output, "{}</DL><p>", indent)?;
+                let children = build_bookmark_nodes(by_parent, entry.id);
+                nodes.push(BookmarkNode::Folder {
+                    title: entry.title,
+                    children,
+                });
             }
-            3 => writeln!(output, "{}<HR>", indent)?,
+            3 => nodes.push(BookmarkNode::Separator),
             _ => {}
         }
     }
 
-    Ok(())
+    nodes
 }
 
-fn escape_html(value: &str) -> String {
-    value
-        .replace('&', "&amp;")
-        .replace('"', "&quot;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::nss::tests::{create_key4_db, encrypt_login};
+
+    #[test]
+    fn test_escape_csv_quotes_special_and_formula_fields() {
+        assert_eq!(escape_csv("alice"), "alice");
+        assert_eq!(escape_csv("a,b"), "\"a,b\"");
+        assert_eq!(escape_csv("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape_csv("line\nbreak"), "\"line\nbreak\"");
+        assert_eq!(escape_csv("cr\rhere"), "\"cr\rhere\"");
+        for formula in ["=1+1", "+1", "-1", "@SUM(A1)", "\tx"] {
+            assert_eq!(escape_csv(formula), format!("\"{}\"", formula));
+        }
+        assert_eq!(escape_csv("a=b"), "a=b");
+    }
+
+    #[test]
+    fn test_write_logins_csv_writes_header_and_escaped_rows() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let key4_path = temp_dir.path().join("key4.db");
+        let login_key = [0x42u8; 32];
+        create_key4_db(&key4_path, &login_key, b"hunter2");
+
+        let logins = serde_json::json!({
+            "logins": [
+                {
+                    "hostname": "https://example.com",
+                    "encryptedUsername": encrypt_login(&login_key, "alice", true),
+                    "encryptedPassword": encrypt_login(&login_key, "p,a\"ss", true),
+                },
+                {
+                    "hostname": "https://example.org",
+                    "encryptedUsername": encrypt_login(&login_key, "bob", false),
+                    "encryptedPassword": encrypt_login(&login_key, "=cmd", false),
+                },
+                {
+                    "hostname": "https://broken.example",
+                    "encryptedUsername": "not base64",
+                    "encryptedPassword": "not base64",
+                },
+            ]
+        });
+        let logins_path = temp_dir.path().join("logins.json");
+        std::fs::write(&logins_path, logins.to_string()).unwrap();
+
+        let csv_path = temp_dir.path().join("logins.csv");
+        let count = write_logins_csv(&logins_path, &key4_path, "hunter2", &csv_path).unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(
+            std::fs::read_to_string(&csv_path).unwrap(),
+            "url,username,password\n\
+             https://example.com,alice,\"p,a\"\"ss\"\n\
+             https://example.org,bob,\"=cmd\"\n"
+        );
+
+        assert!(write_logins_csv(&logins_path, &key4_path, "wrong", &csv_path).is_err());
+    }
+
+    #[test]
+    fn test_primary_password_prefers_file_over_config() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let password_file = temp_dir.path().join("firefox-password");
+        std::fs::write(&password_file, "from file\nignored\n").unwrap();
+
+        let mut source = crate::models::config::Config::default().source.firefox;
+        assert!(primary_password(&source).unwrap().is_none());
+
+        source.primary_password = Some("from config".to_string());
+        assert_eq!(
+            primary_password(&source).unwrap().unwrap().as_str(),
+            "from config"
+        );
+
+        source.primary_password_file = Some(password_file);
+        assert_eq!(
+            primary_password(&source).unwrap().unwrap().as_str(),
+            "from file"
+        );
+
+        source.primary_password_file = Some(temp_dir.path().join("missing"));
+        assert!(primary_password(&source).is_err());
+    }
 }
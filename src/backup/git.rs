@@ -0,0 +1,364 @@
+use anyhow::{anyhow, Result};
+use log::info;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use tokio::fs;
+use tokio::process::Command;
+
+/// Directory inside the archive that holds repository bundles.
+pub const ARCHIVE_DIR: &str = "git";
+
+/// Namespace of the refs that carry older stashes into a bundle; `refs/stash` only points at
+/// the latest one, the others live in its reflog.
+pub const STASH_REFS: &str = "refs/briefcase/stash";
+
+/// Writes `git/<name>.bundle` for every repository, plus `git/<name>.patch` with uncommitted
+/// changes and untracked, non-ignored files when the working tree is dirty.
+///
+/// Bundles are created with `--all`, so every branch, tag, and stash is included, whether or
+/// not it has been pushed. Stash `stash@{n}` is bundled as `refs/briefcase/stash/<n>`.
+pub async fn export_git_repositories(repos: &[PathBuf], temp_dir: &Path) -> Result<()> {
+    if repos.is_empty() {
+        return Err(anyhow!("No repositories configured for git source"));
+    }
+
+    let dest_dir = temp_dir.join(ARCHIVE_DIR);
+    fs::create_dir_all(&dest_dir).await?;
+
+    let mut used_names: HashMap<String, usize> = HashMap::new();
+    for repo in repos {
+        if !repo.exists() {
+            return Err(anyhow!("Git repository does not exist: {:?}", repo));
+        }
+
+        let name = bundle_name(repo, &mut used_names);
+        let bundle_path = dest_dir.join(format!("{}.bundle", name));
+        bundle_repository(repo, &bundle_path).await?;
+        let bundle_size = fs::metadata(&bundle_path).await?.len();
+        info!(
+            "Bundled git repository {:?} as {}.bundle ({} bytes)",
+            repo, name, bundle_size
+        );
+
+        let patch = uncommitted_changes(repo).await?;
+        if !patch.is_empty() {
+            fs::write(dest_dir.join(format!("{}.patch", name)), &patch).await?;
+            info!(
+                "Saved uncommitted changes of {:?} as {}.patch ({} bytes)",
+                repo,
+                name,
+                patch.len()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Bundles every ref of `repo`, pointing temporary refs under [`STASH_REFS`] at each stash
+/// for the duration of the bundle.
+async fn bundle_repository(repo: &Path, bundle_path: &Path) -> Result<()> {
+    // Refs left behind by an interrupted backup would otherwise be bundled as stashes
+    delete_stash_refs(repo).await?;
+
+    let stashes = run_git(
+        repo,
+        &["stash".as_ref(), "list".as_ref(), "--format=%H".as_ref()],
+    )
+    .await?;
+    let stashes = String::from_utf8_lossy(&stashes);
+    let mut result = Ok(());
+    for (index, commit) in stashes.lines().enumerate() {
+        let stash_ref = format!("{}/{}", STASH_REFS, index);
+        result = run_git(
+            repo,
+            &["update-ref".as_ref(), stash_ref.as_ref(), commit.as_ref()],
+        )
+        .await
+        .map(drop);
+        if result.is_err() {
+            break;
+        }
+    }
+
+    if result.is_ok() {
+        result = run_git(
+            repo,
+            &[
+                "bundle".as_ref(),
+                "create".as_ref(),
+                bundle_path.as_os_str(),
+                "--all".as_ref(),
+            ],
+        )
+        .await
+        .map(drop);
+    }
+
+    let cleanup = delete_stash_refs(repo).await;
+    result.and(cleanup)
+}
+
+async fn delete_stash_refs(repo: &Path) -> Result<()> {
+    let refs = run_git(
+        repo,
+        &[
+            "for-each-ref".as_ref(),
+            "--format=%(refname)".as_ref(),
+            STASH_REFS.as_ref(),
+        ],
+    )
+    .await?;
+    for stash_ref in String::from_utf8_lossy(&refs).lines() {
+        run_git(
+            repo,
+            &["update-ref".as_ref(), "-d".as_ref(), stash_ref.as_ref()],
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Diff of the working tree against `HEAD`, followed by the content of every untracked file
+/// that is not ignored, as one patch `git apply` can restore.
+async fn uncommitted_changes(repo: &Path) -> Result<Vec<u8>> {
+    let mut patch = run_git(
+        repo,
+        &["diff".as_ref(), "HEAD".as_ref(), "--binary".as_ref()],
+    )
+    .await?;
+
+    let untracked = run_git(
+        repo,
+        &[
+            "ls-files".as_ref(),
+            "--others".as_ref(),
+            "--exclude-standard".as_ref(),
+            "-z".as_ref(),
+        ],
+    )
+    .await?;
+    for path in untracked
+        .split(|byte| *byte == 0)
+        .filter(|path| !path.is_empty())
+    {
+        let path = String::from_utf8_lossy(path);
+        // Exits with 1 whenever there is a difference, which a new file always is
+        let output = git_output(
+            repo,
+            &[
+                "diff".as_ref(),
+                "--no-index".as_ref(),
+                "--binary".as_ref(),
+                "--".as_ref(),
+                "/dev/null".as_ref(),
+                path.as_ref().as_ref(),
+            ],
+        )
+        .await?;
+        if output.status.code() != Some(1) {
+            return Err(git_error("diff", repo, &output));
+        }
+        patch.extend_from_slice(&output.stdout);
+    }
+    Ok(patch)
+}
+
+/// Uses the repository directory name, adding a numeric suffix when two repos share it.
+fn bundle_name(repo: &Path, used_names: &mut HashMap<String, usize>) -> String {
+    let base = repo
+        .file_name()
+        .map(|name| name.to_string_lossy().trim_end_matches(".git").to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "repo".to_string());
+
+    let count = used_names.entry(base.clone()).or_insert(0);
+    *count += 1;
+    if *count == 1 {
+        base
+    } else {
+        format!("{}-{}", base, count)
+    }
+}
+
+async fn run_git(repo: &Path, args: &[&std::ffi::OsStr]) -> Result<Vec<u8>> {
+    let output = git_output(repo, args).await?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        let command = args
+            .first()
+            .map(|arg| arg.to_string_lossy())
+            .unwrap_or_default();
+        Err(git_error(&command, repo, &output))
+    }
+}
+
+async fn git_output(repo: &Path, args: &[&std::ffi::OsStr]) -> Result<Output> {
+    Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| anyhow!("Failed to spawn git: {}", e))
+}
+
+fn git_error(command: &str, repo: &Path, output: &Output) -> anyhow::Error {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    anyhow!("git {} failed for {:?}: {}", command, repo, stderr.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command as StdCommand;
+
+    fn git(dir: &Path, args: &[&str]) -> String {
+        let output = StdCommand::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args([
+                "-c",
+                "user.name=Briefcase",
+                "-c",
+                "user.email=briefcase@example.com",
+            ])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8_lossy(&output.stdout).to_string()
+    }
+
+    fn init_repo(dir: &Path) {
+        std::fs::create_dir_all(dir).unwrap();
+        git(dir, &["init", "-q", "-b", "main"]);
+        std::fs::write(dir.join("notes.md"), "first\n").unwrap();
+        git(dir, &["add", "notes.md"]);
+        git(dir, &["commit", "-q", "-m", "first"]);
+    }
+
+    #[tokio::test]
+    async fn test_export_git_repositories_bundles_refs_and_changes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let repo = temp_dir.path().join("dotfiles");
+        init_repo(&repo);
+
+        git(&repo, &["checkout", "-q", "-b", "unpushed"]);
+        std::fs::write(repo.join("notes.md"), "second\n").unwrap();
+        git(&repo, &["commit", "-q", "-am", "second"]);
+        std::fs::write(repo.join("notes.md"), "older stash\n").unwrap();
+        git(&repo, &["stash", "-q"]);
+        std::fs::write(repo.join("notes.md"), "stashed\n").unwrap();
+        git(&repo, &["stash", "-q"]);
+        std::fs::write(repo.join("notes.md"), "dirty\n").unwrap();
+        std::fs::write(repo.join(".gitignore"), "*.log\n").unwrap();
+        std::fs::write(repo.join("todo.txt"), "untracked\n").unwrap();
+        std::fs::write(repo.join("build.log"), "ignored\n").unwrap();
+
+        let staging_dir = temp_dir.path().join("staging");
+        export_git_repositories(std::slice::from_ref(&repo), &staging_dir)
+            .await
+            .unwrap();
+
+        let bundle = staging_dir.join(ARCHIVE_DIR).join("dotfiles.bundle");
+        let heads = git(
+            temp_dir.path(),
+            &["bundle", "list-heads", bundle.to_str().unwrap()],
+        );
+        assert!(heads.contains("refs/heads/main"));
+        assert!(heads.contains("refs/heads/unpushed"));
+        assert!(heads.contains("refs/stash"));
+        assert!(heads.contains("refs/briefcase/stash/0"));
+        assert!(heads.contains("refs/briefcase/stash/1"));
+        // The temporary stash refs do not outlive the bundle
+        assert!(git(&repo, &["for-each-ref", STASH_REFS]).is_empty());
+
+        let clone = temp_dir.path().join("clone");
+        git(
+            temp_dir.path(),
+            &[
+                "clone",
+                "-q",
+                "-b",
+                "unpushed",
+                bundle.to_str().unwrap(),
+                clone.to_str().unwrap(),
+            ],
+        );
+        assert_eq!(
+            std::fs::read_to_string(clone.join("notes.md")).unwrap(),
+            "second\n"
+        );
+
+        let patch_path = staging_dir.join(ARCHIVE_DIR).join("dotfiles.patch");
+        assert!(std::fs::read_to_string(&patch_path)
+            .unwrap()
+            .contains("+dirty"));
+        git(&clone, &["apply", patch_path.to_str().unwrap()]);
+        assert_eq!(
+            std::fs::read_to_string(clone.join("notes.md")).unwrap(),
+            "dirty\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(clone.join("todo.txt")).unwrap(),
+            "untracked\n"
+        );
+        assert!(clone.join(".gitignore").exists());
+        assert!(!clone.join("build.log").exists());
+
+        git(&clone, &["checkout", "-q", "--", "."]);
+        git(
+            &clone,
+            &[
+                "fetch",
+                "-q",
+                bundle.to_str().unwrap(),
+                "refs/briefcase/stash/1",
+            ],
+        );
+        git(&clone, &["stash", "apply", "-q", "FETCH_HEAD"]);
+        assert_eq!(
+            std::fs::read_to_string(clone.join("notes.md")).unwrap(),
+            "older stash\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_git_repositories_clean_tree_and_duplicate_names() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let first = temp_dir.path().join("a").join("notes");
+        let second = temp_dir.path().join("b").join("notes");
+        init_repo(&first);
+        init_repo(&second);
+
+        let staging_dir = temp_dir.path().join("staging");
+        export_git_repositories(&[first, second], &staging_dir)
+            .await
+            .unwrap();
+
+        let dir = staging_dir.join(ARCHIVE_DIR);
+        assert!(dir.join("notes.bundle").exists());
+        assert!(dir.join("notes-2.bundle").exists());
+        assert!(!dir.join("notes.patch").exists());
+    }
+
+    #[tokio::test]
+    async fn test_export_git_repositories_fails_for_non_repository() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let not_repo = temp_dir.path().join("plain");
+        std::fs::create_dir_all(&not_repo).unwrap();
+
+        assert!(
+            export_git_repositories(&[not_repo], &temp_dir.path().join("staging"))
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use crate::backup::command;
+use crate::models::backup_file::SourceType;
+use crate::models::config::{Config, Hook, HookFailure};
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use std::ffi::OsStr;
+use std::path::Path;
+use std::time::Duration;
+
+/// Name of the source being backed up, such as `Folder`.
+pub const ENV_SOURCE: &str = "BRIEFCASE_SOURCE";
+/// Staging directory whose contents are archived; pre-backup hooks may write files into it.
+pub const ENV_TEMP_DIR: &str = "BRIEFCASE_TEMP_DIR";
+/// Path of the archive or snapshot that was written, empty when there is none.
+pub const ENV_ARCHIVE: &str = "BRIEFCASE_ARCHIVE";
+/// Outcome for post-backup hooks: `created`, `unchanged`, or `failed`.
+pub const ENV_STATUS: &str = "BRIEFCASE_STATUS";
+
+/// When a hook runs relative to the source's backup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    PreBackup,
+    PostBackup,
+}
+
+impl Stage {
+    fn name(self) -> &'static str {
+        match self {
+            Stage::PreBackup => "pre-backup",
+            Stage::PostBackup => "post-backup",
+        }
+    }
+}
+
+/// What hooks are told about the backup through their environment.
+pub struct HookContext<'a> {
+    pub source_type: SourceType,
+    pub temp_dir: &'a Path,
+    pub archive: Option<&'a Path>,
+    pub status: Option<&'a str>,
+}
+
+/// The hooks of a stage in run order: global pre-backup hooks come before the source's own,
+/// and global post-backup hooks after them.
+pub fn hooks_for(config: &Config, source_type: SourceType, stage: Stage) -> Vec<Hook> {
+    let source = config.source.options(source_type);
+    match stage {
+        Stage::PreBackup => [&config.general.pre_backup, &source.pre_backup],
+        Stage::PostBackup => [&source.post_backup, &config.general.post_backup],
+    }
+    .into_iter()
+    .flatten()
+    .cloned()
+    .collect()
+}
+
+/// Runs hooks one after another through `sh -c`.
+///
+/// A hook that exits non-zero or outlives `timeout_secs` fails the stage when its policy is
+/// `Abort`, skipping the hooks after it; with `Warn` the failure is only logged.
+pub async fn run_hooks(stage: Stage, hooks: &[Hook], context: &HookContext<'_>) -> Result<()> {
+    let envs: [(&str, &OsStr); 4] = [
+        (ENV_SOURCE, OsStr::new(context.source_type.name())),
+        (ENV_TEMP_DIR, context.temp_dir.as_os_str()),
+        (
+            ENV_ARCHIVE,
+            context.archive.map_or(OsStr::new(""), Path::as_os_str),
+        ),
+        (ENV_STATUS, OsStr::new(context.status.unwrap_or(""))),
+    ];
+
+    for hook in hooks {
+        info!(
+            "Running {} hook for {}: {}",
+            stage.name(),
+            context.source_type.name(),
+            hook.command
+        );
+        let timeout = Duration::from_secs(hook.timeout_secs);
+        if let Err(e) = command::run_command(&hook.command, &envs, timeout).await {
+            match hook.on_failure {
+                HookFailure::Abort => {
+                    return Err(anyhow!(
+                        "{} {} hook failed: {}",
+                        context.source_type.name(),
+                        stage.name(),
+                        e
+                    ));
+                }
+                HookFailure::Warn => warn!(
+                    "{} {} hook failed, continuing: {}",
+                    context.source_type.name(),
+                    stage.name(),
+                    e
+                ),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(command: &str, on_failure: HookFailure) -> Hook {
+        Hook {
+            command: command.to_string(),
+            timeout_secs: 10,
+            on_failure,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_hooks_passes_environment() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let archive = temp_dir.path().join("Folder_2026-04-22_10-00-00.7z");
+        let context = HookContext {
+            source_type: SourceType::Folder,
+            temp_dir: temp_dir.path(),
+            archive: Some(&archive),
+            status: Some("created"),
+        };
+
+        run_hooks(
+            Stage::PostBackup,
+            &[hook(
+                "printf '%s %s %s' \"$BRIEFCASE_SOURCE\" \"$BRIEFCASE_STATUS\" \"$BRIEFCASE_ARCHIVE\" > \"$BRIEFCASE_TEMP_DIR/env.txt\"",
+                HookFailure::Abort,
+            )],
+            &context,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(temp_dir.path().join("env.txt")).unwrap(),
+            format!("Folder created {}", archive.display())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_hooks_applies_failure_policy_and_timeout() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let context = HookContext {
+            source_type: SourceType::Git,
+            temp_dir: temp_dir.path(),
+            archive: None,
+            status: None,
+        };
+
+        run_hooks(
+            Stage::PreBackup,
+            &[
+                hook("exit 1", HookFailure::Warn),
+                hook("touch \"$BRIEFCASE_TEMP_DIR/ran\"", HookFailure::Abort),
+            ],
+            &context,
+        )
+        .await
+        .unwrap();
+        assert!(temp_dir.path().join("ran").exists());
+
+        let mut slow = hook("sleep 5", HookFailure::Abort);
+        slow.timeout_secs = 1;
+        let error = run_hooks(
+            Stage::PreBackup,
+            &[
+                slow,
+                hook("touch \"$BRIEFCASE_TEMP_DIR/skipped\"", HookFailure::Abort),
+            ],
+            &context,
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("timed out"));
+        assert!(!temp_dir.path().join("skipped").exists());
+    }
+
+    #[test]
+    fn test_hooks_for_orders_global_hooks_around_source_hooks() {
+        let mut config = Config::default();
+        config.general.pre_backup = vec![hook("global-pre", HookFailure::Abort)];
+        config.general.post_backup = vec![hook("global-post", HookFailure::Abort)];
+        config.source.folder.options.pre_backup = vec![hook("folder-pre", HookFailure::Abort)];
+        config.source.folder.options.post_backup = vec![hook("folder-post", HookFailure::Warn)];
+
+        let commands = |stage| {
+            hooks_for(&config, SourceType::Folder, stage)
+                .into_iter()
+                .map(|hook| hook.command)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(commands(Stage::PreBackup), ["global-pre", "folder-pre"]);
+        assert_eq!(commands(Stage::PostBackup), ["folder-post", "global-post"]);
+        assert_eq!(
+            hooks_for(&config, SourceType::Ssh, Stage::PreBackup).len(),
+            1
+        );
+    }
+}
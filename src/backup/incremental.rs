@@ -0,0 +1,632 @@
+use crate::backup::compress;
+use crate::crypto::encrypt;
+use crate::models::backup_file::{BackupName, SourceType};
+use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use zeroize::Zeroizing;
+
+/// Directory inside every archive that holds briefcase's own metadata.
+pub const MANIFEST_DIR: &str = ".briefcase";
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Inserted before `.7z` in the names of incremental archives.
+pub const INCREMENTAL_SUFFIX: &str = "_inc";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    File,
+    Symlink,
+    Directory,
+}
+
+/// State of one archive entry when it was last backed up.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileRecord {
+    pub kind: EntryKind,
+    pub size: u64,
+    pub mtime_ns: u64,
+    pub mode: u32,
+    /// SHA-256 of the content (the link target for symlinks); empty for directories.
+    pub hash: String,
+}
+
+/// Per-source index of what the latest archive in the chain contains, stored encrypted as
+/// `<Source>.index` in the data directory.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FileIndex {
+    /// File name of the archive this index describes.
+    pub archive: String,
+    /// Incremental archives since the last full backup.
+    pub chain_length: u32,
+    pub files: BTreeMap<String, FileRecord>,
+}
+
+/// Stored at `.briefcase/manifest.json` in every archive.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub incremental: bool,
+    /// Archive this one applies on top of.
+    #[serde(default)]
+    pub previous: Option<String>,
+    /// Entries removed since `previous`.
+    #[serde(default)]
+    pub deleted: Vec<String>,
+}
+
+/// What the next archive of a source should contain.
+pub struct ArchivePlan {
+    pub manifest: Manifest,
+    /// Archive entry names to store, or `None` for everything.
+    pub include: Option<HashSet<String>>,
+    /// Index to save once the archive is written; `archive` is filled in by the caller.
+    pub index: FileIndex,
+}
+
+impl ArchivePlan {
+    pub fn includes(&self, name: &str) -> bool {
+        if is_metadata(name) {
+            return true;
+        }
+        match &self.include {
+            Some(include) => include.contains(name),
+            None => true,
+        }
+    }
+}
+
+/// Whether an archive entry is briefcase's own metadata rather than backed-up data.
+fn is_metadata(name: &str) -> bool {
+    name == MANIFEST_DIR || name.starts_with(&format!("{}/", MANIFEST_DIR))
+}
+
+/// Whether an archive file name belongs to an incremental backup.
+pub fn is_incremental(filename: &str) -> bool {
+    compress::archive_stem(filename).is_some_and(|stem| stem.ends_with(INCREMENTAL_SUFFIX))
+}
+
+fn index_path(data_dir: &Path, source_type: SourceType) -> PathBuf {
+    data_dir.join(format!("{}.index", source_type.name()))
+}
+
+/// Loads the index of a source; an unreadable index only forces a full backup.
+pub fn load_index(data_dir: &Path, source_type: SourceType, key: &[u8; 32]) -> Option<FileIndex> {
+    let path = index_path(data_dir, source_type);
+    if !path.exists() {
+        return None;
+    }
+
+    let index = encrypt::decrypt_from_file_with_derived_key(&path, key)
+        .and_then(|data| Ok(serde_json::from_slice(&data)?));
+    match index {
+        Ok(index) => Some(index),
+        Err(error) => {
+            warn!(
+                "Ignoring unreadable {} index, taking a full backup: {}",
+                source_type.name(),
+                error
+            );
+            None
+        }
+    }
+}
+
+pub fn save_index(
+    data_dir: &Path,
+    source_type: SourceType,
+    index: &FileIndex,
+    key: &[u8; 32],
+) -> Result<()> {
+    let data = Zeroizing::new(serde_json::to_vec(index)?);
+    encrypt::encrypt_to_file_with_derived_key(&data, &index_path(data_dir, source_type), key)
+}
+
+/// Drops the index so a source that stops being incremental starts a fresh chain later.
+pub fn remove_index(data_dir: &Path, source_type: SourceType) -> Result<()> {
+    let path = index_path(data_dir, source_type);
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Records every entry the archive would hold, hashing only entries whose size, mtime, or
+/// mode differ from `previous`.
+pub fn scan(
+    roots: &[(&Path, &str)],
+    previous: Option<&FileIndex>,
+) -> Result<BTreeMap<String, FileRecord>> {
+    let mut records = BTreeMap::new();
+
+    for (dir, prefix) in roots {
+        for (path, name, metadata) in compress::collect_entries(dir, prefix)? {
+            if name == MANIFEST_DIR || name.starts_with(&format!("{}/", MANIFEST_DIR)) {
+                continue;
+            }
+
+            let mut record = FileRecord {
+                kind: entry_kind(&metadata),
+                size: metadata.len(),
+                mtime_ns: mtime_ns(&metadata),
+                mode: mode(&metadata),
+                hash: String::new(),
+            };
+            let known = previous.and_then(|index| index.files.get(&name));
+            record.hash = match (record.kind, known) {
+                (EntryKind::Directory, _) => String::new(),
+                (_, Some(known))
+                    if known.kind == record.kind
+                        && known.size == record.size
+                        && known.mtime_ns == record.mtime_ns
+                        && known.mode == record.mode =>
+                {
+                    known.hash.clone()
+                }
+                (EntryKind::Symlink, _) => hash_bytes(
+                    std::fs::read_link(&path)?
+                        .into_os_string()
+                        .as_encoded_bytes(),
+                ),
+                (EntryKind::File, _) => hash_file(&path)?,
+            };
+            records.insert(name, record);
+        }
+    }
+
+    Ok(records)
+}
+
+/// Fingerprint of scanned content: entry names, kinds, modes, and hashes, but not mtimes, so
+/// re-exported data that is byte-identical fingerprints the same.
+pub fn fingerprint(records: &BTreeMap<String, FileRecord>) -> String {
+    let mut hasher = Sha256::new();
+    for (name, record) in records {
+        hasher.update(name.as_bytes());
+        hasher.update([0]);
+        hasher.update(format!(
+            "{:?}:{:o}:{}",
+            record.kind, record.mode, record.hash
+        ));
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Decides between a full and an incremental archive and writes the manifest into `staging_dir`.
+///
+/// A full backup is taken when there is no usable index, the archive it describes is gone from
+/// `data_dir`, or `full_every` incrementals have been taken since the last full backup.
+/// Directories are always stored so new and re-moded directories survive restore.
+pub fn plan(
+    data_dir: &Path,
+    previous: Option<FileIndex>,
+    current: BTreeMap<String, FileRecord>,
+    full_every: u32,
+    staging_dir: &Path,
+) -> Result<ArchivePlan> {
+    let base = previous.filter(|index| can_build_on(data_dir, index, full_every));
+
+    let plan = match base {
+        Some(base) => {
+            let include = current
+                .iter()
+                .filter(|(name, record)| {
+                    record.kind == EntryKind::Directory
+                        || match base.files.get(*name) {
+                            Some(known) => {
+                                known.kind != record.kind
+                                    || known.hash != record.hash
+                                    || known.mode != record.mode
+                            }
+                            None => true,
+                        }
+                })
+                .map(|(name, _)| name.clone())
+                .collect();
+            let deleted = base
+                .files
+                .keys()
+                .filter(|name| !current.contains_key(*name))
+                .cloned()
+                .collect();
+            ArchivePlan {
+                manifest: Manifest {
+                    incremental: true,
+                    previous: Some(base.archive),
+                    deleted,
+                },
+                include: Some(include),
+                index: FileIndex {
+                    archive: String::new(),
+                    chain_length: base.chain_length + 1,
+                    files: current,
+                },
+            }
+        }
+        None => ArchivePlan {
+            manifest: Manifest::default(),
+            include: None,
+            index: FileIndex {
+                archive: String::new(),
+                chain_length: 0,
+                files: current,
+            },
+        },
+    };
+
+    let manifest_dir = staging_dir.join(MANIFEST_DIR);
+    std::fs::create_dir_all(&manifest_dir)?;
+    std::fs::write(
+        manifest_dir.join(MANIFEST_FILE),
+        serde_json::to_vec_pretty(&plan.manifest)?,
+    )?;
+
+    Ok(plan)
+}
+
+/// Whether the next archive can build on the one `index` describes, rather than a full
+/// backup being due.
+pub fn can_build_on(data_dir: &Path, index: &FileIndex, full_every: u32) -> bool {
+    index.chain_length < full_every
+        && !index.archive.is_empty()
+        && data_dir.join(&index.archive).exists()
+}
+
+/// Restores `input` into `output_dir`. For an incremental archive the full backup it builds on
+/// and every incremental in between are read from the same directory and applied in order.
+///
+/// Returns the archives that were applied.
+pub fn restore(input: &Path, output_dir: &Path, key: &[u8; 32]) -> Result<Vec<PathBuf>> {
+    let chain = find_chain(input)?;
+    let mut directories = Vec::new();
+    let mut previous: Option<String> = None;
+
+    for archive_path in &chain {
+        let mut archive = encrypt::open_decrypted(archive_path, key)?;
+        // The manifest is read from the archive itself; metadata entries are never extracted
+        let manifest_name = format!("{}/{}", MANIFEST_DIR, MANIFEST_FILE);
+        let manifest: Manifest = match compress::read_entry(&mut archive, &manifest_name)? {
+            Some(manifest) => serde_json::from_slice(&manifest)?,
+            None => Manifest::default(),
+        };
+        compress::extract_into(
+            archive,
+            output_dir,
+            &|name| !is_metadata(name),
+            &mut directories,
+        )?;
+
+        if manifest.incremental {
+            if manifest.previous.is_none() || manifest.previous != previous {
+                return Err(anyhow!(
+                    "Backup chain is broken: {} builds on {}, but the archive before it is {}",
+                    archive_path.display(),
+                    manifest.previous.as_deref().unwrap_or("nothing"),
+                    previous.as_deref().unwrap_or("missing")
+                ));
+            }
+            for name in &manifest.deleted {
+                let path = compress::output_path(output_dir, name)?;
+                match std::fs::symlink_metadata(&path) {
+                    Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(&path)?,
+                    Ok(_) => std::fs::remove_file(&path)?,
+                    Err(_) => {}
+                }
+                directories.retain(|(directory, _, _)| !directory.starts_with(&path));
+            }
+        }
+
+        previous = archive_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+    }
+
+    compress::apply_directory_metadata(directories)?;
+    Ok(chain)
+}
+
+/// The full backup before `input` and every archive of the same source and host up to `input`.
+fn find_chain(input: &Path) -> Result<Vec<PathBuf>> {
+    let filename = input
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if !is_incremental(&filename) {
+        return Ok(vec![input.to_path_buf()]);
+    }
+
+    let input_name = BackupName::parse(&filename)
+        .ok_or_else(|| anyhow!("Cannot tell the source of {}", filename))?;
+    let dir = match input.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    // Other hosts' archives may sit alongside when restoring from a downloaded remote
+    let mut archives: Vec<(NaiveDateTime, String)> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| compress::is_archive(name))
+        .filter_map(|name| {
+            let parsed = BackupName::parse(&name)?;
+            (parsed.source_type == input_name.source_type
+                && parsed.host == input_name.host
+                && parsed.timestamp <= input_name.timestamp)
+                .then_some((parsed.timestamp, name))
+        })
+        .collect();
+    archives.sort();
+
+    let start = archives
+        .iter()
+        .rposition(|(_, name)| !is_incremental(name))
+        .ok_or_else(|| anyhow!("No full backup found before {}", filename))?;
+    Ok(archives[start..]
+        .iter()
+        .map(|(_, name)| dir.join(name))
+        .collect())
+}
+
+fn entry_kind(metadata: &Metadata) -> EntryKind {
+    if metadata.is_dir() {
+        EntryKind::Directory
+    } else if metadata.file_type().is_symlink() {
+        EntryKind::Symlink
+    } else {
+        EntryKind::File
+    }
+}
+
+fn mtime_ns(metadata: &Metadata) -> u64 {
+    metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_nanos() as u64)
+}
+
+#[cfg(unix)]
+fn mode(metadata: &Metadata) -> u32 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.mode()
+}
+
+#[cfg(not(unix))]
+fn mode(_metadata: &Metadata) -> u32 {
+    0
+}
+
+fn hash_bytes(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+fn hash_file(path: &Path) -> Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::config::ArchiveSettings;
+
+    const KEY: [u8; 32] = [7u8; 32];
+
+    /// Runs one backup of `source` into `data_dir` the way the service does.
+    fn backup(data_dir: &Path, source: &Path, timestamp: &str, full_every: u32) -> String {
+        let staging = tempfile::tempdir().unwrap();
+        let roots = [(staging.path(), ""), (source, "sensitive")];
+        let previous = load_index(data_dir, SourceType::Folder, &KEY);
+        let current = scan(&roots, previous.as_ref()).unwrap();
+        let mut plan = plan(data_dir, previous, current, full_every, staging.path()).unwrap();
+
+        let suffix = if plan.manifest.incremental {
+            INCREMENTAL_SUFFIX
+        } else {
+            ""
+        };
+        let filename = format!("Folder_{}{}.7z", timestamp, suffix);
+        encrypt::encrypt_stream_to_file(&data_dir.join(&filename), &KEY, |encryptor| {
+            compress::compress_to_writer(
+                &roots,
+                &|name| plan.includes(name),
+                ArchiveSettings::default(),
+                encryptor,
+            )?;
+            Ok(())
+        })
+        .unwrap();
+        plan.index.archive = filename.clone();
+        save_index(data_dir, SourceType::Folder, &plan.index, &KEY).unwrap();
+        filename
+    }
+
+    #[test]
+    fn test_incremental_chain_restores_each_point_in_time() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let source = temp_dir.path().join("source");
+        std::fs::create_dir_all(&data_dir).unwrap();
+        std::fs::create_dir_all(source.join("notes")).unwrap();
+        std::fs::write(source.join("keep.txt"), "unchanged").unwrap();
+        std::fs::write(source.join("edit.txt"), "v1").unwrap();
+        std::fs::write(source.join("notes").join("old.txt"), "old").unwrap();
+
+        let full = backup(&data_dir, &source, "2026-01-01_00-00-00", 6);
+        assert!(!is_incremental(&full));
+
+        std::fs::write(source.join("edit.txt"), "v2").unwrap();
+        std::fs::remove_dir_all(source.join("notes")).unwrap();
+        std::fs::write(source.join("new.txt"), "new").unwrap();
+        let first = backup(&data_dir, &source, "2026-01-02_00-00-00", 6);
+        assert!(is_incremental(&first));
+
+        std::fs::write(source.join("edit.txt"), "v3").unwrap();
+        let second = backup(&data_dir, &source, "2026-01-03_00-00-00", 6);
+
+        // Only the changed file and the always-stored directories are in the increment
+        let listing = temp_dir.path().join("listing");
+        let archive = encrypt::open_decrypted(&data_dir.join(&second), &KEY).unwrap();
+        compress::extract_from_reader(archive, &listing).unwrap();
+        assert!(listing.join("sensitive").join("edit.txt").exists());
+        assert!(!listing.join("sensitive").join("keep.txt").exists());
+        assert!(!listing.join("sensitive").join("new.txt").exists());
+
+        let restored = temp_dir.path().join("restored-first");
+        let chain = restore(&data_dir.join(&first), &restored, &KEY).unwrap();
+        assert_eq!(chain, vec![data_dir.join(&full), data_dir.join(&first)]);
+        let sensitive = restored.join("sensitive");
+        assert_eq!(
+            std::fs::read_to_string(sensitive.join("edit.txt")).unwrap(),
+            "v2"
+        );
+        assert_eq!(
+            std::fs::read_to_string(sensitive.join("keep.txt")).unwrap(),
+            "unchanged"
+        );
+        assert_eq!(
+            std::fs::read_to_string(sensitive.join("new.txt")).unwrap(),
+            "new"
+        );
+        assert!(!sensitive.join("notes").exists());
+        assert!(!restored.join(MANIFEST_DIR).exists());
+
+        let restored = temp_dir.path().join("restored-second");
+        restore(&data_dir.join(&second), &restored, &KEY).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(restored.join("sensitive").join("edit.txt")).unwrap(),
+            "v3"
+        );
+
+        let restored = temp_dir.path().join("restored-full");
+        restore(&data_dir.join(&full), &restored, &KEY).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(restored.join("sensitive").join("edit.txt")).unwrap(),
+            "v1"
+        );
+        assert!(restored
+            .join("sensitive")
+            .join("notes")
+            .join("old.txt")
+            .exists());
+    }
+
+    #[test]
+    fn test_restore_keeps_existing_briefcase_dir_in_output() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let source = temp_dir.path().join("source");
+        std::fs::create_dir_all(&data_dir).unwrap();
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::write(source.join("a.txt"), "a").unwrap();
+        backup(&data_dir, &source, "2026-01-01_00-00-00", 6);
+        std::fs::write(source.join("a.txt"), "b").unwrap();
+        let last = backup(&data_dir, &source, "2026-01-02_00-00-00", 6);
+
+        let restored = temp_dir.path().join("restored");
+        let own = restored.join(MANIFEST_DIR);
+        std::fs::create_dir_all(&own).unwrap();
+        std::fs::write(own.join("notes.txt"), "mine").unwrap();
+
+        restore(&data_dir.join(&last), &restored, &KEY).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(restored.join("sensitive").join("a.txt")).unwrap(),
+            "b"
+        );
+        assert_eq!(
+            std::fs::read_to_string(own.join("notes.txt")).unwrap(),
+            "mine"
+        );
+        assert!(!own.join(MANIFEST_FILE).exists());
+    }
+
+    #[test]
+    fn test_fingerprint_ignores_mtime_but_not_content() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let source = temp_dir.path();
+        std::fs::write(source.join("a.txt"), "a").unwrap();
+        let fingerprint_now = || fingerprint(&scan(&[(source, "")], None).unwrap());
+
+        let original = fingerprint_now();
+        filetime::set_file_mtime(
+            source.join("a.txt"),
+            filetime::FileTime::from_unix_time(1_600_000_000, 0),
+        )
+        .unwrap();
+        assert_eq!(fingerprint_now(), original);
+
+        std::fs::write(source.join("a.txt"), "b").unwrap();
+        assert_ne!(fingerprint_now(), original);
+
+        std::fs::write(source.join("a.txt"), "a").unwrap();
+        std::fs::rename(source.join("a.txt"), source.join("b.txt")).unwrap();
+        assert_ne!(fingerprint_now(), original);
+    }
+
+    #[test]
+    fn test_full_backup_after_full_every_or_missing_base() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let source = temp_dir.path().join("source");
+        std::fs::create_dir_all(&data_dir).unwrap();
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::write(source.join("a.txt"), "a").unwrap();
+
+        assert!(!is_incremental(&backup(
+            &data_dir,
+            &source,
+            "2026-01-01_00-00-00",
+            1
+        )));
+        assert!(is_incremental(&backup(
+            &data_dir,
+            &source,
+            "2026-01-02_00-00-00",
+            1
+        )));
+        let full = backup(&data_dir, &source, "2026-01-03_00-00-00", 1);
+        assert!(!is_incremental(&full));
+
+        std::fs::remove_file(data_dir.join(&full)).unwrap();
+        assert!(!is_incremental(&backup(
+            &data_dir,
+            &source,
+            "2026-01-04_00-00-00",
+            1
+        )));
+    }
+
+    #[test]
+    fn test_restore_rejects_broken_chain() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let source = temp_dir.path().join("source");
+        std::fs::create_dir_all(&data_dir).unwrap();
+        std::fs::create_dir_all(&source).unwrap();
+        std::fs::write(source.join("a.txt"), "a").unwrap();
+
+        let full = backup(&data_dir, &source, "2026-01-01_00-00-00", 6);
+        backup(&data_dir, &source, "2026-01-02_00-00-00", 6);
+        let last = backup(&data_dir, &source, "2026-01-03_00-00-00", 6);
+        std::fs::remove_file(data_dir.join("Folder_2026-01-02_00-00-00_inc.7z")).unwrap();
+
+        let error = restore(&data_dir.join(&last), &temp_dir.path().join("out"), &KEY)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Backup chain is broken"));
+
+        std::fs::remove_file(data_dir.join(full)).unwrap();
+        let error = restore(&data_dir.join(&last), &temp_dir.path().join("out"), &KEY)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("No full backup found"));
+    }
+}
@@ -1,5 +1,21 @@
+pub mod bookmarks;
+pub mod chromium;
+pub mod chunks;
+pub mod command;
 pub mod compress;
+pub mod files;
 pub mod firefox;
 pub mod folder;
+pub mod git;
+pub mod gpg;
+pub mod hooks;
+pub mod incremental;
+pub mod pins;
+pub mod recovery;
 pub mod retention;
 pub mod service;
+pub mod sevenz;
+pub mod size;
+pub mod sqlite;
+pub mod ssh;
+pub mod thunderbird;
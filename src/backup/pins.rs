@@ -0,0 +1,124 @@
+use crate::backup::{chunks, compress};
+use crate::crypto::encrypt;
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Suffix of the sidecar file that exempts the backup it is named after from retention. The
+/// sidecar holds the note given when pinning and is synced along with the backup.
+pub const PIN_SUFFIX: &str = ".pin";
+
+/// Path of the sidecar that pins `backup`.
+pub fn pin_path(backup: &Path) -> PathBuf {
+    let mut name = backup.file_name().unwrap_or_default().to_os_string();
+    name.push(PIN_SUFFIX);
+    backup.with_file_name(name)
+}
+
+pub fn is_pinned(backup: &Path) -> bool {
+    pin_path(backup).is_file()
+}
+
+/// The note `backup` was pinned with, or `None` when it is not pinned.
+pub fn pin_note(backup: &Path) -> Option<String> {
+    fs::read_to_string(pin_path(backup))
+        .ok()
+        .map(|note| note.trim().to_string())
+}
+
+/// The backup a sidecar pins, or `None` for any other file.
+pub fn pinned_backup(pin: &Path) -> Option<PathBuf> {
+    let name = pin.file_name()?.to_str()?.strip_suffix(PIN_SUFFIX)?;
+    let backup = pin.with_file_name(name);
+    is_backup(&backup).then_some(backup)
+}
+
+/// Pins `backup`, replacing the note of an existing pin.
+pub fn pin(backup: &Path, note: &str) -> Result<()> {
+    if !is_backup(backup) {
+        return Err(anyhow!("{:?} is not a backup archive or snapshot", backup));
+    }
+    if !backup.is_file() {
+        return Err(anyhow!("Backup {:?} does not exist", backup));
+    }
+    encrypt::write_atomically(&pin_path(backup), note.as_bytes())
+}
+
+/// Removes the pin of `backup`, returning whether it was pinned.
+pub fn unpin(backup: &Path) -> Result<bool> {
+    match fs::remove_file(pin_path(backup)) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(anyhow!("Failed to unpin {:?}: {}", backup, e)),
+    }
+}
+
+/// Finds a backup given as a path, or as a file name in the data directory or its snapshot
+/// directory. Backups that are gone but still pinned are found too, so they can be unpinned.
+pub fn resolve(data_dir: &Path, backup: &str) -> Result<PathBuf> {
+    let path = Path::new(backup);
+    let candidates = if path.components().count() > 1 {
+        vec![path.to_path_buf()]
+    } else {
+        vec![
+            data_dir.join(backup),
+            chunks::snapshots_dir(data_dir).join(backup),
+        ]
+    };
+    candidates
+        .into_iter()
+        .find(|candidate| candidate.is_file() || is_pinned(candidate))
+        .ok_or_else(|| anyhow!("No backup named {:?} in {:?}", backup, data_dir))
+}
+
+fn is_backup(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    compress::is_archive(&name) || chunks::is_snapshot(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pin_and_unpin() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path();
+        let archive = data_dir.join("Folder_laptop_2026-04-22_10-00-00.7z");
+        fs::write(&archive, b"backup").unwrap();
+
+        assert!(!is_pinned(&archive));
+        pin(&archive, "before the migration").unwrap();
+        assert!(is_pinned(&archive));
+        assert_eq!(pin_note(&archive).as_deref(), Some("before the migration"));
+        assert_eq!(
+            pinned_backup(&pin_path(&archive)).as_deref(),
+            Some(archive.as_path())
+        );
+        assert_eq!(
+            resolve(data_dir, "Folder_laptop_2026-04-22_10-00-00.7z").unwrap(),
+            archive
+        );
+
+        // A pinned backup that was deleted can still be found and unpinned
+        fs::remove_file(&archive).unwrap();
+        let found = resolve(data_dir, "Folder_laptop_2026-04-22_10-00-00.7z").unwrap();
+        assert!(unpin(&found).unwrap());
+        assert!(!unpin(&found).unwrap());
+        assert!(resolve(data_dir, "Folder_laptop_2026-04-22_10-00-00.7z").is_err());
+    }
+
+    #[test]
+    fn test_pin_rejects_non_backups() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let index = temp_dir.path().join("Folder.index");
+        fs::write(&index, b"index").unwrap();
+        assert!(pin(&index, "").is_err());
+        assert!(pin(&temp_dir.path().join("Folder_2026-04-22_10-00-00.7z"), "").is_err());
+        assert_eq!(
+            pinned_backup(&temp_dir.path().join("Folder.index.pin")),
+            None
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::backup::compress;
+use crate::crypto::encrypt::PARTIAL_SUFFIX;
+use crate::crypto::stream;
+use crate::models::temp_dir;
+use anyhow::Result;
+use log::{info, warn};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Suffixes of the plaintext archives older versions staged while backing up or decrypting.
+const LEGACY_TEMP_SUFFIXES: [&str; 2] = [".temp", ".temp.7z"];
+
+/// Staging directories older than this are assumed to belong to a process that died.
+const STALE_STAGING_HOURS: i64 = 24;
+
+/// Removes what interrupted backups left behind: `.partial` files, plaintext `.temp` archives,
+/// archives too short to hold any ciphertext, and stale staging directories.
+///
+/// Must only run while holding the data directory lock, since another process's `.partial`
+/// files are still being written. Plaintext is overwritten before it is unlinked.
+pub fn clean_up(data_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    if data_dir.exists() {
+        clean_dir(data_dir, &mut removed)?;
+    }
+
+    for dir in temp_dir::stale_staging_dirs(chrono::Duration::hours(STALE_STAGING_HOURS)) {
+        match shred_dir(&dir) {
+            Ok(()) => removed.push(dir),
+            Err(e) => warn!("Failed to remove stale staging directory {:?}: {}", dir, e),
+        }
+    }
+
+    for path in &removed {
+        info!("Removed leftover of an interrupted backup: {:?}", path);
+    }
+    Ok(removed)
+}
+
+fn clean_dir(dir: &Path, removed: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            clean_dir(&path, removed)?;
+            continue;
+        }
+        if !file_type.is_file() {
+            continue;
+        }
+
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if LEGACY_TEMP_SUFFIXES
+            .iter()
+            .any(|suffix| name.ends_with(suffix))
+        {
+            shred_file(&path)?;
+        } else if name.ends_with(PARTIAL_SUFFIX)
+            || (compress::is_archive(&name) && entry.metadata()?.len() < stream::encrypted_len(0))
+        {
+            fs::remove_file(&path)?;
+        } else {
+            continue;
+        }
+        removed.push(path);
+    }
+    Ok(())
+}
+
+/// Overwrites a file with zeros before removing it. This is best effort: copy-on-write and
+/// flash storage may keep the old blocks.
+fn shred_file(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+
+    let length = fs::metadata(path)?.len();
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    let zeros = [0u8; 64 * 1024];
+    let mut remaining = length;
+    while remaining > 0 {
+        let chunk = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..chunk])?;
+        remaining -= chunk as u64;
+    }
+    file.sync_all()?;
+    drop(file);
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+fn shred_dir(dir: &Path) -> Result<()> {
+    // Staged folders keep their modes; read-only directories block removal
+    temp_dir::make_dirs_writable(dir);
+    shred_files(dir)?;
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+fn shred_files(dir: &Path) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            shred_files(&entry.path())?;
+        } else if file_type.is_file() {
+            shred_file(&entry.path())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_up_removes_only_leftovers() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        let chunk_dir = data_dir.join("repository/chunks/ab");
+        fs::create_dir_all(&chunk_dir).unwrap();
+
+        let complete = data_dir.join("Folder_2026-04-22_10-00-00.7z");
+        fs::write(&complete, [1u8; 128]).unwrap();
+        let index = data_dir.join("Folder.index");
+        fs::write(&index, [1u8; 128]).unwrap();
+        let chunk = chunk_dir.join("ab01");
+        fs::write(&chunk, [1u8; 128]).unwrap();
+
+        let leftovers = [
+            data_dir.join("Folder_2026-04-22_11-00-00.7z.0123456789abcdef.partial"),
+            data_dir.join("Firefox_2026-04-22_11-00-00.7z.temp"),
+            data_dir.join("Firefox_2026-04-22_09-00-00.temp.7z"),
+            data_dir.join("Git_2026-04-22_11-00-00.tar.zst"),
+            chunk_dir.join("ab02.0123456789abcdef.partial"),
+        ];
+        for path in &leftovers {
+            fs::write(path, b"plaintext").unwrap();
+        }
+
+        let mut removed = clean_up(&data_dir).unwrap();
+        removed.retain(|path| path.starts_with(&data_dir));
+        removed.sort();
+        let mut expected = leftovers.to_vec();
+        expected.sort();
+        assert_eq!(removed, expected);
+
+        assert!(leftovers.iter().all(|path| !path.exists()));
+        assert!(complete.exists() && index.exists() && chunk.exists());
+    }
+
+    #[test]
+    fn test_shred_dir_removes_read_only_content() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let staging = temp_dir.path().join("briefcase_1_2");
+        let nested = staging.join("ssh");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join("id_ed25519"), "PRIVATE KEY").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(nested.join("id_ed25519"), fs::Permissions::from_mode(0o400))
+                .unwrap();
+            fs::set_permissions(&nested, fs::Permissions::from_mode(0o500)).unwrap();
+        }
+
+        shred_dir(&staging).unwrap();
+        assert!(!staging.exists());
+    }
+}
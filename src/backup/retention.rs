@@ -1,49 +1,282 @@
-use crate::models::backup_file::SourceType;
+use crate::backup::{compress, pins};
+use crate::models::backup_file::{BackupName, SourceType};
+use crate::models::config::RetentionPolicy;
 use anyhow::Result;
+use chrono::{Datelike, Duration, Local, NaiveDateTime, Timelike};
 use std::fs;
 use std::path::PathBuf;
 
+/// An archive or snapshot considered by retention.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Archive {
+    pub path: PathBuf,
+    pub timestamp: NaiveDateTime,
+    pub size: u64,
+    pub pinned: bool,
+}
+
+/// What the retention planner sees of one removable unit: a full backup with its increments,
+/// or a single snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backup {
+    /// When the unit's newest archive was taken.
+    pub timestamp: NaiveDateTime,
+    /// Archives in the unit, each a restore point counted by `keep_last`.
+    pub archives: usize,
+    /// Bytes the unit takes up, counted by `max_total_size`.
+    pub size: u64,
+    /// Whether any archive in the unit is pinned.
+    pub pinned: bool,
+}
+
+/// Applies the source's retention policy to the archives `host` took of it. Other hosts'
+/// archives and pinned archives are never touched.
+///
+/// Incremental archives are only usable together with the full backup they build on, so a
+/// full backup and its increments are kept or removed as one chain. `keep_last` counts
+/// archives, so the count can exceed it by up to one chain.
 pub fn enforce_retention(
     data_dir: &PathBuf,
     source_type: &SourceType,
-    max_retention: u32,
+    host: &str,
+    policy: &RetentionPolicy,
 ) -> Result<()> {
-    let source_prefix = match source_type {
-        SourceType::Firefox => "Firefox_",
-        SourceType::Folder => "Folder_",
+    let now = Local::now().naive_local();
+    for path in expired(chains(data_dir, source_type, host)?, policy, now) {
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+/// Archives retention would remove after one more archive of the source is written, as a full
+/// backup or as an increment of the newest chain.
+pub fn expired_after_backup(
+    data_dir: &PathBuf,
+    source_type: &SourceType,
+    host: &str,
+    policy: &RetentionPolicy,
+    incremental: bool,
+) -> Result<Vec<PathBuf>> {
+    let mut chains = if data_dir.exists() {
+        chains(data_dir, source_type, host)?
+    } else {
+        Vec::new()
+    };
+    let now = Local::now().naive_local();
+    // The newest chain is never removed, so the placeholder is never returned
+    let pending = Archive {
+        path: PathBuf::new(),
+        timestamp: now,
+        size: 0,
+        pinned: false,
     };
+    match chains.last_mut() {
+        Some(chain) if incremental => chain.push(pending),
+        _ => chains.push(vec![pending]),
+    }
+    Ok(expired(chains, policy, now))
+}
 
-    let mut entries: Vec<_> = fs::read_dir(data_dir)?
+/// The archives `host` took of the source, grouped into chains, oldest first.
+///
+/// Archives are ordered by the timestamp in their names, since copies and remote restores
+/// reset mtimes. Files whose names do not parse are not briefcase archives and are ignored.
+fn chains(data_dir: &PathBuf, source_type: &SourceType, host: &str) -> Result<Vec<Vec<Archive>>> {
+    let mut archives: Vec<(NaiveDateTime, bool, PathBuf, u64)> = fs::read_dir(data_dir)?
         .filter_map(|e| e.ok())
-        .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("7z"))
-        .filter(|e| {
-            e.path()
+        .filter_map(|e| {
+            let path = e.path();
+            let name = path
                 .file_name()
                 .and_then(|name| name.to_str())
-                .is_some_and(|name| name.starts_with(source_prefix))
+                .filter(|name| compress::is_archive(name))
+                .and_then(BackupName::parse)?;
+            let size = e.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            (name.source_type == *source_type && name.is_from_host(host)).then_some((
+                name.timestamp,
+                name.incremental,
+                path,
+                size,
+            ))
         })
         .collect();
 
-    // Sort by modified time, oldest first
-    entries.sort_by_key(|e| e.metadata().unwrap().modified().unwrap());
+    // A full backup sorts before an increment taken in the same second
+    archives.sort();
 
-    if entries.len() > max_retention as usize {
-        let to_remove = entries.len() - max_retention as usize;
-        for entry in entries.into_iter().take(to_remove) {
-            fs::remove_file(entry.path())?;
+    // A chain starts at each full backup and runs until the next one
+    let mut chains: Vec<Vec<Archive>> = Vec::new();
+    for (timestamp, is_incremental, path, size) in archives {
+        let archive = Archive {
+            pinned: pins::is_pinned(&path),
+            path,
+            timestamp,
+            size,
+        };
+        match chains.last_mut() {
+            Some(chain) if is_incremental => chain.push(archive),
+            _ => chains.push(vec![archive]),
         }
     }
 
-    Ok(())
+    Ok(chains)
+}
+
+/// Paths of the chains `policy` does not keep at `now`. Empty chains are ignored.
+pub fn expired(
+    chains: Vec<Vec<Archive>>,
+    policy: &RetentionPolicy,
+    now: NaiveDateTime,
+) -> Vec<PathBuf> {
+    let chains: Vec<Vec<Archive>> = chains
+        .into_iter()
+        .filter(|chain| !chain.is_empty())
+        .collect();
+    let backups: Vec<Backup> = chains
+        .iter()
+        .map(|chain| Backup {
+            timestamp: chain
+                .iter()
+                .map(|archive| archive.timestamp)
+                .max()
+                .unwrap_or_default(),
+            archives: chain.len(),
+            size: chain.iter().map(|archive| archive.size).sum(),
+            pinned: chain.iter().any(|archive| archive.pinned),
+        })
+        .collect();
+
+    plan(&backups, policy, now)
+        .into_iter()
+        .zip(chains)
+        .filter(|(keep, _)| !keep)
+        .flat_map(|(_, chain)| chain.into_iter().map(|archive| archive.path))
+        .collect()
+}
+
+/// Calendar periods the `keep_*` rules pick one backup from.
+#[derive(Debug, Clone, Copy)]
+enum Period {
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Period {
+    /// Identifies the period `timestamp` falls in; equal keys mean the same period.
+    fn key(self, timestamp: NaiveDateTime) -> i64 {
+        let date = timestamp.date();
+        match self {
+            Period::Hour => i64::from(date.num_days_from_ce()) * 24 + i64::from(timestamp.hour()),
+            Period::Day => i64::from(date.num_days_from_ce()),
+            Period::Week => {
+                let week = date.iso_week();
+                i64::from(week.year()) * 100 + i64::from(week.week())
+            }
+            Period::Month => i64::from(date.year()) * 12 + i64::from(date.month0()),
+            Period::Year => i64::from(date.year()),
+        }
+    }
+}
+
+/// Decides which backups `policy` keeps at `now`, returning one flag per backup.
+///
+/// Pinned backups are always kept and otherwise ignored, so they count towards no rule or
+/// limit. The other backups may be given in any order. `keep_last` keeps the newest backups
+/// until they hold that many archives. Each period rule walks from the newest backup and
+/// keeps the first one it meets in each period, for that many distinct periods. Without any
+/// `keep_*` rule every backup is kept. Of the kept backups, those taken more than
+/// `max_age_days` before `now` are dropped, and so is every backup from the first one that no
+/// longer fits in `max_total_size`, counting from the newest. The newest backup is always
+/// kept, so a policy never removes the backup that was just taken.
+pub fn plan(backups: &[Backup], policy: &RetentionPolicy, now: NaiveDateTime) -> Vec<bool> {
+    // Newest first; among equal timestamps the later one in `backups` counts as newer
+    let mut order: Vec<usize> = (0..backups.len())
+        .filter(|&index| !backups[index].pinned)
+        .collect();
+    order.sort_by(|&a, &b| (backups[b].timestamp, b).cmp(&(backups[a].timestamp, a)));
+
+    let mut keep = vec![!policy.has_keep_rules(); backups.len()];
+
+    if let Some(keep_last) = policy.keep_last {
+        let mut archives = 0;
+        for &index in &order {
+            if archives >= keep_last as usize {
+                break;
+            }
+            keep[index] = true;
+            archives += backups[index].archives;
+        }
+    }
+
+    for (limit, period) in [
+        (policy.keep_hourly, Period::Hour),
+        (policy.keep_daily, Period::Day),
+        (policy.keep_weekly, Period::Week),
+        (policy.keep_monthly, Period::Month),
+        (policy.keep_yearly, Period::Year),
+    ] {
+        let Some(limit) = limit else {
+            continue;
+        };
+        let mut periods = 0;
+        let mut last_key = None;
+        for &index in &order {
+            if periods >= limit {
+                break;
+            }
+            let key = period.key(backups[index].timestamp);
+            if last_key != Some(key) {
+                keep[index] = true;
+                periods += 1;
+                last_key = Some(key);
+            }
+        }
+    }
+
+    if let Some(max_age_days) = policy.max_age_days {
+        let cutoff = now - Duration::days(i64::from(max_age_days));
+        for &index in &order {
+            if backups[index].timestamp < cutoff {
+                keep[index] = false;
+            }
+        }
+    }
+
+    if let Some(max_total_size) = policy.max_total_size {
+        let mut total: u64 = 0;
+        let mut full = false;
+        for &index in &order {
+            if !keep[index] {
+                continue;
+            }
+            total = total.saturating_add(backups[index].size);
+            full |= total > max_total_size;
+            if full {
+                keep[index] = false;
+            }
+        }
+    }
+
+    if let Some(&newest) = order.first() {
+        keep[newest] = true;
+    }
+    for (keep, backup) in keep.iter_mut().zip(backups) {
+        *keep |= backup.pinned;
+    }
+    keep
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::thread;
-    use std::time::Duration;
     use tempfile::tempdir;
 
+    const HOST: &str = "laptop";
+
     #[test]
     fn test_enforce_retention_counts_each_source_separately() {
         let temp_dir = tempdir().unwrap();
@@ -58,10 +291,15 @@ mod tests {
             "Folder_2026-04-22_10-10-00.7z",
         ] {
             fs::write(data_dir.join(file_name), b"backup").unwrap();
-            thread::sleep(Duration::from_millis(20));
         }
 
-        enforce_retention(&data_dir, &SourceType::Firefox, 2).unwrap();
+        enforce_retention(
+            &data_dir,
+            &SourceType::Firefox,
+            HOST,
+            &RetentionPolicy::keep_last(2),
+        )
+        .unwrap();
 
         assert!(!data_dir.join("Firefox_2026-04-22_10-00-00.7z").exists());
         assert!(data_dir.join("Firefox_2026-04-22_10-05-00.7z").exists());
@@ -71,4 +309,579 @@ mod tests {
         assert!(data_dir.join("Folder_2026-04-22_10-05-00.7z").exists());
         assert!(data_dir.join("Folder_2026-04-22_10-10-00.7z").exists());
     }
+
+    #[test]
+    fn test_enforce_retention_removes_incremental_chains_whole() {
+        let temp_dir = tempdir().unwrap();
+        let data_dir = temp_dir.path().to_path_buf();
+
+        for file_name in [
+            "Folder_2026-04-22_10-00-00.7z",
+            "Folder_2026-04-22_10-05-00_inc.7z",
+            "Folder_2026-04-22_10-10-00_inc.7z",
+            "Folder_2026-04-22_10-15-00.7z",
+            "Folder_2026-04-22_10-20-00_inc.7z",
+        ] {
+            fs::write(data_dir.join(file_name), b"backup").unwrap();
+        }
+
+        // Dropping the first chain would leave only two archives
+        enforce_retention(
+            &data_dir,
+            &SourceType::Folder,
+            HOST,
+            &RetentionPolicy::keep_last(3),
+        )
+        .unwrap();
+        assert_eq!(fs::read_dir(&data_dir).unwrap().count(), 5);
+
+        enforce_retention(
+            &data_dir,
+            &SourceType::Folder,
+            HOST,
+            &RetentionPolicy::keep_last(2),
+        )
+        .unwrap();
+        assert!(!data_dir.join("Folder_2026-04-22_10-00-00.7z").exists());
+        assert!(!data_dir.join("Folder_2026-04-22_10-05-00_inc.7z").exists());
+        assert!(!data_dir.join("Folder_2026-04-22_10-10-00_inc.7z").exists());
+        assert!(data_dir.join("Folder_2026-04-22_10-15-00.7z").exists());
+        assert!(data_dir.join("Folder_2026-04-22_10-20-00_inc.7z").exists());
+
+        // A new increment joins the newest chain; a new full backup lets the old chain go
+        let expired = |incremental| {
+            expired_after_backup(
+                &data_dir,
+                &SourceType::Folder,
+                HOST,
+                &RetentionPolicy::keep_last(1),
+                incremental,
+            )
+            .unwrap()
+        };
+        assert!(expired(true).is_empty());
+        assert_eq!(
+            expired(false),
+            vec![
+                data_dir.join("Folder_2026-04-22_10-15-00.7z"),
+                data_dir.join("Folder_2026-04-22_10-20-00_inc.7z"),
+            ]
+        );
+
+        // The newest chain is never removed, even when it alone exceeds the limit
+        enforce_retention(
+            &data_dir,
+            &SourceType::Folder,
+            HOST,
+            &RetentionPolicy::keep_last(1),
+        )
+        .unwrap();
+        assert_eq!(fs::read_dir(&data_dir).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_enforce_retention_leaves_other_hosts_alone() {
+        let temp_dir = tempdir().unwrap();
+        let data_dir = temp_dir.path().to_path_buf();
+
+        for file_name in [
+            "Ssh_2026-04-22_09-00-00.7z",
+            "Ssh_desktop_2026-04-22_09-30-00.7z",
+            "Ssh_laptop_2026-04-22_10-00-00.7z",
+            "Ssh_desktop_2026-04-22_10-30-00.7z",
+            "Ssh_laptop_2026-04-22_11-00-00.7z",
+        ] {
+            fs::write(data_dir.join(file_name), b"backup").unwrap();
+        }
+
+        // Host-less names predate host ids and count as this host's
+        enforce_retention(
+            &data_dir,
+            &SourceType::Ssh,
+            HOST,
+            &RetentionPolicy::keep_last(1),
+        )
+        .unwrap();
+        assert!(!data_dir.join("Ssh_2026-04-22_09-00-00.7z").exists());
+        assert!(!data_dir.join("Ssh_laptop_2026-04-22_10-00-00.7z").exists());
+        assert!(data_dir.join("Ssh_laptop_2026-04-22_11-00-00.7z").exists());
+        assert!(data_dir.join("Ssh_desktop_2026-04-22_09-30-00.7z").exists());
+        assert!(data_dir.join("Ssh_desktop_2026-04-22_10-30-00.7z").exists());
+    }
+
+    #[test]
+    fn test_enforce_retention_orders_by_name_not_mtime() {
+        let temp_dir = tempdir().unwrap();
+        let data_dir = temp_dir.path().to_path_buf();
+
+        // Restored from a remote in reverse order, so the oldest archive has the newest mtime
+        let names = [
+            "Git_laptop_2026-04-22_12-00-00.7z",
+            "Git_laptop_2026-04-22_11-00-00.7z",
+            "Git_laptop_2026-04-22_10-00-00.7z",
+        ];
+        for (age, file_name) in names.iter().enumerate() {
+            let path = data_dir.join(file_name);
+            fs::write(&path, b"backup").unwrap();
+            let mtime = filetime::FileTime::from_unix_time(1_800_000_000 - age as i64 * 3600, 0);
+            filetime::set_file_mtime(&path, mtime).unwrap();
+        }
+        let unparseable = [
+            "Git_laptop.7z",
+            "Git_laptop_2026-99-99_00-00-00.7z",
+            "Git_laptop_2026-04-22_09-00-00.7z.0123456789abcdef.partial",
+        ];
+        for file_name in unparseable {
+            fs::write(data_dir.join(file_name), b"backup").unwrap();
+        }
+
+        enforce_retention(
+            &data_dir,
+            &SourceType::Git,
+            HOST,
+            &RetentionPolicy::keep_last(2),
+        )
+        .unwrap();
+
+        assert!(data_dir.join(names[0]).exists());
+        assert!(data_dir.join(names[1]).exists());
+        assert!(!data_dir.join(names[2]).exists());
+        assert!(unparseable
+            .iter()
+            .all(|file_name| data_dir.join(file_name).exists()));
+    }
+
+    fn at(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn backups(times: &[&str]) -> Vec<Backup> {
+        times
+            .iter()
+            .map(|time| Backup {
+                timestamp: at(time),
+                archives: 1,
+                size: 1,
+                pinned: false,
+            })
+            .collect()
+    }
+
+    /// The times of the backups `policy` keeps, in input order.
+    fn kept<'a>(times: &[&'a str], policy: &RetentionPolicy, now: &str) -> Vec<&'a str> {
+        plan(&backups(times), policy, at(now))
+            .into_iter()
+            .zip(times)
+            .filter(|(keep, _)| *keep)
+            .map(|(_, time)| *time)
+            .collect()
+    }
+
+    const NOW: &str = "2026-04-22 12:00";
+
+    #[test]
+    fn test_plan_of_nothing_is_empty() {
+        assert!(plan(&[], &RetentionPolicy::keep_last(3), at(NOW)).is_empty());
+    }
+
+    #[test]
+    fn test_plan_keep_last_matches_max_retention_for_every_chain_layout() {
+        // The count-based rule retention used before policies existed
+        fn legacy(lengths: &[usize], max_retention: usize) -> Vec<bool> {
+            let mut remaining: usize = lengths.iter().sum();
+            let mut keep = vec![true; lengths.len()];
+            for (index, length) in lengths.iter().enumerate() {
+                if remaining - length < max_retention {
+                    break;
+                }
+                remaining -= length;
+                keep[index] = false;
+            }
+            keep
+        }
+
+        // Every layout of up to five chains of one to three archives, oldest first
+        let mut layouts: Vec<Vec<usize>> = vec![Vec::new()];
+        let mut shorter: Vec<Vec<usize>> = vec![Vec::new()];
+        for _ in 0..5 {
+            shorter = shorter
+                .iter()
+                .flat_map(|layout| (1..=3).map(|length| [layout.as_slice(), &[length]].concat()))
+                .collect();
+            layouts.extend(shorter.iter().cloned());
+        }
+
+        for layout in &layouts {
+            let backups: Vec<Backup> = layout
+                .iter()
+                .enumerate()
+                .map(|(day, &archives)| Backup {
+                    timestamp: at("2026-04-01 00:00") + Duration::days(day as i64),
+                    archives,
+                    size: 1,
+                    pinned: false,
+                })
+                .collect();
+            for max_retention in 1..=10 {
+                let mut expected = legacy(layout, max_retention);
+                if let Some(newest) = expected.last_mut() {
+                    *newest = true;
+                }
+                assert_eq!(
+                    plan(
+                        &backups,
+                        &RetentionPolicy::keep_last(max_retention as u32),
+                        at(NOW)
+                    ),
+                    expected,
+                    "{:?} keeping {}",
+                    layout,
+                    max_retention
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_plan_keep_hourly_keeps_newest_of_each_hour() {
+        let times = [
+            "2026-04-22 08:10",
+            "2026-04-22 09:00",
+            "2026-04-22 09:40",
+            "2026-04-22 10:20",
+            "2026-04-22 11:00",
+            "2026-04-22 11:30",
+        ];
+        let policy = RetentionPolicy {
+            keep_hourly: Some(3),
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(
+            kept(&times, &policy, NOW),
+            vec!["2026-04-22 09:40", "2026-04-22 10:20", "2026-04-22 11:30"]
+        );
+    }
+
+    #[test]
+    fn test_plan_keep_daily_counts_only_days_with_backups() {
+        let times = [
+            "2026-04-01 23:00",
+            "2026-04-10 08:00",
+            "2026-04-10 20:00",
+            "2026-04-21 07:00",
+            "2026-04-22 07:00",
+        ];
+        let policy = RetentionPolicy {
+            keep_daily: Some(3),
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(
+            kept(&times, &policy, NOW),
+            vec!["2026-04-10 20:00", "2026-04-21 07:00", "2026-04-22 07:00"]
+        );
+    }
+
+    #[test]
+    fn test_plan_keep_weekly_uses_iso_weeks() {
+        // 2026-04-19 is a Sunday; 2027-01-01 is a Friday in ISO week 53 of 2026
+        let times = [
+            "2026-04-13 09:00",
+            "2026-04-19 09:00",
+            "2026-04-20 09:00",
+            "2026-04-26 09:00",
+            "2026-12-28 09:00",
+            "2027-01-01 09:00",
+            "2027-01-04 09:00",
+        ];
+        let policy = RetentionPolicy {
+            keep_weekly: Some(10),
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(
+            kept(&times, &policy, "2027-01-05 00:00"),
+            vec![
+                "2026-04-19 09:00",
+                "2026-04-26 09:00",
+                "2027-01-01 09:00",
+                "2027-01-04 09:00"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_keep_monthly_and_yearly() {
+        let times = [
+            "2024-06-01 00:00",
+            "2024-12-31 00:00",
+            "2025-03-01 00:00",
+            "2025-03-31 00:00",
+            "2026-01-15 00:00",
+            "2026-02-01 00:00",
+            "2026-02-28 00:00",
+        ];
+        let monthly = RetentionPolicy {
+            keep_monthly: Some(3),
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(
+            kept(&times, &monthly, NOW),
+            vec!["2025-03-31 00:00", "2026-01-15 00:00", "2026-02-28 00:00"]
+        );
+
+        let yearly = RetentionPolicy {
+            keep_yearly: Some(5),
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(
+            kept(&times, &yearly, NOW),
+            vec!["2024-12-31 00:00", "2025-03-31 00:00", "2026-02-28 00:00"]
+        );
+    }
+
+    #[test]
+    fn test_plan_keeps_union_of_rules() {
+        let times = [
+            "2025-11-30 00:00",
+            "2026-03-31 00:00",
+            "2026-04-15 00:00",
+            "2026-04-20 10:00",
+            "2026-04-21 10:00",
+            "2026-04-22 10:00",
+            "2026-04-22 11:00",
+        ];
+        let policy = RetentionPolicy {
+            keep_last: Some(1),
+            keep_daily: Some(2),
+            keep_monthly: Some(3),
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(
+            kept(&times, &policy, NOW),
+            vec![
+                "2025-11-30 00:00",
+                "2026-03-31 00:00",
+                "2026-04-21 10:00",
+                "2026-04-22 11:00"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_plan_max_age_without_keep_rules() {
+        let times = ["2026-01-01 00:00", "2026-04-01 00:00", "2026-04-20 00:00"];
+        let policy = RetentionPolicy {
+            max_age_days: Some(30),
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(
+            kept(&times, &policy, NOW),
+            vec!["2026-04-01 00:00", "2026-04-20 00:00"]
+        );
+    }
+
+    #[test]
+    fn test_plan_max_age_overrides_keep_rules_but_not_newest() {
+        let times = ["2025-01-01 00:00", "2025-06-01 00:00", "2025-12-01 00:00"];
+        let policy = RetentionPolicy {
+            keep_monthly: Some(12),
+            max_age_days: Some(200),
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(kept(&times, &policy, NOW), vec!["2025-12-01 00:00"]);
+
+        let policy = RetentionPolicy {
+            max_age_days: Some(1),
+            ..policy
+        };
+        assert_eq!(kept(&times, &policy, NOW), vec!["2025-12-01 00:00"]);
+    }
+
+    #[test]
+    fn test_plan_max_total_size_fills_from_newest() {
+        let sizes = [10, 1, 30, 20, 40];
+        let backups: Vec<Backup> = sizes
+            .iter()
+            .enumerate()
+            .map(|(day, &size)| Backup {
+                timestamp: at("2026-04-01 00:00") + Duration::days(day as i64),
+                archives: 1,
+                size,
+                pinned: false,
+            })
+            .collect();
+        let policy = |max_total_size| RetentionPolicy {
+            max_total_size: Some(max_total_size),
+            ..RetentionPolicy::default()
+        };
+
+        // The small oldest backup still fits, but nothing older than the first misfit is kept
+        assert_eq!(
+            plan(&backups, &policy(65), at(NOW)),
+            vec![false, false, false, true, true]
+        );
+        assert_eq!(
+            plan(&backups, &policy(101), at(NOW)),
+            vec![true, true, true, true, true]
+        );
+        // The newest backup is kept even when it alone is over budget
+        assert_eq!(
+            plan(&backups, &policy(5), at(NOW)),
+            vec![false, false, false, false, true]
+        );
+
+        // Only backups the keep rules selected count towards the budget
+        let policy = RetentionPolicy {
+            keep_last: Some(2),
+            keep_weekly: Some(1),
+            max_total_size: Some(70),
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(
+            plan(&backups, &policy, at(NOW)),
+            vec![false, false, false, true, true]
+        );
+    }
+
+    #[test]
+    fn test_plan_invariants_hold_for_every_subset() {
+        let timeline = [
+            "2025-12-31 23:00",
+            "2026-01-01 00:30",
+            "2026-03-15 12:00",
+            "2026-04-13 09:00",
+            "2026-04-19 18:00",
+            "2026-04-22 09:10",
+            "2026-04-22 09:50",
+            "2026-04-22 11:00",
+        ];
+        let policies = [
+            RetentionPolicy::keep_last(1),
+            RetentionPolicy::keep_last(4),
+            RetentionPolicy {
+                keep_hourly: Some(2),
+                keep_daily: Some(2),
+                keep_weekly: Some(2),
+                keep_monthly: Some(2),
+                keep_yearly: Some(2),
+                ..RetentionPolicy::default()
+            },
+            RetentionPolicy {
+                keep_daily: Some(7),
+                max_age_days: Some(30),
+                ..RetentionPolicy::default()
+            },
+            RetentionPolicy {
+                max_total_size: Some(3),
+                ..RetentionPolicy::default()
+            },
+        ];
+
+        for mask in 1u32..(1 << timeline.len()) {
+            let times: Vec<&str> = timeline
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| mask & (1 << index) != 0)
+                .map(|(_, time)| *time)
+                .collect();
+            for policy in &policies {
+                let keep = kept(&times, policy, NOW);
+
+                // The newest backup always survives
+                assert_eq!(keep.last(), times.last(), "{:?} {:?}", times, policy);
+
+                // Input order does not matter
+                let reversed: Vec<&str> = times.iter().rev().copied().collect();
+                let mut keep_reversed = kept(&reversed, policy, NOW);
+                keep_reversed.reverse();
+                assert_eq!(keep, keep_reversed, "{:?} {:?}", times, policy);
+
+                // Planning again over what the keep rules kept removes nothing more
+                if policy.max_total_size.is_none() && policy.max_age_days.is_none() {
+                    let replanned = kept(&keep, policy, NOW);
+                    assert_eq!(replanned, keep, "{:?} {:?}", times, policy);
+                }
+
+                if let Some(keep_last) = policy.keep_last {
+                    assert_eq!(
+                        keep.len(),
+                        times.len().min(keep_last as usize),
+                        "{:?} {:?}",
+                        times,
+                        policy
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_plan_keeps_pinned_backups_without_counting_them() {
+        let mut backups = backups(&[
+            "2026-01-01 00:00",
+            "2026-04-20 00:00",
+            "2026-04-21 00:00",
+            "2026-04-22 00:00",
+        ]);
+        backups[0].pinned = true;
+        backups[2].pinned = true;
+
+        let policy = RetentionPolicy {
+            keep_last: Some(1),
+            max_age_days: Some(1),
+            ..RetentionPolicy::default()
+        };
+        assert_eq!(
+            plan(&backups, &policy, at(NOW)),
+            vec![true, false, true, true]
+        );
+
+        // A pinned newest backup does not stand in for the newest unpinned one
+        backups[3].pinned = true;
+        assert_eq!(
+            plan(&backups, &policy, at(NOW)),
+            vec![true, true, true, true]
+        );
+    }
+
+    #[test]
+    fn test_enforce_retention_keeps_pinned_chains() {
+        let temp_dir = tempdir().unwrap();
+        let data_dir = temp_dir.path().to_path_buf();
+
+        for file_name in [
+            "Folder_laptop_2026-04-22_10-00-00.7z",
+            "Folder_laptop_2026-04-22_10-05-00_inc.7z",
+            "Folder_laptop_2026-04-22_10-10-00.7z",
+            "Folder_laptop_2026-04-22_10-15-00.7z",
+        ] {
+            fs::write(data_dir.join(file_name), b"backup").unwrap();
+        }
+        // Pinning an increment keeps the full backup it needs
+        pins::pin(
+            &data_dir.join("Folder_laptop_2026-04-22_10-05-00_inc.7z"),
+            "",
+        )
+        .unwrap();
+
+        enforce_retention(
+            &data_dir,
+            &SourceType::Folder,
+            HOST,
+            &RetentionPolicy::keep_last(1),
+        )
+        .unwrap();
+
+        assert!(data_dir
+            .join("Folder_laptop_2026-04-22_10-00-00.7z")
+            .exists());
+        assert!(data_dir
+            .join("Folder_laptop_2026-04-22_10-05-00_inc.7z")
+            .exists());
+        assert!(!data_dir
+            .join("Folder_laptop_2026-04-22_10-10-00.7z")
+            .exists());
+        assert!(data_dir
+            .join("Folder_laptop_2026-04-22_10-15-00.7z")
+            .exists());
+    }
 }
@@ -1,14 +1,115 @@
-use crate::backup::{compress, firefox, folder, retention};
+use crate::backup::{
+    chromium, chunks, command, compress, files, firefox, folder, git, gpg, hooks, incremental,
+    retention, size, ssh, thunderbird,
+};
 
-use crate::models::backup_file::{BackupFile, SourceType};
-use crate::models::config::Config;
+use crate::crypto::{encrypt, stream};
+use crate::models::backup_file::{backup_stem, BackupFile, BackupName, SourceType};
+use crate::models::config::{ArchiveSettings, Config, RepositoryFormat, DEFAULT_FULL_EVERY};
 use crate::models::temp_dir::TempDir;
+use anyhow::anyhow;
 use chrono::Local;
-use log::{info, warn};
-use std::path::PathBuf;
+use log::{error, info, warn};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinSet;
+use zeroize::Zeroizing;
 
+/// Result of backing up one source.
+enum SourceBackup {
+    Created {
+        backup_file: BackupFile,
+        fingerprint: String,
+    },
+    /// The data matched the last archive's fingerprint, so no archive was written.
+    Unchanged { fingerprint: String },
+}
+
+/// How one source's backup ended.
+#[derive(Debug)]
+pub enum SourceOutcome {
+    Created(BackupFile),
+    /// The data was unchanged since the last archive, so none was written.
+    Skipped,
+    Failed(anyhow::Error),
+}
+
+/// Result of one source within a backup run.
+#[derive(Debug)]
+pub struct SourceReport {
+    pub source_type: SourceType,
+    pub outcome: SourceOutcome,
+}
+
+/// Per-source results of a backup run, in source order.
+#[derive(Debug, Default)]
+pub struct BackupReport {
+    pub sources: Vec<SourceReport>,
+}
+
+impl BackupReport {
+    /// Archives created by the run.
+    pub fn backup_files(&self) -> Vec<&BackupFile> {
+        self.sources
+            .iter()
+            .filter_map(|report| match &report.outcome {
+                SourceOutcome::Created(backup_file) => Some(backup_file),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn into_backup_files(self) -> Vec<BackupFile> {
+        self.sources
+            .into_iter()
+            .filter_map(|report| match report.outcome {
+                SourceOutcome::Created(backup_file) => Some(backup_file),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = (SourceType, &anyhow::Error)> {
+        self.sources
+            .iter()
+            .filter_map(|report| match &report.outcome {
+                SourceOutcome::Failed(error) => Some((report.source_type, error)),
+                _ => None,
+            })
+    }
+}
+
+/// What backing up one source would do, as found by a dry run.
+#[derive(Debug)]
+pub struct SourcePreview {
+    /// What the backup would export, run, or bundle, for sources that are not sized.
+    pub actions: Vec<String>,
+    /// File count and total size of the source's input paths, for sources that archive
+    /// them directly. Other sources only learn their data by exporting it.
+    pub inputs: Option<(u64, u64)>,
+    pub max_size: Option<u64>,
+    pub incremental: bool,
+    /// Size of the encrypted archive, or of the new chunks with the chunk repository, estimated
+    /// from the input size before compression or deduplication.
+    pub estimated_size: Option<u64>,
+    /// Archives or snapshots retention would delete afterwards.
+    pub expired: Vec<PathBuf>,
+}
+
+/// A source exported to its staging directory, before it is archived.
+struct StagedSource {
+    roots: Vec<(PathBuf, &'static str)>,
+    records: BTreeMap<String, incremental::FileRecord>,
+    previous: Option<incremental::FileIndex>,
+    fingerprint: String,
+    /// The fingerprint matches the last backup and its archive is still present.
+    unchanged: bool,
+}
+
+#[derive(Clone)]
 pub struct BackupService {
     config: Arc<Mutex<Config>>,
     backup_dir: PathBuf,
@@ -19,7 +120,7 @@ impl BackupService {
         Self { config, backup_dir }
     }
 
-    pub async fn perform_backup(&self, password: &str) -> anyhow::Result<Vec<BackupFile>> {
+    pub async fn perform_backup(&self, password: &str) -> anyhow::Result<BackupReport> {
         // Derive encryption key from password for backward compatibility
         let salt_bytes: [u8; 32] = [
             98, 114, 105, 101, 102, 99, 97, 115, 101, 95, 101, 110, 99, 114, 121, 112, 116, 105,
@@ -30,64 +131,83 @@ impl BackupService {
         self.perform_backup_with_key(&encryption_key).await
     }
 
+    /// Backs up every enabled source, up to `general.max_concurrent_backups` at a time. A
+    /// failing source is recorded in the report and does not stop the others.
     pub async fn perform_backup_with_key(
         &self,
         encryption_key: &[u8; 32],
-    ) -> anyhow::Result<Vec<BackupFile>> {
+    ) -> anyhow::Result<BackupReport> {
         info!("Starting backup process");
-        let mut backup_files = Vec::new();
-        let config = self.config.lock().await;
-
-        // Firefox backup
-        if config.source.firefox.enabled {
-            let backup_file = self
-                .backup_source_with_key(SourceType::Firefox, encryption_key, &config)
-                .await?;
-            backup_files.push(backup_file);
-        }
+        // Sources work from a snapshot so the config lock is not held during I/O
+        let config = Arc::new(self.config.lock().await.clone());
+        let limit = Arc::new(Semaphore::new(
+            config.general.max_concurrent_backups.max(1) as usize
+        ));
+        let shared_key = Arc::new(Zeroizing::new(*encryption_key));
 
-        // Folder backup
-        if config.source.folder.enabled {
-            let backup_file = self
-                .backup_source_with_key(SourceType::Folder, encryption_key, &config)
-                .await?;
-            backup_files.push(backup_file);
-        }
-
-        // Enforce retention policy
-        let max_retention = config.general.max_retention;
-        drop(config); // Release lock before retention check
-        for backup_file in &backup_files {
-            if let Err(e) = retention::enforce_retention(
-                &self.backup_dir,
-                &backup_file.source_type,
-                max_retention,
-            ) {
-                warn!(
-                    "Failed to enforce retention policy for {} backups: {}",
-                    match backup_file.source_type {
-                        SourceType::Firefox => "Firefox",
-                        SourceType::Folder => "Folder",
-                    },
-                    e
-                );
-            }
+        let mut tasks = JoinSet::new();
+        let mut spawned = HashMap::new();
+        for (index, source_type) in SourceType::ALL
+            .into_iter()
+            .filter(|source_type| config.source.is_enabled(*source_type))
+            .enumerate()
+        {
+            let service = self.clone();
+            let config = config.clone();
+            let limit = limit.clone();
+            let shared_key = shared_key.clone();
+            let task = tasks.spawn(async move {
+                // The semaphore is never closed, so acquiring only waits for a free slot
+                let _permit = limit.acquire_owned().await;
+                let outcome = service
+                    .backup_source_with_key(source_type, &shared_key, &config)
+                    .await;
+                if let Err(e) = &outcome {
+                    error!("{} backup failed: {:#}", source_type.name(), e);
+                }
+                (index, source_type, outcome)
+            });
+            spawned.insert(task.id(), (index, source_type));
         }
 
-        let mut config = self.config.lock().await;
-        for backup_file in &backup_files {
-            match backup_file.source_type {
-                SourceType::Firefox => {
-                    config.source.firefox.last_backup = Some(backup_file.datetime)
+        let mut outcomes = Vec::new();
+        while let Some(joined) = tasks.join_next().await {
+            match joined {
+                Ok(outcome) => outcomes.push(outcome),
+                // A panicking task fails only its own source
+                Err(e) => {
+                    let (index, source_type) = spawned[&e.id()];
+                    error!("{} backup failed: {}", source_type.name(), e);
+                    outcomes.push((
+                        index,
+                        source_type,
+                        Err(anyhow!("Backup task failed: {}", e)),
+                    ));
                 }
-                SourceType::Folder => config.source.folder.last_backup = Some(backup_file.datetime),
             }
         }
-        drop(config);
+        outcomes.sort_by_key(|(index, _, _)| *index);
+        let outcomes = outcomes
+            .into_iter()
+            .map(|(_, source_type, outcome)| (source_type, outcome))
+            .collect();
 
-        info!("Backup completed successfully");
+        let report = self
+            .finish_source_backups(outcomes, &config, encryption_key)
+            .await;
+
+        let failed = report.failures().count();
+        if failed == 0 {
+            info!("Backup completed successfully");
+        } else {
+            warn!(
+                "Backup completed with {} of {} sources failed",
+                failed,
+                report.sources.len()
+            );
+        }
 
-        Ok(backup_files)
+        Ok(report)
     }
 
     pub async fn perform_source_backup_with_key(
@@ -96,113 +216,589 @@ impl BackupService {
         encryption_key: &[u8; 32],
     ) -> anyhow::Result<Vec<BackupFile>> {
         info!("Starting backup process for {:?}", source_type);
-        let config = self.config.lock().await;
-        let backup_file = self
+        let config = self.config.lock().await.clone();
+        let outcome = self
             .backup_source_with_key(source_type, encryption_key, &config)
             .await?;
-        let max_retention = config.general.max_retention;
-        drop(config);
 
-        if let Err(e) =
-            retention::enforce_retention(&self.backup_dir, &backup_file.source_type, max_retention)
-        {
-            warn!(
-                "Failed to enforce retention policy for {} backups: {}",
-                match backup_file.source_type {
-                    SourceType::Firefox => "Firefox",
-                    SourceType::Folder => "Folder",
+        let report = self
+            .finish_source_backups(vec![(source_type, Ok(outcome))], &config, encryption_key)
+            .await;
+
+        info!("Backup completed successfully");
+
+        Ok(report.into_backup_files())
+    }
+
+    /// Reports what a backup of every enabled source would write and delete. Nothing is
+    /// exported, run, or compressed: only the input paths of sources that have them are sized.
+    pub async fn preview_backup_with_key(
+        &self,
+        encryption_key: &[u8; 32],
+    ) -> Vec<(SourceType, anyhow::Result<SourcePreview>)> {
+        let config = self.config.lock().await.clone();
+        let mut previews = Vec::new();
+        for source_type in SourceType::ALL {
+            if config.source.is_enabled(source_type) {
+                let preview = self
+                    .preview_source_with_key(source_type, encryption_key, &config)
+                    .await;
+                previews.push((source_type, preview));
+            }
+        }
+        previews
+    }
+
+    async fn preview_source_with_key(
+        &self,
+        source_type: SourceType,
+        encryption_key: &[u8; 32],
+        config: &Config,
+    ) -> anyhow::Result<SourcePreview> {
+        let max_size = config.source.max_size(source_type);
+        let mut preview = SourcePreview {
+            actions: preview_actions(config, source_type),
+            inputs: None,
+            max_size,
+            incremental: false,
+            estimated_size: None,
+            expired: Vec::new(),
+        };
+
+        let input_paths = config.source.input_paths(source_type);
+        if !input_paths.is_empty() {
+            let report = tokio::task::spawn_blocking(move || size::scan(&input_paths)).await??;
+            if let Some(max_size) = max_size {
+                let warn_only = config.source.options(source_type).max_size_warn_only;
+                let base = match source_type {
+                    SourceType::Folder => config.source.folder.dir.as_path(),
+                    _ => Path::new(""),
+                };
+                size::check_limit(source_type.name(), &report, max_size, warn_only, base)?;
+            }
+            preview.inputs = Some((report.file_count, report.total));
+        }
+
+        let data_dir = self.get_data_dir();
+        let policy = config.retention_policy(source_type);
+        let host = config.general.effective_host_id();
+        if config.general.repository == RepositoryFormat::Chunks {
+            let repository = chunks::Repository::existing(&data_dir, encryption_key);
+            // The new snapshot counts towards the retained ones
+            preview.expired = repository.expired_snapshots(source_type, &host, &policy, true)?;
+            preview.estimated_size = preview.inputs.map(|(_, total)| total);
+            return Ok(preview);
+        }
+
+        let options = config.source.options(source_type);
+        preview.incremental = options.incremental
+            && incremental::load_index(&data_dir, source_type, encryption_key).is_some_and(
+                |index| {
+                    incremental::can_build_on(
+                        &data_dir,
+                        &index,
+                        options.full_every.unwrap_or(DEFAULT_FULL_EVERY),
+                    )
                 },
-                e
             );
+        preview.expired = retention::expired_after_backup(
+            &data_dir,
+            &source_type,
+            &host,
+            &policy,
+            preview.incremental,
+        )?;
+        preview.estimated_size = preview
+            .inputs
+            .map(|(_, total)| stream::encrypted_len(total));
+
+        Ok(preview)
+    }
+
+    /// Enforces retention for new archives and records `last_backup` and the fingerprint of
+    /// every source that succeeded, leaving failed sources untouched.
+    async fn finish_source_backups(
+        &self,
+        outcomes: Vec<(SourceType, anyhow::Result<SourceBackup>)>,
+        config: &Config,
+        encryption_key: &[u8; 32],
+    ) -> BackupReport {
+        let host = config.general.effective_host_id();
+        for (source_type, outcome) in &outcomes {
+            if let Ok(SourceBackup::Created { backup_file, .. }) = outcome {
+                let policy = config.retention_policy(*source_type);
+                // Snapshot retention ends with garbage collection of unreferenced chunks
+                let result = if chunks::is_snapshot(&backup_file.path) {
+                    chunks::Repository::open(&self.backup_dir, encryption_key).and_then(
+                        |repository| repository.enforce_retention(*source_type, &host, &policy),
+                    )
+                } else {
+                    retention::enforce_retention(&self.backup_dir, source_type, &host, &policy)
+                };
+                if let Err(e) = result {
+                    warn!(
+                        "Failed to enforce retention policy for {} backups: {}",
+                        source_type.name(),
+                        e
+                    );
+                }
+            }
         }
 
+        let mut report = BackupReport::default();
         let mut config = self.config.lock().await;
-        match backup_file.source_type {
-            SourceType::Firefox => config.source.firefox.last_backup = Some(backup_file.datetime),
-            SourceType::Folder => config.source.folder.last_backup = Some(backup_file.datetime),
+        for (source_type, outcome) in outcomes {
+            let outcome = match outcome {
+                Ok(SourceBackup::Created {
+                    backup_file,
+                    fingerprint,
+                }) => {
+                    config
+                        .source
+                        .set_last_backup(source_type, backup_file.datetime);
+                    config.source.set_last_fingerprint(source_type, fingerprint);
+                    SourceOutcome::Created(backup_file)
+                }
+                Ok(SourceBackup::Unchanged { fingerprint }) => {
+                    config.source.set_last_backup(source_type, Local::now());
+                    config.source.set_last_fingerprint(source_type, fingerprint);
+                    SourceOutcome::Skipped
+                }
+                Err(e) => SourceOutcome::Failed(e),
+            };
+            report.sources.push(SourceReport {
+                source_type,
+                outcome,
+            });
         }
-        drop(config);
 
-        info!("Backup completed successfully");
-
-        Ok(vec![backup_file])
+        report
     }
 
+    /// Backs up one source between its pre- and post-backup hooks.
+    ///
+    /// Post-backup hooks also run when the backup or an aborting pre-backup hook fails, with
+    /// `BRIEFCASE_STATUS=failed`; that error then takes precedence over theirs.
     async fn backup_source_with_key(
         &self,
         source_type: SourceType,
         encryption_key: &[u8; 32],
         config: &Config,
-    ) -> anyhow::Result<BackupFile> {
-        let temp_dir = TempDir::new(32 * 1024 * 1024)?;
+    ) -> anyhow::Result<SourceBackup> {
+        let max_size = config.source.max_size(source_type);
+        let temp_dir = TempDir::new(max_size.unwrap_or(u64::MAX))?;
+
+        let mut context = hooks::HookContext {
+            source_type,
+            temp_dir: &temp_dir.path,
+            archive: None,
+            status: None,
+        };
+        let pre_backup = hooks::hooks_for(config, source_type, hooks::Stage::PreBackup);
+        let result = match hooks::run_hooks(hooks::Stage::PreBackup, &pre_backup, &context).await {
+            Ok(()) => {
+                self.archive_source_with_key(source_type, encryption_key, config, &temp_dir)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+
+        context.status = Some(match &result {
+            Ok(SourceBackup::Created { backup_file, .. }) => {
+                context.archive = Some(&backup_file.path);
+                "created"
+            }
+            Ok(SourceBackup::Unchanged { .. }) => "unchanged",
+            Err(_) => "failed",
+        });
+        let post_backup = hooks::hooks_for(config, source_type, hooks::Stage::PostBackup);
+        let hooks_result = hooks::run_hooks(hooks::Stage::PostBackup, &post_backup, &context).await;
+
+        let outcome = result?;
+        hooks_result?;
+        Ok(outcome)
+    }
+
+    /// Exports a source to `temp_dir` within its size limit and compares it with the source's
+    /// last backup. Nothing is written to the data directory.
+    async fn stage_source_with_key(
+        &self,
+        source_type: SourceType,
+        encryption_key: &[u8; 32],
+        config: &Config,
+        temp_dir: &TempDir,
+    ) -> anyhow::Result<StagedSource> {
+        let max_size = config.source.max_size(source_type);
+        let warn_only = config.source.options(source_type).max_size_warn_only;
+
+        // Sources with known inputs are measured before anything is copied
+        let input_paths = config.source.input_paths(source_type);
+        if let (Some(max_size), false) = (max_size, input_paths.is_empty()) {
+            let inputs = input_paths.clone();
+            let report = tokio::task::spawn_blocking(move || size::scan(&inputs)).await??;
+            let base = match source_type {
+                SourceType::Folder => config.source.folder.dir.as_path(),
+                _ => std::path::Path::new(""),
+            };
+            size::check_limit(source_type.name(), &report, max_size, warn_only, base)?;
+        }
 
         match source_type {
             SourceType::Firefox => {
                 info!("Backing up Firefox data");
                 firefox::export_firefox_data(&config.source.firefox.dir, &temp_dir.path).await?;
-
-                if !temp_dir.is_within_limit()? {
-                    return Err(anyhow::anyhow!(
-                        "Firefox backup data exceeds size limit (32MB). Current size: {} bytes",
-                        temp_dir.size()?
-                    ));
+                if config.source.firefox.export_logins {
+                    let primary_password = firefox::primary_password(&config.source.firefox)?;
+                    firefox::export_logins_csv(
+                        &temp_dir.path,
+                        primary_password.as_ref().map(|password| password.as_str()),
+                    )
+                    .await?;
                 }
             }
             SourceType::Folder => {
+                // Streamed straight from the source directory when the archive is built
                 info!("Backing up sensitive folder");
-                folder::copy_sensitive_folder(&config.source.folder.dir, &temp_dir.path).await?;
             }
+            SourceType::Chromium => {
+                info!("Backing up Chromium browser data");
+                chromium::export_chromium_source(&config.source.chromium.dir, &temp_dir.path)
+                    .await?;
+            }
+            SourceType::Thunderbird => {
+                info!("Backing up Thunderbird profile");
+                thunderbird::export_thunderbird_source(
+                    &config.source.thunderbird.dir,
+                    &temp_dir.path,
+                )
+                .await?;
+            }
+            SourceType::Ssh => {
+                info!("Backing up SSH keys");
+                ssh::export_ssh_source(&config.source.ssh.dir, &temp_dir.path).await?;
+            }
+            SourceType::Gpg => {
+                info!("Backing up GnuPG keyring");
+                gpg::export_gpg_source(&config.source.gpg.dir, &temp_dir.path).await?;
+            }
+            SourceType::Command => {
+                info!("Capturing command outputs");
+                command::capture_command_outputs(
+                    &config.source.command.commands,
+                    config.source.command.timeout_secs,
+                    &temp_dir.path,
+                )
+                .await?;
+            }
+            SourceType::Git => {
+                info!("Bundling git repositories");
+                git::export_git_repositories(&config.source.git.repos, &temp_dir.path).await?;
+            }
+            SourceType::Files => {
+                info!("Backing up listed files");
+                let files = &config.source.files;
+                files::export_files(
+                    &files.paths,
+                    Duration::from_secs(files.stable_window_secs),
+                    Duration::from_secs(files.stable_timeout_secs),
+                    files.open_database,
+                    &temp_dir.path,
+                )
+                .await?;
+            }
+        }
+
+        if let (Some(max_size), true) = (max_size, input_paths.is_empty()) {
+            let staged = temp_dir.path.clone();
+            let report = tokio::task::spawn_blocking(move || size::scan(&[staged])).await??;
+            size::check_limit(
+                source_type.name(),
+                &report,
+                max_size,
+                warn_only,
+                &temp_dir.path,
+            )?;
+        }
+
+        let mut roots = vec![(temp_dir.path.clone(), "")];
+        if source_type == SourceType::Folder {
+            roots.push((config.source.folder.dir.clone(), folder::ARCHIVE_DIR));
         }
 
-        self.create_backup_file_with_key(&temp_dir.path, source_type, encryption_key)
-            .await
+        let data_dir = self.get_data_dir();
+        let options = config.source.options(source_type);
+        let previous = options
+            .incremental
+            .then(|| incremental::load_index(&data_dir, source_type, encryption_key))
+            .flatten();
+        let (records, previous) = run_blocking(&roots, move |roots| {
+            let records = incremental::scan(roots, previous.as_ref())?;
+            Ok((records, previous))
+        })
+        .await?;
+        let fingerprint = incremental::fingerprint(&records);
+        let unchanged = config.source.last_fingerprint(source_type) == Some(fingerprint.as_str())
+            && has_archive(&data_dir, source_type, &config.general.effective_host_id())?;
+
+        Ok(StagedSource {
+            roots,
+            records,
+            previous,
+            fingerprint,
+            unchanged,
+        })
+    }
+
+    async fn archive_source_with_key(
+        &self,
+        source_type: SourceType,
+        encryption_key: &[u8; 32],
+        config: &Config,
+        temp_dir: &TempDir,
+    ) -> anyhow::Result<SourceBackup> {
+        let StagedSource {
+            roots,
+            records,
+            previous,
+            fingerprint,
+            unchanged,
+        } = self
+            .stage_source_with_key(source_type, encryption_key, config, temp_dir)
+            .await?;
+
+        // Unchanged data only bumps last_backup, so retention keeps the older distinct versions
+        if unchanged {
+            info!(
+                "{} data is unchanged since its last backup, skipping",
+                source_type.name()
+            );
+            return Ok(SourceBackup::Unchanged { fingerprint });
+        }
+
+        let data_dir = self.get_data_dir();
+        let options = config.source.options(source_type);
+        let host = config.general.effective_host_id();
+        if config.general.repository == RepositoryFormat::Chunks {
+            let backup_file = self
+                .create_snapshot_with_key(&roots, source_type, &host, encryption_key)
+                .await?;
+            return Ok(SourceBackup::Created {
+                backup_file,
+                fingerprint,
+            });
+        }
+
+        let plan = if options.incremental {
+            Some(incremental::plan(
+                &data_dir,
+                previous,
+                records,
+                options.full_every.unwrap_or(DEFAULT_FULL_EVERY),
+                &temp_dir.path,
+            )?)
+        } else {
+            incremental::remove_index(&data_dir, source_type)?;
+            None
+        };
+
+        let backup_file = self
+            .create_backup_file_with_key(
+                &roots,
+                source_type,
+                &host,
+                plan,
+                options.archive_settings(),
+                encryption_key,
+            )
+            .await?;
+        Ok(SourceBackup::Created {
+            backup_file,
+            fingerprint,
+        })
     }
 
     async fn create_backup_file_with_key(
         &self,
-        temp_dir: &std::path::Path,
+        roots: &[(PathBuf, &'static str)],
         source_type: SourceType,
+        host: &str,
+        plan: Option<incremental::ArchivePlan>,
+        settings: ArchiveSettings,
         encryption_key: &[u8; 32],
     ) -> anyhow::Result<BackupFile> {
         let datetime = Local::now();
-        let filename = format!(
-            "{}_{}.7z",
-            match source_type {
-                SourceType::Firefox => "Firefox",
-                SourceType::Folder => "Folder",
-            },
-            datetime.format("%Y-%m-%d_%H-%M-%S")
-        );
-
         let data_dir = self.get_data_dir();
 
-        // First create the compressed archive
-        let temp_archive_path = data_dir.join(format!("{}.temp", filename));
-        compress::compress_directory(temp_dir, &temp_archive_path, None)?;
+        let is_incremental = plan.as_ref().is_some_and(|plan| plan.manifest.incremental);
 
-        // Then encrypt the compressed archive
+        let filename = format!(
+            "{}.{}",
+            backup_stem(
+                source_type,
+                Some(host),
+                &datetime.naive_local(),
+                is_incremental
+            ),
+            settings.container.extension()
+        );
+        if is_incremental {
+            info!("Taking incremental {} backup", source_type.name());
+        }
+
+        // The archive is encrypted segment by segment as it is compressed, so only ciphertext
+        // reaches the disk and no more than a few segments of plaintext are in memory
         let encrypted_archive_path = data_dir.join(&filename);
-        crate::crypto::encrypt::encrypt_file_with_derived_key(
-            &temp_archive_path,
-            &encrypted_archive_path,
-            encryption_key,
-        )?;
+        let output_path = encrypted_archive_path.clone();
+        let key = Zeroizing::new(*encryption_key);
+        let plan = run_blocking(roots, move |roots| {
+            encrypt::encrypt_stream_to_file(&output_path, &key, |encryptor| {
+                build_archive(roots, plan.as_ref(), settings, encryptor)?;
+                Ok(())
+            })?;
+            Ok(plan)
+        })
+        .await?;
 
-        // Clean up the unencrypted temp file
-        if temp_archive_path.exists() {
-            std::fs::remove_file(&temp_archive_path)?;
+        if let Some(mut plan) = plan {
+            plan.index.archive = filename;
+            incremental::save_index(&data_dir, source_type, &plan.index, encryption_key)?;
         }
 
         // Create backup file record
         let mut backup_file = BackupFile::new(encrypted_archive_path, source_type);
         backup_file.datetime = datetime;
+        backup_file.host = Some(host.to_string());
         backup_file.size = std::fs::metadata(&backup_file.path)?.len();
         // Hash would be calculated here
 
         Ok(backup_file)
     }
 
+    /// Stores the source as a snapshot in the chunk repository, writing only chunks it lacks.
+    async fn create_snapshot_with_key(
+        &self,
+        roots: &[(PathBuf, &'static str)],
+        source_type: SourceType,
+        host: &str,
+        encryption_key: &[u8; 32],
+    ) -> anyhow::Result<BackupFile> {
+        let datetime = Local::now();
+        let filename = format!(
+            "{}.{}",
+            backup_stem(source_type, Some(host), &datetime.naive_local(), false),
+            chunks::SNAPSHOT_EXTENSION
+        );
+
+        let data_dir = self.get_data_dir();
+        let key = Zeroizing::new(*encryption_key);
+        let (snapshot_path, stats) = run_blocking(roots, move |roots| {
+            chunks::Repository::open(&data_dir, &key)?.write_snapshot(roots, &filename)
+        })
+        .await?;
+        info!(
+            "{} snapshot stored {} new chunks ({}) and reused {}",
+            source_type.name(),
+            stats.new_chunks,
+            size::human_size(stats.new_bytes),
+            stats.reused_chunks
+        );
+
+        let mut backup_file = BackupFile::new(snapshot_path, source_type);
+        backup_file.datetime = datetime;
+        backup_file.host = Some(host.to_string());
+        // Only new chunks and the manifest take up space
+        backup_file.size = stats.new_bytes + std::fs::metadata(&backup_file.path)?.len();
+
+        Ok(backup_file)
+    }
+
     fn get_data_dir(&self) -> PathBuf {
         self.backup_dir.clone()
     }
 }
+
+/// What a backup of a source without input paths would export, run, or bundle.
+fn preview_actions(config: &Config, source_type: SourceType) -> Vec<String> {
+    let export = |dir: &Path| {
+        if dir.as_os_str().is_empty() {
+            "would export from the default location".to_string()
+        } else {
+            format!("would export from {:?}", dir)
+        }
+    };
+    let source = &config.source;
+    match source_type {
+        SourceType::Firefox => vec![export(&source.firefox.dir)],
+        SourceType::Chromium => vec![export(&source.chromium.dir)],
+        SourceType::Thunderbird => vec![export(&source.thunderbird.dir)],
+        SourceType::Ssh => vec![export(&source.ssh.dir)],
+        SourceType::Gpg => vec![export(&source.gpg.dir)],
+        SourceType::Command => source
+            .command
+            .commands
+            .iter()
+            .map(|command| format!("would run `{}` into {}", command.command, command.output))
+            .collect(),
+        SourceType::Git => source
+            .git
+            .repos
+            .iter()
+            .map(|repo| format!("would bundle {:?}", repo))
+            .collect(),
+        SourceType::Folder | SourceType::Files => Vec::new(),
+    }
+}
+
+/// Compresses `roots` into `writer`, limited to the plan's entries.
+fn build_archive<W: std::io::Write + std::io::Seek>(
+    roots: &[(&Path, &str)],
+    plan: Option<&incremental::ArchivePlan>,
+    settings: ArchiveSettings,
+    writer: W,
+) -> anyhow::Result<W> {
+    match plan {
+        Some(plan) => {
+            compress::compress_to_writer(roots, &|name| plan.includes(name), settings, writer)
+        }
+        None => compress::compress_to_writer(roots, &|_| true, settings, writer),
+    }
+}
+
+/// Runs archive work that reads `roots` on the blocking thread pool, so concurrent sources
+/// compress and encrypt in parallel without stalling the runtime.
+async fn run_blocking<T, F>(roots: &[(PathBuf, &'static str)], work: F) -> anyhow::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&[(&Path, &str)]) -> anyhow::Result<T> + Send + 'static,
+{
+    let roots = roots.to_vec();
+    tokio::task::spawn_blocking(move || {
+        let roots: Vec<(&Path, &str)> = roots
+            .iter()
+            .map(|(dir, prefix)| (dir.as_path(), *prefix))
+            .collect();
+        work(&roots)
+    })
+    .await?
+}
+
+/// Whether the data directory still holds an archive or snapshot `host` took of the source.
+fn has_archive(data_dir: &Path, source_type: SourceType, host: &str) -> anyhow::Result<bool> {
+    let snapshots_dir = chunks::snapshots_dir(data_dir);
+    for dir in [data_dir, snapshots_dir.as_path()] {
+        if !dir.exists() {
+            continue;
+        }
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let is_backup = compress::is_archive(&name) || chunks::is_snapshot(&path);
+            let is_own = BackupName::parse(&name)
+                .is_some_and(|name| name.source_type == source_type && name.is_from_host(host));
+            if is_backup && is_own {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
@@ -0,0 +1,324 @@
+//! Minimal 7z writer for store-only archives, which sevenz-rust can read but not write.
+//!
+//! sevenz-rust only encodes LZMA and LZMA2, while store-only archives need the COPY coder.
+//! Each block becomes a folder with a single COPY coder, and the header is written unencoded,
+//! so any 7z reader can open the archive.
+
+use sevenz_rust::{SevenZArchiveEntry, SevenZMethod};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+const SIGNATURE: &[u8; 6] = b"7z\xbc\xaf\x27\x1c";
+const SIGNATURE_HEADER_SIZE: u64 = 32;
+
+// Property ids from the 7z format description
+const K_END: u8 = 0x00;
+const K_HEADER: u8 = 0x01;
+const K_MAIN_STREAMS_INFO: u8 = 0x04;
+const K_FILES_INFO: u8 = 0x05;
+const K_PACK_INFO: u8 = 0x06;
+const K_UNPACK_INFO: u8 = 0x07;
+const K_SUB_STREAMS_INFO: u8 = 0x08;
+const K_SIZE: u8 = 0x09;
+const K_CRC: u8 = 0x0a;
+const K_FOLDER: u8 = 0x0b;
+const K_CODERS_UNPACK_SIZE: u8 = 0x0c;
+const K_NUM_UNPACK_STREAM: u8 = 0x0d;
+const K_EMPTY_STREAM: u8 = 0x0e;
+const K_EMPTY_FILE: u8 = 0x0f;
+const K_NAME: u8 = 0x11;
+const K_M_TIME: u8 = 0x14;
+const K_WIN_ATTRIBUTES: u8 = 0x15;
+
+const CRC32: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_ISO_HDLC);
+
+struct Folder {
+    pack_size: u64,
+    unpack_size: u64,
+    sizes: Vec<u64>,
+    crcs: Vec<u32>,
+}
+
+/// Writes a 7z archive whose blocks are stored without compression.
+pub struct BlockWriter<W: Write + Seek> {
+    output: W,
+    files: Vec<SevenZArchiveEntry>,
+    folders: Vec<Folder>,
+}
+
+impl<W: Write + Seek> BlockWriter<W> {
+    pub fn new(mut output: W) -> io::Result<Self> {
+        // The signature header is filled in by `finish` once the header position is known
+        output.seek(SeekFrom::Start(0))?;
+        output.write_all(&[0; SIGNATURE_HEADER_SIZE as usize])?;
+        Ok(Self {
+            output,
+            files: Vec::new(),
+            folders: Vec::new(),
+        })
+    }
+
+    /// Adds an entry without content, such as a directory.
+    pub fn push_entry(&mut self, mut entry: SevenZArchiveEntry) {
+        entry.has_stream = false;
+        entry.size = 0;
+        self.files.push(entry);
+    }
+
+    /// Writes `sources` as one solid block holding `entries`, in order.
+    pub fn push_block<R: Read>(
+        &mut self,
+        mut entries: Vec<SevenZArchiveEntry>,
+        sources: Vec<R>,
+    ) -> io::Result<()> {
+        assert_eq!(entries.len(), sources.len());
+        let start = self.output.stream_position()?;
+        let (sizes, crcs) = write_sources(&mut self.output, sources)?;
+        let pack_size = self.output.stream_position()? - start;
+
+        for ((entry, size), crc) in entries.iter_mut().zip(&sizes).zip(&crcs) {
+            entry.has_stream = true;
+            entry.size = *size;
+            entry.has_crc = true;
+            entry.crc = *crc as u64;
+        }
+        self.files.extend(entries);
+        self.folders.push(Folder {
+            pack_size,
+            unpack_size: sizes.iter().sum(),
+            sizes,
+            crcs,
+        });
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut header = Vec::new();
+        self.write_header(&mut header);
+        let header_position = self.output.stream_position()?;
+        self.output.write_all(&header)?;
+
+        let mut start_header = Vec::with_capacity(20);
+        start_header.extend_from_slice(&(header_position - SIGNATURE_HEADER_SIZE).to_le_bytes());
+        start_header.extend_from_slice(&(header.len() as u64).to_le_bytes());
+        start_header.extend_from_slice(&CRC32.checksum(&header).to_le_bytes());
+
+        self.output.seek(SeekFrom::Start(0))?;
+        self.output.write_all(SIGNATURE)?;
+        self.output.write_all(&[0, 4])?;
+        self.output
+            .write_all(&CRC32.checksum(&start_header).to_le_bytes())?;
+        self.output.write_all(&start_header)?;
+        self.output.seek(SeekFrom::End(0))?;
+        self.output.flush()?;
+        Ok(self.output)
+    }
+
+    fn write_header(&self, header: &mut Vec<u8>) {
+        header.push(K_HEADER);
+        if !self.folders.is_empty() {
+            header.push(K_MAIN_STREAMS_INFO);
+            self.write_streams_info(header);
+        }
+        self.write_files_info(header);
+        header.push(K_END);
+    }
+
+    fn write_streams_info(&self, header: &mut Vec<u8>) {
+        header.push(K_PACK_INFO);
+        write_number(header, 0);
+        write_number(header, self.folders.len() as u64);
+        header.push(K_SIZE);
+        for folder in &self.folders {
+            write_number(header, folder.pack_size);
+        }
+        header.push(K_END);
+
+        header.push(K_UNPACK_INFO);
+        header.push(K_FOLDER);
+        write_number(header, self.folders.len() as u64);
+        header.push(0); // not external
+        let id = SevenZMethod::ID_COPY;
+        for _ in &self.folders {
+            write_number(header, 1); // one coder
+            header.push(id.len() as u8); // simple coder without properties
+            header.extend_from_slice(id);
+        }
+        header.push(K_CODERS_UNPACK_SIZE);
+        for folder in &self.folders {
+            write_number(header, folder.unpack_size);
+        }
+        header.push(K_END);
+
+        header.push(K_SUB_STREAMS_INFO);
+        header.push(K_NUM_UNPACK_STREAM);
+        for folder in &self.folders {
+            write_number(header, folder.sizes.len() as u64);
+        }
+        header.push(K_SIZE);
+        for folder in &self.folders {
+            for size in &folder.sizes[..folder.sizes.len().saturating_sub(1)] {
+                write_number(header, *size);
+            }
+        }
+        header.push(K_CRC);
+        header.push(1); // all defined
+        for folder in &self.folders {
+            for crc in &folder.crcs {
+                header.extend_from_slice(&crc.to_le_bytes());
+            }
+        }
+        header.push(K_END);
+
+        header.push(K_END);
+    }
+
+    fn write_files_info(&self, header: &mut Vec<u8>) {
+        header.push(K_FILES_INFO);
+        write_number(header, self.files.len() as u64);
+
+        let empty_streams: Vec<bool> = self.files.iter().map(|file| !file.has_stream).collect();
+        if empty_streams.contains(&true) {
+            write_property(header, K_EMPTY_STREAM, &bit_vector(&empty_streams));
+            let empty_files: Vec<bool> = self
+                .files
+                .iter()
+                .filter(|file| !file.has_stream)
+                .map(|file| !file.is_directory)
+                .collect();
+            if empty_files.contains(&true) {
+                write_property(header, K_EMPTY_FILE, &bit_vector(&empty_files));
+            }
+        }
+
+        let mut names = vec![0]; // not external
+        for file in &self.files {
+            for unit in file.name.encode_utf16().chain([0]) {
+                names.extend_from_slice(&unit.to_le_bytes());
+            }
+        }
+        write_property(header, K_NAME, &names);
+
+        let mtimes = self.optional_values(
+            |file| file.has_last_modified_date,
+            |file| file.last_modified_date.to_raw().to_le_bytes().to_vec(),
+        );
+        if let Some(mtimes) = mtimes {
+            write_property(header, K_M_TIME, &mtimes);
+        }
+        let attributes = self.optional_values(
+            |file| file.has_windows_attributes,
+            |file| file.windows_attributes.to_le_bytes().to_vec(),
+        );
+        if let Some(attributes) = attributes {
+            write_property(header, K_WIN_ATTRIBUTES, &attributes);
+        }
+
+        header.push(K_END);
+    }
+
+    /// Encodes a per-file value that only some files define, or `None` when none do.
+    fn optional_values(
+        &self,
+        defined: impl Fn(&SevenZArchiveEntry) -> bool,
+        value: impl Fn(&SevenZArchiveEntry) -> Vec<u8>,
+    ) -> Option<Vec<u8>> {
+        let defined: Vec<bool> = self.files.iter().map(defined).collect();
+        if !defined.contains(&true) {
+            return None;
+        }
+        let mut data = Vec::new();
+        if defined.contains(&false) {
+            data.push(0);
+            data.extend(bit_vector(&defined));
+        } else {
+            data.push(1); // all defined
+        }
+        data.push(0); // not external
+        for (file, _) in self.files.iter().zip(&defined).filter(|(_, d)| **d) {
+            data.extend(value(file));
+        }
+        Some(data)
+    }
+}
+
+/// Copies each source into `writer`, returning every source's size and CRC.
+fn write_sources<W: Write, R: Read>(
+    writer: &mut W,
+    sources: Vec<R>,
+) -> io::Result<(Vec<u64>, Vec<u32>)> {
+    let mut sizes = Vec::with_capacity(sources.len());
+    let mut crcs = Vec::with_capacity(sources.len());
+    let mut buffer = vec![0; 64 * 1024];
+    for mut source in sources {
+        let mut digest = CRC32.digest();
+        let mut size = 0;
+        loop {
+            let read = source.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            digest.update(&buffer[..read]);
+            writer.write_all(&buffer[..read])?;
+            size += read as u64;
+        }
+        sizes.push(size);
+        crcs.push(digest.finalize());
+    }
+    Ok((sizes, crcs))
+}
+
+fn write_property(header: &mut Vec<u8>, id: u8, data: &[u8]) {
+    header.push(id);
+    write_number(header, data.len() as u64);
+    header.extend_from_slice(data);
+}
+
+fn bit_vector(bits: &[bool]) -> Vec<u8> {
+    let mut bytes = vec![0; bits.len().div_ceil(8)];
+    for (i, bit) in bits.iter().enumerate() {
+        if *bit {
+            bytes[i / 8] |= 0x80 >> (i % 8);
+        }
+    }
+    bytes
+}
+
+/// Writes a 7z variable-length number: the leading one bits of the first byte count the
+/// little-endian bytes that follow, and its remaining bits hold the value's high part.
+fn write_number(header: &mut Vec<u8>, value: u64) {
+    let mut extra = 0;
+    while extra < 8 && value >= 1u64 << (7 * (extra + 1)) {
+        extra += 1;
+    }
+    let mask = (0xff00u16 >> extra) as u8;
+    let high = if extra < 8 {
+        (value >> (8 * extra)) as u8
+    } else {
+        0
+    };
+    header.push(mask | high);
+    header.extend_from_slice(&value.to_le_bytes()[..extra]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_number_matches_7z_encoding() {
+        for (value, expected) in [
+            (0x7f, vec![0x7f]),
+            (0x80, vec![0x80, 0x80]),
+            (0x3fff, vec![0xbf, 0xff]),
+            (0x4000, vec![0xc0, 0x00, 0x40]),
+            (
+                u64::MAX,
+                vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            ),
+        ] {
+            let mut encoded = Vec::new();
+            write_number(&mut encoded, value);
+            assert_eq!(encoded, expected, "{:#x}", value);
+        }
+    }
+}
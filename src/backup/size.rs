@@ -0,0 +1,151 @@
+use anyhow::{anyhow, Result};
+use log::warn;
+use std::path::{Path, PathBuf};
+
+/// Number of files listed when a source is over its size limit.
+const LARGEST_FILES: usize = 10;
+
+/// Total size of a set of paths and the largest files among them.
+#[derive(Debug, Default)]
+pub struct SizeReport {
+    pub total: u64,
+    pub file_count: u64,
+    pub largest: Vec<(PathBuf, u64)>,
+}
+
+impl SizeReport {
+    fn add(&mut self, path: PathBuf, size: u64) {
+        self.total += size;
+        self.file_count += 1;
+
+        let position = self.largest.partition_point(|(_, other)| *other >= size);
+        if position < LARGEST_FILES {
+            self.largest.insert(position, (path, size));
+            self.largest.truncate(LARGEST_FILES);
+        }
+    }
+}
+
+/// Sums regular file sizes under `paths` without following symlinks.
+pub fn scan(paths: &[PathBuf]) -> Result<SizeReport> {
+    let mut report = SizeReport::default();
+    let mut stack: Vec<PathBuf> = paths.to_vec();
+
+    while let Some(path) = stack.pop() {
+        let metadata = std::fs::symlink_metadata(&path)?;
+        if metadata.is_dir() {
+            for entry in std::fs::read_dir(&path)? {
+                stack.push(entry?.path());
+            }
+        } else if metadata.is_file() {
+            report.add(path, metadata.len());
+        }
+    }
+
+    Ok(report)
+}
+
+/// Fails (or only warns, when `warn_only` is set) if `report` exceeds `max_size`, listing the
+/// largest files so the user can see what to exclude.
+pub fn check_limit(
+    source_name: &str,
+    report: &SizeReport,
+    max_size: u64,
+    warn_only: bool,
+    base: &Path,
+) -> Result<()> {
+    if report.total <= max_size {
+        return Ok(());
+    }
+
+    let mut message = format!(
+        "{} backup data is {} in {} files, over the {} limit. Largest files:",
+        source_name,
+        human_size(report.total),
+        report.file_count,
+        human_size(max_size)
+    );
+    for (path, size) in &report.largest {
+        let path = path.strip_prefix(base).unwrap_or(path);
+        message.push_str(&format!(
+            "\n  {:>10}  {}",
+            human_size(*size),
+            path.display()
+        ));
+    }
+
+    if warn_only {
+        warn!("{}", message);
+        Ok(())
+    } else {
+        Err(anyhow!(message))
+    }
+}
+
+/// Formats a byte count for people, e.g. `1.5 GiB`.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+    format!("{:.1} {}", value, unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_reports_total_and_largest_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        std::fs::create_dir_all(root.join("nested")).unwrap();
+        for size in 1..=12u64 {
+            std::fs::write(root.join(format!("file{}", size)), vec![0u8; size as usize]).unwrap();
+        }
+        std::fs::write(root.join("nested").join("big.iso"), vec![0u8; 100]).unwrap();
+        std::os::unix::fs::symlink(root.join("nested"), root.join("loop")).unwrap();
+
+        let report = scan(&[root.to_path_buf()]).unwrap();
+
+        assert_eq!(report.total, 178);
+        assert_eq!(report.file_count, 13);
+        assert_eq!(report.largest.len(), LARGEST_FILES);
+        assert_eq!(
+            report.largest[0],
+            (root.join("nested").join("big.iso"), 100)
+        );
+        assert_eq!(report.largest[1].1, 12);
+        assert_eq!(report.largest[9].1, 4);
+    }
+
+    #[test]
+    fn test_check_limit_fails_with_breakdown_or_warns() {
+        let mut report = SizeReport::default();
+        report.add(
+            PathBuf::from("/data/videos/movie.mkv"),
+            3 * 1024 * 1024 * 1024,
+        );
+        report.add(PathBuf::from("/data/notes.txt"), 2048);
+
+        let error = check_limit("Folder", &report, 1024 * 1024, false, Path::new("/data"))
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Folder backup data is 3.0 GiB in 2 files, over the 1.0 MiB limit"));
+        assert!(error.contains("3.0 GiB  videos/movie.mkv"));
+        assert!(error.contains("2.0 KiB  notes.txt"));
+
+        assert!(check_limit("Folder", &report, 1024 * 1024, true, Path::new("/data")).is_ok());
+        assert!(check_limit("Folder", &report, u64::MAX, false, Path::new("/data")).is_ok());
+    }
+}
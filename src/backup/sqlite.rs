@@ -0,0 +1,28 @@
+use anyhow::Result;
+use log::warn;
+use rusqlite::{Connection, OpenFlags};
+use std::path::Path;
+
+/// Copies a SQLite database through SQLite itself so the copy is transactionally consistent
+/// even while the owning application is writing to it.
+pub fn snapshot_sqlite(src: &Path, dest: &Path) -> Result<()> {
+    if dest.exists() {
+        std::fs::remove_file(dest)?;
+    }
+
+    let snapshot =
+        Connection::open_with_flags(src, OpenFlags::SQLITE_OPEN_READ_ONLY).and_then(|connection| {
+            connection.execute("VACUUM INTO ?1", [dest.to_string_lossy().to_string()])
+        });
+
+    if let Err(e) = snapshot {
+        // Applications may hold an exclusive lock; a plain copy is better than nothing
+        warn!(
+            "SQLite snapshot of {:?} failed ({}), falling back to file copy",
+            src, e
+        );
+        std::fs::copy(src, dest)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,289 @@
+use crate::backup::{firefox, sqlite};
+use anyhow::{anyhow, Result};
+use log::info;
+use rusqlite::{Connection, OpenFlags};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Account directories that hold per-server settings next to the mail stores.
+const MAIL_DIRS: [&str; 2] = ["Mail", "ImapMail"];
+
+/// Locates the default Thunderbird profile through `~/.thunderbird/profiles.ini`.
+pub fn detect_default_profile() -> Result<PathBuf> {
+    let profiles_root = dirs::home_dir()
+        .ok_or_else(|| anyhow!("Could not find home directory"))?
+        .join(".thunderbird");
+    firefox::find_default_profile(&profiles_root)
+}
+
+/// Backs up the configured profile, or the default profile when `profile_dir` is empty.
+pub async fn export_thunderbird_source(profile_dir: &Path, temp_dir: &Path) -> Result<()> {
+    if profile_dir.as_os_str().is_empty() {
+        let profile_dir = detect_default_profile()?;
+        info!("Using detected Thunderbird profile {:?}", profile_dir);
+        return export_thunderbird_data(&profile_dir, temp_dir).await;
+    }
+
+    export_thunderbird_data(profile_dir, temp_dir).await
+}
+
+/// Exports Thunderbird account settings, saved logins, filters, and address books.
+///
+/// Mail stores are not copied; only `msgFilterRules.dat` is taken from each account directory.
+pub async fn export_thunderbird_data(profile_dir: &Path, temp_dir: &Path) -> Result<()> {
+    if !profile_dir.exists() {
+        return Err(anyhow!(
+            "Thunderbird profile directory does not exist: {:?}",
+            profile_dir
+        ));
+    }
+
+    fs::create_dir_all(temp_dir).await?;
+
+    firefox::copy_profile_files(
+        "Thunderbird",
+        profile_dir,
+        temp_dir,
+        &["logins.json", "key4.db", "prefs.js"],
+    )
+    .await?;
+
+    copy_filter_rules(profile_dir, temp_dir).await?;
+    export_address_books(profile_dir, temp_dir).await?;
+
+    Ok(())
+}
+
+async fn copy_filter_rules(profile_dir: &Path, temp_dir: &Path) -> Result<()> {
+    for mail_dir in MAIL_DIRS {
+        let Ok(mut entries) = fs::read_dir(profile_dir.join(mail_dir)).await else {
+            continue;
+        };
+
+        while let Some(entry) = entries.next_entry().await? {
+            let rules = entry.path().join("msgFilterRules.dat");
+            if !rules.is_file() {
+                continue;
+            }
+
+            let dest_dir = temp_dir.join(mail_dir).join(entry.file_name());
+            fs::create_dir_all(&dest_dir).await?;
+            let size = fs::copy(&rules, dest_dir.join("msgFilterRules.dat")).await?;
+            info!(
+                "Copied Thunderbird filters for {}/{} ({} bytes)",
+                mail_dir,
+                entry.file_name().to_string_lossy(),
+                size
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn export_address_books(profile_dir: &Path, temp_dir: &Path) -> Result<()> {
+    let mut books = Vec::new();
+    let mut entries = fs::read_dir(profile_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with("abook") && name.ends_with(".sqlite") {
+            books.push(entry.path());
+        }
+    }
+    books.sort();
+
+    if books.is_empty() {
+        info!("Thunderbird address books not found, skipping vCard export");
+        return Ok(());
+    }
+
+    let output_dir = temp_dir.join("addressbooks");
+    fs::create_dir_all(&output_dir).await?;
+
+    for book in books {
+        let stem = book
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("abook")
+            .to_string();
+        let snapshot = temp_dir.join(format!("{}.sqlite.export", stem));
+        let output = output_dir.join(format!("{}.vcf", stem));
+
+        let snapshot_for_export = snapshot.clone();
+        let count = tokio::task::spawn_blocking(move || {
+            sqlite::snapshot_sqlite(&book, &snapshot_for_export)?;
+            export_vcards(&snapshot_for_export, &output)
+        })
+        .await??;
+        fs::remove_file(&snapshot).await?;
+
+        info!("Exported {} Thunderbird contacts to {}.vcf", count, stem);
+    }
+
+    Ok(())
+}
+
+/// Writes every card in a Thunderbird `abook.sqlite` as a vCard, returning the card count.
+fn export_vcards(database_path: &Path, output_path: &Path) -> Result<usize> {
+    let connection = Connection::open_with_flags(database_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut statement = connection.prepare("SELECT card, name, value FROM properties")?;
+
+    let mut cards: BTreeMap<String, BTreeMap<String, String>> = BTreeMap::new();
+    let rows = statement.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, Option<String>>(2)?,
+        ))
+    })?;
+    for row in rows {
+        let (card, name, value) = row?;
+        if let Some(value) = value.filter(|value| !value.is_empty()) {
+            cards.entry(card).or_default().insert(name, value);
+        }
+    }
+
+    let mut output = File::create(output_path)?;
+    for (uid, properties) in &cards {
+        match properties.get("_vCard") {
+            // Thunderbird 102+ keeps the full vCard alongside the legacy columns
+            Some(vcard) => write!(output, "{}\r\n", vcard.trim_end())?,
+            None => write_vcard(&mut output, uid, properties)?,
+        }
+    }
+
+    Ok(cards.len())
+}
+
+fn write_vcard(output: &mut File, uid: &str, properties: &BTreeMap<String, String>) -> Result<()> {
+    let get = |name: &str| properties.get(name).map(|value| escape_vcard(value));
+
+    let first = get("FirstName").unwrap_or_default();
+    let last = get("LastName").unwrap_or_default();
+    let display = get("DisplayName").unwrap_or_else(|| {
+        let full = format!("{} {}", first, last);
+        full.trim().to_string()
+    });
+
+    write!(output, "BEGIN:VCARD\r\nVERSION:4.0\r\n")?;
+    write!(output, "FN:{}\r\n", display)?;
+    write!(output, "N:{};{};;;\r\n", last, first)?;
+
+    let fields = [
+        ("NickName", "NICKNAME"),
+        ("PrimaryEmail", "EMAIL;PREF=1"),
+        ("SecondEmail", "EMAIL"),
+        ("WorkPhone", "TEL;TYPE=work"),
+        ("HomePhone", "TEL;TYPE=home"),
+        ("CellularNumber", "TEL;TYPE=cell"),
+        ("Company", "ORG"),
+        ("JobTitle", "TITLE"),
+        ("Notes", "NOTE"),
+    ];
+    for (name, property) in fields {
+        if let Some(value) = get(name) {
+            write!(output, "{}:{}\r\n", property, value)?;
+        }
+    }
+
+    write!(output, "UID:{}\r\n", escape_vcard(uid))?;
+    write!(output, "END:VCARD\r\n")?;
+
+    Ok(())
+}
+
+fn escape_vcard(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(',', "\\,")
+        .replace(';', "\\;")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_abook(path: &Path) {
+        let connection = Connection::open(path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE properties (card TEXT, name TEXT, value TEXT);
+                 INSERT INTO properties VALUES ('a1', 'FirstName', 'Ada');
+                 INSERT INTO properties VALUES ('a1', 'LastName', 'Lovelace');
+                 INSERT INTO properties VALUES ('a1', 'PrimaryEmail', 'ada@example.com');
+                 INSERT INTO properties VALUES ('a1', 'Notes', 'Analyst; engine, notes');
+                 INSERT INTO properties VALUES ('b2', '_vCard',
+                     'BEGIN:VCARD\r\nVERSION:4.0\r\nFN:Grace Hopper\r\nEND:VCARD');",
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_find_default_profile() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+
+        std::fs::write(
+            root.join("profiles.ini"),
+            "[General]\nStartWithLastProfile=1\n\n\
+             [Profile1]\nName=other\nIsRelative=1\nPath=xyz.other\n\n\
+             [Profile0]\nName=default\nIsRelative=1\nPath=abc.default\nDefault=1\n",
+        )
+        .unwrap();
+        assert_eq!(
+            firefox::find_default_profile(root).unwrap(),
+            root.join("abc.default")
+        );
+
+        std::fs::write(
+            root.join("profiles.ini"),
+            "[Install4F96D1932A9F858E]\nDefault=xyz.other\nLocked=1\n\n\
+             [Profile0]\nName=default\nIsRelative=1\nPath=abc.default\nDefault=1\n",
+        )
+        .unwrap();
+        assert_eq!(
+            firefox::find_default_profile(root).unwrap(),
+            root.join("xyz.other")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_export_thunderbird_data_skips_mail_stores() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let profile_dir = temp_dir.path().join("profile");
+        let staging_dir = temp_dir.path().join("staging");
+
+        let account_dir = profile_dir.join("ImapMail").join("imap.example.com");
+        std::fs::create_dir_all(&account_dir).unwrap();
+        std::fs::write(account_dir.join("msgFilterRules.dat"), "version=\"9\"").unwrap();
+        std::fs::write(account_dir.join("INBOX"), "From - mail store").unwrap();
+        std::fs::write(account_dir.join("INBOX.msf"), "index").unwrap();
+        std::fs::write(profile_dir.join("prefs.js"), "user_pref();").unwrap();
+        create_abook(&profile_dir.join("abook.sqlite"));
+
+        export_thunderbird_data(&profile_dir, &staging_dir)
+            .await
+            .unwrap();
+
+        assert!(staging_dir.join("prefs.js").exists());
+        let staged_account = staging_dir.join("ImapMail").join("imap.example.com");
+        assert!(staged_account.join("msgFilterRules.dat").exists());
+        assert!(!staged_account.join("INBOX").exists());
+        assert!(!staged_account.join("INBOX.msf").exists());
+        assert!(!staging_dir.join("abook.sqlite.export").exists());
+
+        let vcards =
+            std::fs::read_to_string(staging_dir.join("addressbooks").join("abook.vcf")).unwrap();
+        assert!(vcards.contains("FN:Ada Lovelace\r\n"));
+        assert!(vcards.contains("N:Lovelace;Ada;;;\r\n"));
+        assert!(vcards.contains("EMAIL;PREF=1:ada@example.com\r\n"));
+        assert!(vcards.contains("NOTE:Analyst\\; engine\\, notes\r\n"));
+        assert!(vcards.contains("FN:Grace Hopper\r\nEND:VCARD\r\n"));
+        assert_eq!(vcards.matches("BEGIN:VCARD").count(), 2);
+    }
+}
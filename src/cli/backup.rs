@@ -1,5 +1,9 @@
-use crate::backup::service::BackupService;
+use crate::backup::service::{BackupService, SourceOutcome};
+use crate::backup::{recovery, size};
 use crate::config;
+use crate::lock::{self, LockMode};
+use crate::models::config::{Config, RepositoryFormat};
+use crate::sync::service::SyncService;
 use anyhow::Result;
 use base64::{engine::general_purpose, Engine as _};
 use clap::Args;
@@ -13,18 +17,27 @@ pub struct BackupArgs {
     /// Perform dry run without actual backup
     #[arg(long)]
     pub dry_run: bool,
+
+    /// Wait for another running backup or sync instead of failing
+    #[arg(long)]
+    pub wait: bool,
 }
 
 pub async fn run(args: BackupArgs) -> Result<()> {
+    let data_dir = data_dir();
+
+    // Taken before the config is read, so a waiting run sees the other run's updates
+    let _lock = if args.dry_run {
+        None
+    } else {
+        let lock = lock::acquire(&data_dir, lock_mode(args.wait)).await?;
+        recovery::clean_up(&data_dir)?;
+        Some(lock)
+    };
+
     let config_path = config::get_config_path()?;
     let config = config::load_config(&config_path)?;
 
-    if args.dry_run {
-        info!("Starting dry run backup");
-        println!("Dry run: Would backup enabled sources");
-        return Ok(());
-    }
-
     // Use encryption_key from config as the derived key
     if config.general.encryption_key.is_empty() {
         return Err(anyhow::anyhow!(
@@ -32,11 +45,6 @@ pub async fn run(args: BackupArgs) -> Result<()> {
         ));
     }
 
-    let data_dir = dirs::data_local_dir()
-        .unwrap_or_else(|| PathBuf::from("/tmp"))
-        .join("briefcase")
-        .join("data");
-
     let config_arc = Arc::new(Mutex::new(config));
     let service = BackupService::new(config_arc.clone(), data_dir);
 
@@ -51,13 +59,139 @@ pub async fn run(args: BackupArgs) -> Result<()> {
     let mut encryption_key = [0u8; 32];
     encryption_key.copy_from_slice(&encryption_key_bytes);
 
-    let backup_files = service.perform_backup_with_key(&encryption_key).await?;
+    if args.dry_run {
+        info!("Starting dry run backup");
+        let config = config_arc.lock().await.clone();
+        return print_dry_run(&service, &config, &encryption_key).await;
+    }
+
+    let report = service.perform_backup_with_key(&encryption_key).await?;
 
+    // Successful sources are recorded even when others failed
     let updated_config = config_arc.lock().await.clone();
     config::save_config(&updated_config, &config_path)?;
 
-    for file in backup_files {
-        println!("Created backup: {:?}", file.path);
+    for source in &report.sources {
+        match &source.outcome {
+            SourceOutcome::Created(file) => {
+                println!("{}: created {:?}", source.source_type.name(), file.path)
+            }
+            SourceOutcome::Skipped => println!(
+                "{}: skipped, unchanged since its last backup",
+                source.source_type.name()
+            ),
+            SourceOutcome::Failed(e) => {
+                println!("{}: failed: {:#}", source.source_type.name(), e)
+            }
+        }
+    }
+
+    let failed = report.failures().count();
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} sources failed to back up",
+            failed,
+            report.sources.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Data directory the CLI commands work on.
+pub(crate) fn data_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("briefcase")
+        .join("data")
+}
+
+pub(crate) fn lock_mode(wait: bool) -> LockMode {
+    if wait {
+        LockMode::Wait
+    } else {
+        LockMode::FailFast
+    }
+}
+
+/// Prints what a backup would archive, delete, and sync, failing if any source would fail.
+async fn print_dry_run(
+    service: &BackupService,
+    config: &Config,
+    encryption_key: &[u8; 32],
+) -> Result<()> {
+    println!("Dry run: nothing will be written or deleted");
+
+    let previews = service.preview_backup_with_key(encryption_key).await;
+    if previews.is_empty() {
+        println!("No sources are enabled");
+    }
+    let mut failed = 0;
+    for (source_type, preview) in &previews {
+        let preview = match preview {
+            Ok(preview) => preview,
+            Err(e) => {
+                failed += 1;
+                println!("{}: would fail: {:#}", source_type.name(), e);
+                continue;
+            }
+        };
+
+        let limit = match preview.max_size {
+            Some(max_size) => format!(" of {} allowed", size::human_size(max_size)),
+            None => String::new(),
+        };
+        match preview.inputs {
+            Some((file_count, total_size)) => println!(
+                "{}: {} files, {}{}",
+                source_type.name(),
+                file_count,
+                size::human_size(total_size),
+                limit
+            ),
+            None => println!("{}: size known once exported{}", source_type.name(), limit),
+        }
+        for action in &preview.actions {
+            println!("  {}", action);
+        }
+
+        let kind = if config.general.repository == RepositoryFormat::Chunks {
+            "a snapshot"
+        } else if preview.incremental {
+            "an incremental archive"
+        } else {
+            "a full archive"
+        };
+        match preview.estimated_size {
+            Some(estimated_size) => println!(
+                "  would store {} of about {} before compression",
+                kind,
+                size::human_size(estimated_size)
+            ),
+            None => println!("  would store {}", kind),
+        }
+        for path in &preview.expired {
+            println!("  retention would delete {:?}", path);
+        }
+    }
+
+    let destinations = SyncService::new(config.clone()).destinations();
+    if destinations.is_empty() {
+        println!("No remotes are enabled; archives stay local");
+    }
+    for (remote, destination) in destinations {
+        println!(
+            "Remote {} would receive the archives at {} on the next sync",
+            remote, destination
+        );
+    }
+
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} sources would fail to back up",
+            failed,
+            previews.len()
+        ));
     }
 
     Ok(())
@@ -1,4 +1,5 @@
 use crate::config;
+use crate::lock::{self, LockMode};
 use anyhow::Result;
 use base64::{engine::general_purpose, Engine as _};
 use clap::Args;
@@ -40,10 +41,18 @@ pub async fn run(args: ConfigArgs) -> Result<()> {
 
     // Initialize directories when initializing config
     if args.action == "init" {
-        crate::config::get_data_dir()?;
         crate::config::get_log_dir()?;
     }
 
+    // Actions that write the config keep backups from reading a half-replaced key and from
+    // overwriting the edit with their last_backup times
+    let _lock = if matches!(args.action.as_str(), "init" | "edit") {
+        let data_dir = crate::config::get_data_dir()?;
+        Some(lock::acquire(&data_dir, LockMode::FailFast).await?)
+    } else {
+        None
+    };
+
     match args.action.as_str() {
         "init" => {
             let password = args
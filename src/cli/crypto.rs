@@ -1,6 +1,7 @@
-use crate::backup::compress;
+use crate::backup::{chunks, gpg, incremental, ssh};
 use crate::config;
 use crate::crypto::encrypt;
+use crate::models::backup_file::SourceType;
 use anyhow::Result;
 use clap::Args;
 
@@ -43,9 +44,6 @@ pub async fn run(args: CryptoArgs) -> Result<()> {
             let input_path = Path::new(&input);
             let output_path = Path::new(&output);
 
-            // Create temp file for decrypted 7Zip
-            let temp_7z_path = output_path.with_extension("temp.7z");
-
             // Try to get encryption key from config first
             let encryption_key_bytes = match config::get_config_path() {
                 Ok(config_path) => match config::load_config(&config_path) {
@@ -85,15 +83,38 @@ pub async fn run(args: CryptoArgs) -> Result<()> {
             let mut encryption_key = [0u8; 32];
             encryption_key.copy_from_slice(&encryption_key_bytes);
 
-            // Decrypt AES-encrypted file using derived key
-            encrypt::decrypt_file_with_derived_key(input_path, &temp_7z_path, &encryption_key)?;
-
-            // Extract 7Zip archive (no password needed for archives created by compress_directory)
-            compress::extract_archive(&temp_7z_path, output_path)?;
+            if chunks::is_snapshot(input_path) {
+                // Chunks are read from the repository the snapshot manifest lives in
+                chunks::restore_snapshot(input_path, output_path, &encryption_key)?;
+            } else {
+                // Archives are decrypted as they are extracted so plaintext archive data is never
+                // written to disk; an incremental archive also pulls in its full backup and earlier
+                // increments
+                let chain = incremental::restore(input_path, output_path, &encryption_key)?;
+                if chain.len() > 1 {
+                    println!(
+                        "Applied {} archives, starting from full backup {}",
+                        chain.len(),
+                        chain[0].display()
+                    );
+                }
+            }
 
-            // Clean up temp file
-            if temp_7z_path.exists() {
-                std::fs::remove_file(&temp_7z_path)?;
+            // Archived modes are reapplied on extraction, but key material is tightened regardless
+            let source_type = input_path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(SourceType::from_filename);
+            match source_type {
+                Some(SourceType::Ssh) => {
+                    ssh::restore_ssh_permissions(&output_path.join(ssh::ARCHIVE_DIR))?;
+                    println!("Restored SSH key permissions (0700/0600)");
+                }
+                Some(SourceType::Gpg) => {
+                    gpg::restore_gpg_permissions(&output_path.join(gpg::ARCHIVE_DIR))?;
+                    println!("Restored GnuPG keyring permissions (0700/0600)");
+                }
+                _ => {}
             }
 
             println!("Decryption and extraction completed to: {}", output);
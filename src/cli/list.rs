@@ -0,0 +1,105 @@
+use crate::backup::{chunks, compress, pins, size};
+use crate::cli::backup;
+use crate::models::backup_file::BackupName;
+use anyhow::Result;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Prints the archives and snapshots in the data directory, oldest first per source, marking
+/// pinned ones.
+pub async fn run() -> Result<()> {
+    let data_dir = backup::data_dir();
+    let backups = list_backups(&data_dir)?;
+    if backups.is_empty() {
+        println!("No backups in {:?}", data_dir);
+        return Ok(());
+    }
+
+    for path in backups {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let size = match fs::metadata(&path) {
+            Ok(metadata) => size::human_size(metadata.len()),
+            Err(_) => "missing locally".to_string(),
+        };
+        let pin = match pins::pin_note(&path) {
+            Some(note) if note.is_empty() => "  pinned".to_string(),
+            Some(note) => format!("  pinned: {}", note),
+            None => String::new(),
+        };
+        println!("{}  {}{}", name, size, pin);
+    }
+    Ok(())
+}
+
+/// Archives and snapshots in the data directory, plus pinned ones that are gone, sorted by
+/// source, host, and time. Names that do not parse come last.
+fn list_backups(data_dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut backups = Vec::new();
+    let snapshots_dir = chunks::snapshots_dir(data_dir);
+    for dir in [data_dir, snapshots_dir.as_path()] {
+        if !dir.exists() {
+            continue;
+        }
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if let Some(backup) = pins::pinned_backup(&path) {
+                if !backup.exists() {
+                    backups.push(backup);
+                }
+                continue;
+            }
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if compress::is_archive(&name) || chunks::is_snapshot(&path) {
+                backups.push(path);
+            }
+        }
+    }
+
+    backups.sort_by_cached_key(|path| {
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        let parsed = BackupName::parse(&name)
+            .map(|parsed| (parsed.source_type.name(), parsed.host, parsed.timestamp));
+        (parsed.is_none(), parsed, name)
+    });
+    Ok(backups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_backups_includes_missing_pinned_backups() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path();
+        for name in [
+            "Folder_laptop_2026-04-22_10-00-00.7z",
+            "Firefox_laptop_2026-04-22_11-00-00.7z",
+            "Firefox_laptop_2026-04-21_11-00-00.7z",
+            "Firefox_laptop_2026-04-21_11-00-00.7z.pin",
+            "Firefox_laptop_2026-04-01_11-00-00.7z.pin",
+            "Folder.index",
+        ] {
+            fs::write(data_dir.join(name), b"backup").unwrap();
+        }
+
+        let names: Vec<String> = list_backups(data_dir)
+            .unwrap()
+            .iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "Firefox_laptop_2026-04-01_11-00-00.7z",
+                "Firefox_laptop_2026-04-21_11-00-00.7z",
+                "Firefox_laptop_2026-04-22_11-00-00.7z",
+                "Folder_laptop_2026-04-22_10-00-00.7z",
+            ]
+        );
+    }
+}
@@ -3,6 +3,8 @@ pub mod clean;
 pub mod config;
 pub mod crypto;
 pub mod install;
+pub mod list;
+pub mod pin;
 pub mod schedule;
 pub mod sync;
 pub mod uninstall;
@@ -30,6 +32,12 @@ pub enum Commands {
     Schedule(schedule::ScheduleArgs),
     /// Crypto operations
     Crypto(crypto::CryptoArgs),
+    /// List backups in the data directory
+    List,
+    /// Keep a backup regardless of retention
+    Pin(pin::PinArgs),
+    /// Let retention delete a pinned backup again
+    Unpin(pin::UnpinArgs),
     /// Print the current version
     Version,
     /// Install the binary
@@ -47,6 +55,9 @@ pub async fn run(cli: Cli) -> Result<()> {
         Commands::Sync(args) => sync::run(args).await,
         Commands::Schedule(args) => schedule::run(args).await,
         Commands::Crypto(args) => crypto::run(args).await,
+        Commands::List => list::run().await,
+        Commands::Pin(args) => pin::pin(args).await,
+        Commands::Unpin(args) => pin::unpin(args).await,
         Commands::Version => {
             println!("briefcase {}", env!("CARGO_PKG_VERSION"));
             Ok(())
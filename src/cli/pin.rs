@@ -0,0 +1,56 @@
+use crate::backup::pins;
+use crate::cli::backup;
+use crate::lock;
+use anyhow::Result;
+use clap::Args;
+
+#[derive(Args)]
+pub struct PinArgs {
+    /// Archive or snapshot to keep, by file name in the data directory or by path
+    pub backup: String,
+
+    /// Why the backup is kept, shown by `briefcase list`
+    #[arg(long, default_value = "")]
+    pub note: String,
+
+    /// Wait for another running backup or sync instead of failing
+    #[arg(long)]
+    pub wait: bool,
+}
+
+#[derive(Args)]
+pub struct UnpinArgs {
+    /// Pinned archive or snapshot, by file name in the data directory or by path
+    pub backup: String,
+
+    /// Wait for another running backup or sync instead of failing
+    #[arg(long)]
+    pub wait: bool,
+}
+
+pub async fn pin(args: PinArgs) -> Result<()> {
+    let data_dir = backup::data_dir();
+    // Retention must not delete the backup while it is being pinned
+    let _lock = lock::acquire(&data_dir, backup::lock_mode(args.wait)).await?;
+
+    let path = pins::resolve(&data_dir, &args.backup)?;
+    pins::pin(&path, &args.note)?;
+    println!(
+        "Pinned {:?}; retention will keep it until it is unpinned",
+        path
+    );
+    Ok(())
+}
+
+pub async fn unpin(args: UnpinArgs) -> Result<()> {
+    let data_dir = backup::data_dir();
+    let _lock = lock::acquire(&data_dir, backup::lock_mode(args.wait)).await?;
+
+    let path = pins::resolve(&data_dir, &args.backup)?;
+    if pins::unpin(&path)? {
+        println!("Unpinned {:?}; retention may delete it", path);
+    } else {
+        println!("{:?} was not pinned", path);
+    }
+    Ok(())
+}
@@ -1,4 +1,7 @@
+use crate::backup::{compress, recovery};
+use crate::cli::backup;
 use crate::config;
+use crate::lock;
 use crate::models::backup_file::BackupFile;
 use crate::models::backup_file::SourceType;
 use crate::sync::service::SyncService;
@@ -20,27 +23,22 @@ fn find_backup_files(data_dir: &PathBuf) -> Result<Vec<BackupFile>> {
         let entry = entry?;
         let path = entry.path();
 
-        if let Some(extension) = path.extension() {
-            if extension == "7z" {
-                // Parse filename to determine source type
-                let filename = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
-
-                let source_type = if filename.starts_with("Firefox_") {
-                    SourceType::Firefox
-                } else if filename.starts_with("Folder_") {
-                    SourceType::Folder
-                } else {
-                    continue; // Skip files with unknown format
-                };
-
-                let mut backup_file = BackupFile::new(path, source_type);
-                if let Ok(metadata) = entry.metadata() {
-                    backup_file.size = metadata.len();
-                }
-
-                backup_files.push(backup_file);
-            }
+        let filename = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if !compress::is_archive(filename) {
+            continue;
         }
+
+        // Parse filename to determine source type
+        let Some(source_type) = SourceType::from_filename(filename) else {
+            continue; // Skip files with unknown format
+        };
+
+        let mut backup_file = BackupFile::new(path, source_type);
+        if let Ok(metadata) = entry.metadata() {
+            backup_file.size = metadata.len();
+        }
+
+        backup_files.push(backup_file);
     }
 
     Ok(backup_files)
@@ -51,9 +49,23 @@ pub struct SyncArgs {
     /// Dry run
     #[arg(long)]
     pub dry_run: bool,
+
+    /// Wait for another running backup or sync instead of failing
+    #[arg(long)]
+    pub wait: bool,
 }
 
 pub async fn run(args: SyncArgs) -> Result<()> {
+    let data_dir = backup::data_dir();
+    let _lock = if args.dry_run {
+        None
+    } else {
+        let lock = lock::acquire(&data_dir, backup::lock_mode(args.wait)).await?;
+        // Never upload what an interrupted backup left behind
+        recovery::clean_up(&data_dir)?;
+        Some(lock)
+    };
+
     let config_path = config::get_config_path()?;
     let mut config = config::load_config(&config_path)?;
 
@@ -63,11 +75,6 @@ pub async fn run(args: SyncArgs) -> Result<()> {
     service.validate_remotes().await?;
 
     // Get actual backup files from the data directory
-    let data_dir = dirs::data_local_dir()
-        .unwrap_or_else(|| PathBuf::from("/tmp"))
-        .join("briefcase")
-        .join("data");
-
     let backup_files = find_backup_files(&data_dir)?;
 
     let synced_remotes = service
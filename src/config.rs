@@ -1,4 +1,5 @@
-use crate::models::config::Config;
+use crate::models::backup_file::{is_valid_host_id, SourceType};
+use crate::models::config::{Compression, Config, Container, RetentionPolicy};
 use anyhow::{anyhow, Result};
 use std::path::Path;
 
@@ -38,6 +39,15 @@ pub fn validate_config(config: &Config) -> Result<()> {
     if config.general.max_retention == 0 || config.general.max_retention > 10 {
         return Err(anyhow!("max_retention must be between 1 and 10"));
     }
+    if config.general.max_concurrent_backups == 0 {
+        return Err(anyhow!("max_concurrent_backups must be at least 1"));
+    }
+    if !config.general.host_id.is_empty() && !is_valid_host_id(&config.general.host_id) {
+        return Err(anyhow!(
+            "host_id must be lowercase letters, digits, and dashes, not starting or ending with a dash: {:?}",
+            config.general.host_id
+        ));
+    }
 
     // Validate sources
     if config.source.firefox.enabled && !config.source.firefox.dir.exists() {
@@ -54,6 +64,119 @@ pub fn validate_config(config: &Config) -> Result<()> {
         ));
     }
 
+    let chromium = &config.source.chromium;
+    if chromium.enabled && !chromium.dir.as_os_str().is_empty() && !chromium.dir.exists() {
+        return Err(anyhow!(
+            "Chromium profile directory does not exist: {:?}",
+            chromium.dir
+        ));
+    }
+
+    let thunderbird = &config.source.thunderbird;
+    if thunderbird.enabled && !thunderbird.dir.as_os_str().is_empty() && !thunderbird.dir.exists() {
+        return Err(anyhow!(
+            "Thunderbird profile directory does not exist: {:?}",
+            thunderbird.dir
+        ));
+    }
+
+    for (name, enabled, dir) in [
+        ("SSH", config.source.ssh.enabled, &config.source.ssh.dir),
+        ("GnuPG", config.source.gpg.enabled, &config.source.gpg.dir),
+    ] {
+        if enabled && !dir.as_os_str().is_empty() && !dir.exists() {
+            return Err(anyhow!("{} directory does not exist: {:?}", name, dir));
+        }
+    }
+
+    let command = &config.source.command;
+    if command.enabled && command.commands.is_empty() {
+        return Err(anyhow!("Command source is enabled but has no commands"));
+    }
+    let mut outputs = std::collections::HashSet::new();
+    for entry in &command.commands {
+        let is_plain_name = Path::new(&entry.output)
+            .file_name()
+            .is_some_and(|name| name == entry.output.as_str());
+        if !is_plain_name || entry.output == ".." {
+            return Err(anyhow!(
+                "Command output must be a plain file name: {:?}",
+                entry.output
+            ));
+        }
+        if !outputs.insert(entry.output.as_str()) {
+            return Err(anyhow!("Duplicate command output name: {}", entry.output));
+        }
+    }
+
+    let git = &config.source.git;
+    if git.enabled && git.repos.is_empty() {
+        return Err(anyhow!("Git source is enabled but has no repositories"));
+    }
+    if git.enabled {
+        for repo in &git.repos {
+            if !repo.exists() {
+                return Err(anyhow!("Git repository does not exist: {:?}", repo));
+            }
+        }
+    }
+
+    let files = &config.source.files;
+    if files.enabled && files.paths.is_empty() {
+        return Err(anyhow!("Files source is enabled but has no paths"));
+    }
+    if files.enabled {
+        for path in &files.paths {
+            if !path.is_file() {
+                return Err(anyhow!("File does not exist: {:?}", path));
+            }
+        }
+    }
+
+    let general = &config.general;
+    let mut global_hooks = general.pre_backup.iter().chain(&general.post_backup);
+    if global_hooks.any(|hook| hook.command.trim().is_empty()) {
+        return Err(anyhow!("Backup hook command cannot be empty"));
+    }
+
+    for source_type in SourceType::ALL {
+        let options = config.source.options(source_type);
+        let mut hooks = options.pre_backup.iter().chain(&options.post_backup);
+        if hooks.any(|hook| hook.command.trim().is_empty()) {
+            return Err(anyhow!(
+                "{} backup hook command cannot be empty",
+                source_type.name()
+            ));
+        }
+        if options.container == Container::TarZst && options.compression == Compression::Store {
+            return Err(anyhow!(
+                "{} uses the TarZst container, which is always compressed; use Zstd instead of Store",
+                source_type.name()
+            ));
+        }
+        if options.container == Container::SevenZ && options.compression == Compression::Zstd {
+            return Err(anyhow!(
+                "{} uses Zstd compression, which needs container = \"TarZst\"; 7z archives use Lzma2 or Store",
+                source_type.name()
+            ));
+        }
+        if let Some(policy) = &options.retention {
+            validate_retention(source_type, policy)?;
+        }
+        if let Some(level) = options.compression_level {
+            let levels = options.compression.levels();
+            if !levels.contains(&level) {
+                return Err(anyhow!(
+                    "{} compression_level for {:?} must be between {} and {}",
+                    source_type.name(),
+                    options.compression,
+                    levels.start(),
+                    levels.end()
+                ));
+            }
+        }
+    }
+
     // Validate remotes - no API credentials needed as they're stored in rclone config
     for (remote_key, remote_provider) in &config.remote.providers {
         if remote_provider.enabled && remote_provider.name.is_empty() {
@@ -64,6 +187,38 @@ pub fn validate_config(config: &Config) -> Result<()> {
     Ok(())
 }
 
+fn validate_retention(source_type: SourceType, policy: &RetentionPolicy) -> Result<()> {
+    let limits = [
+        ("keep_last", policy.keep_last),
+        ("keep_hourly", policy.keep_hourly),
+        ("keep_daily", policy.keep_daily),
+        ("keep_weekly", policy.keep_weekly),
+        ("keep_monthly", policy.keep_monthly),
+        ("keep_yearly", policy.keep_yearly),
+        ("max_age_days", policy.max_age_days),
+    ];
+    if let Some((name, _)) = limits.iter().find(|(_, value)| *value == Some(0)) {
+        return Err(anyhow!(
+            "{} retention {} must be at least 1",
+            source_type.name(),
+            name
+        ));
+    }
+    if policy.max_total_size == Some(0) {
+        return Err(anyhow!(
+            "{} retention max_total_size must be larger than zero",
+            source_type.name()
+        ));
+    }
+    if *policy == RetentionPolicy::default() {
+        return Err(anyhow!(
+            "{} retention must set a keep_* rule, max_age_days, or max_total_size",
+            source_type.name()
+        ));
+    }
+    Ok(())
+}
+
 pub fn get_config_path() -> Result<std::path::PathBuf> {
     let config_dir = dirs::config_dir()
         .ok_or_else(|| anyhow!("Could not find config directory"))?
@@ -102,6 +257,7 @@ pub fn get_log_dir() -> Result<std::path::PathBuf> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::backup_file::SourceType;
     use chrono::Local;
     use std::path::PathBuf;
 
@@ -127,6 +283,98 @@ mod tests {
         assert!(validate_config(&config).is_err());
     }
 
+    #[test]
+    fn test_validate_max_concurrent_backups() {
+        let mut config = Config::default();
+        assert_eq!(config.general.max_concurrent_backups, 2);
+        config.general.max_concurrent_backups = 0;
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_validate_host_id() {
+        let mut config = Config::default();
+        config.general.host_id = "work-laptop".to_string();
+        assert!(validate_config(&config).is_ok());
+
+        for host_id in ["Work", "work_laptop", "-work", "work.example"] {
+            config.general.host_id = host_id.to_string();
+            assert!(validate_config(&config).is_err(), "{}", host_id);
+        }
+    }
+
+    #[test]
+    fn test_validate_retention_policy() {
+        let mut config = Config::default();
+        config.source.firefox.options.retention = Some(RetentionPolicy {
+            keep_hourly: Some(24),
+            keep_daily: Some(7),
+            ..RetentionPolicy::default()
+        });
+        assert!(validate_config(&config).is_ok());
+
+        config.source.firefox.options.retention = Some(RetentionPolicy {
+            keep_daily: Some(0),
+            ..RetentionPolicy::default()
+        });
+        assert!(validate_config(&config).is_err());
+
+        config.source.firefox.options.retention = Some(RetentionPolicy::default());
+        assert!(validate_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_save_and_load_retention_policy() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        let mut config = Config::default();
+        let policy = RetentionPolicy {
+            keep_last: Some(3),
+            keep_daily: Some(7),
+            keep_monthly: Some(12),
+            max_age_days: Some(400),
+            max_total_size: Some(5 * 1024 * 1024 * 1024),
+            ..RetentionPolicy::default()
+        };
+        config.source.folder.options.retention = Some(policy.clone());
+
+        save_config(&config, &config_path).unwrap();
+        let contents = std::fs::read_to_string(&config_path).unwrap();
+        assert!(contents.contains("[source.folder.retention]"));
+        assert!(contents.contains("max_total_size = \"5GiB\""));
+
+        let loaded = load_config(&config_path).unwrap();
+        assert_eq!(loaded.retention_policy(SourceType::Folder), policy);
+        assert_eq!(
+            loaded.retention_policy(SourceType::Firefox),
+            RetentionPolicy::keep_last(10)
+        );
+    }
+
+    #[test]
+    fn test_validate_compression_level_per_method() {
+        let mut config = Config::default();
+        config.source.folder.options.container = Container::TarZst;
+        config.source.folder.options.compression = Compression::Zstd;
+        config.source.folder.options.compression_level = Some(19);
+        assert!(validate_config(&config).is_ok());
+
+        config.source.folder.options.compression = Compression::Lzma2;
+        assert!(validate_config(&config).is_err());
+
+        config.source.folder.options.compression = Compression::Store;
+        config.source.folder.options.compression_level = Some(0);
+        assert!(validate_config(&config).is_err());
+
+        config.source.folder.options.container = Container::SevenZ;
+        assert!(validate_config(&config).is_ok());
+
+        // Zstandard is only offered through tar.zst
+        config.source.folder.options.compression = Compression::Zstd;
+        config.source.folder.options.compression_level = None;
+        assert!(validate_config(&config).is_err());
+    }
+
     #[test]
     fn test_validate_missing_firefox_dir() {
         let mut config = Config::default();
@@ -135,6 +383,125 @@ mod tests {
         assert!(validate_config(&config).is_err());
     }
 
+    #[test]
+    fn test_save_and_load_command_source() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        let mut config = Config::default();
+        config.source.command.enabled = true;
+        config.source.command.commands = vec![
+            crate::models::config::CommandOutput {
+                command: "crontab -l".to_string(),
+                output: "crontab.txt".to_string(),
+                timeout_secs: None,
+            },
+            crate::models::config::CommandOutput {
+                command: "kubectl config view --raw".to_string(),
+                output: "kubeconfig.yaml".to_string(),
+                timeout_secs: Some(5),
+            },
+        ];
+
+        save_config(&config, &config_path).unwrap();
+        let loaded = load_config(&config_path).unwrap();
+
+        assert_eq!(loaded.source.command.timeout_secs, 60);
+        assert_eq!(loaded.source.command.commands.len(), 2);
+        assert_eq!(loaded.source.command.commands[1].timeout_secs, Some(5));
+    }
+
+    #[test]
+    fn test_save_and_load_source_max_size() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        let mut config = Config::default();
+        config.source.folder.options.max_size = Some(500 * 1024 * 1024);
+        config.source.folder.options.max_size_warn_only = true;
+
+        save_config(&config, &config_path).unwrap();
+        let contents = std::fs::read_to_string(&config_path).unwrap();
+        assert!(contents.contains("max_size = \"500MiB\""));
+
+        let loaded = load_config(&config_path).unwrap();
+        assert_eq!(
+            loaded.source.folder.options.max_size,
+            Some(500 * 1024 * 1024)
+        );
+        assert!(loaded.source.folder.options.max_size_warn_only);
+        assert_eq!(
+            loaded.source.max_size(SourceType::Firefox),
+            Some(crate::models::config::DEFAULT_FIREFOX_MAX_SIZE)
+        );
+        assert_eq!(loaded.source.max_size(SourceType::Chromium), None);
+
+        std::fs::write(
+            &config_path,
+            contents.replace("max_size = \"500MiB\"", "max_size = 2048"),
+        )
+        .unwrap();
+        let loaded = load_config(&config_path).unwrap();
+        assert_eq!(loaded.source.folder.options.max_size, Some(2048));
+
+        assert_eq!(crate::models::config::parse_size("2 GB"), Ok(2_000_000_000));
+        assert!(crate::models::config::parse_size("lots").is_err());
+    }
+
+    #[test]
+    fn test_load_backup_hooks() {
+        use crate::models::config::HookFailure;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let config_path = temp_dir.path().join("config.toml");
+        let contents = toml::to_string_pretty(&Config::default()).unwrap();
+        let contents = contents.replacen(
+            "[source.folder]\n",
+            "[[general.post_backup]]\ncommand = \"notify-send backup\"\n\n[source.folder]\n",
+            1,
+        ) + "\n[[source.folder.pre_backup]]\ncommand = \"pg_dump app > \\\"$BRIEFCASE_TEMP_DIR/app.sql\\\"\"\ntimeout_secs = 600\non_failure = \"Warn\"\n";
+        std::fs::write(&config_path, contents).unwrap();
+
+        let loaded = load_config(&config_path).unwrap();
+        let pre_backup = &loaded.source.folder.options.pre_backup;
+        assert_eq!(pre_backup.len(), 1);
+        assert_eq!(pre_backup[0].timeout_secs, 600);
+        assert_eq!(pre_backup[0].on_failure, HookFailure::Warn);
+        assert_eq!(loaded.general.post_backup[0].timeout_secs, 60);
+        assert_eq!(loaded.general.post_backup[0].on_failure, HookFailure::Abort);
+
+        save_config(&loaded, &config_path).unwrap();
+        let reloaded = load_config(&config_path).unwrap();
+        assert_eq!(reloaded.source.folder.options.pre_backup.len(), 1);
+        assert_eq!(reloaded.general.post_backup.len(), 1);
+    }
+
+    #[test]
+    fn test_validate_command_output_names() {
+        let mut config = Config::default();
+        config.source.command.enabled = true;
+        assert!(validate_config(&config).is_err());
+
+        for (output, valid) in [
+            ("crontab.txt", true),
+            ("../crontab.txt", false),
+            ("nested/crontab.txt", false),
+            ("..", false),
+        ] {
+            config.source.command.commands = vec![crate::models::config::CommandOutput {
+                command: "crontab -l".to_string(),
+                output: output.to_string(),
+                timeout_secs: None,
+            }];
+            assert_eq!(validate_config(&config).is_ok(), valid, "{}", output);
+        }
+
+        config
+            .source
+            .command
+            .commands
+            .push(config.source.command.commands[0].clone());
+        assert!(validate_config(&config).is_err());
+    }
+
     #[test]
     fn test_save_and_load_last_backup() {
         let temp_dir = tempfile::tempdir().unwrap();
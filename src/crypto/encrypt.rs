@@ -1,3 +1,4 @@
+use crate::crypto::stream;
 use aead::{Aead, Payload};
 use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
 use anyhow::Result;
@@ -5,7 +6,9 @@ use argon2::password_hash::{PasswordHash, SaltString};
 use argon2::{Argon2, PasswordVerifier};
 use rand::RngCore;
 use std::fs;
-use std::path::Path;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
 
 pub fn derive_key(password_key: &str, salt: &[u8; 32]) -> Result<[u8; 32]> {
     let salt_string =
@@ -29,7 +32,45 @@ pub fn encrypt_file_with_derived_key(
     output_path: &Path,
     encryption_key: &[u8; 32],
 ) -> Result<()> {
-    let input_data = fs::read(input_path)?;
+    let mut input = fs::File::open(input_path)?;
+    encrypt_stream_to_file(output_path, encryption_key, |encryptor| {
+        std::io::copy(&mut input, encryptor)?;
+        Ok(())
+    })
+}
+
+/// Encrypts whatever `write` writes into `output_path` in the segmented [`stream`] format, so
+/// large plaintext such as an archive is never held in memory.
+///
+/// The ciphertext goes to a [`PartialFile`], which only gets its final name once `write` and
+/// encryption succeed; on failure it is removed.
+pub fn encrypt_stream_to_file<F>(
+    output_path: &Path,
+    encryption_key: &[u8; 32],
+    write: F,
+) -> Result<()>
+where
+    F: FnOnce(&mut stream::Encryptor<PartialFile>) -> Result<()>,
+{
+    let mut encryptor = stream::Encryptor::new(PartialFile::create(output_path)?, encryption_key)?;
+    write(&mut encryptor)?;
+    encryptor.finish()?.commit()
+}
+
+/// Bytes `encrypt_to_file_with_derived_key` adds to its input: salt, nonce, and GCM tag.
+pub const ENCRYPTION_OVERHEAD: u64 = 32 + 12 + 16;
+
+/// Suffix of files still being written; they are renamed to their final name once complete.
+pub const PARTIAL_SUFFIX: &str = ".partial";
+
+/// Encrypts `input_data` into `output_path`, so plaintext held in memory never touches disk.
+///
+/// The ciphertext is written atomically: `output_path` either does not exist or is complete.
+pub fn encrypt_to_file_with_derived_key(
+    input_data: &[u8],
+    output_path: &Path,
+    encryption_key: &[u8; 32],
+) -> Result<()> {
     let mut salt = [0u8; 32];
     rand::thread_rng().fill_bytes(&mut salt);
     let key = aes_gcm::Key::<Aes256Gcm>::from_slice(encryption_key);
@@ -39,7 +80,7 @@ pub fn encrypt_file_with_derived_key(
     rand::thread_rng().fill_bytes(&mut nonce_bytes);
     let nonce = Nonce::from_slice(&nonce_bytes);
     let payload = Payload {
-        msg: input_data.as_ref(),
+        msg: input_data,
         aad: &[],
     };
     let ciphertext = cipher
@@ -51,8 +92,88 @@ pub fn encrypt_file_with_derived_key(
     encrypted_data.extend_from_slice(&nonce_bytes);
     encrypted_data.extend(ciphertext);
 
-    fs::write(output_path, encrypted_data)?;
-    Ok(())
+    write_atomically(output_path, &encrypted_data)
+}
+
+/// Writes `data` under a unique `.partial` name next to `path`, syncs it, and renames it to
+/// `path`, so a crash never leaves a truncated file under the final name.
+pub fn write_atomically(path: &Path, data: &[u8]) -> Result<()> {
+    let mut file = PartialFile::create(path)?;
+    file.write_all(data)?;
+    file.commit()
+}
+
+/// A file being written under a unique `.partial` name next to its final path.
+///
+/// [`PartialFile::commit`] syncs it and renames it into place; dropped without committing, it
+/// is removed, so a failed or interrupted write never leaves a file under the final name.
+pub struct PartialFile {
+    file: fs::File,
+    partial_path: PathBuf,
+    path: PathBuf,
+    committed: bool,
+}
+
+impl PartialFile {
+    pub fn create(path: &Path) -> Result<Self> {
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("Invalid output path: {:?}", path))?;
+        let mut partial_name = file_name.to_os_string();
+        partial_name.push(format!(
+            ".{:016x}{}",
+            rand::thread_rng().next_u64(),
+            PARTIAL_SUFFIX
+        ));
+        let partial_path = path.with_file_name(partial_name);
+
+        Ok(Self {
+            file: fs::File::create(&partial_path)?,
+            partial_path,
+            path: path.to_path_buf(),
+            committed: false,
+        })
+    }
+
+    pub fn commit(mut self) -> Result<()> {
+        self.file.sync_all()?;
+        fs::rename(&self.partial_path, &self.path)?;
+        self.committed = true;
+
+        // Persist the rename itself
+        if let Some(parent) = self
+            .path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::File::open(parent)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for PartialFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Seek for PartialFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_file(&self.partial_path);
+        }
+    }
 }
 
 pub fn decrypt_file_with_derived_key(
@@ -60,7 +181,91 @@ pub fn decrypt_file_with_derived_key(
     output_path: &Path,
     derived_key: &[u8; 32],
 ) -> Result<()> {
-    let encrypted_data = fs::read(input_path)?;
+    let mut plaintext = open_decrypted(input_path, derived_key)?;
+    let result = fs::File::create(output_path)
+        .and_then(|mut output| std::io::copy(&mut plaintext, &mut output));
+    if let Err(e) = result {
+        // Segments are authenticated as they are read, so a bad one fails partway through
+        let _ = fs::remove_file(output_path);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+/// Plaintext of an encrypted file, decrypted as it is read.
+///
+/// Files in the segmented [`stream`] format are read one authenticated segment at a time;
+/// files in the older single-shot format can only be authenticated as a whole, so they are
+/// decrypted into a buffer that is zeroed when dropped.
+pub enum DecryptedReader {
+    Stream(Box<stream::Decryptor<fs::File>>),
+    Whole(Cursor<Zeroizing<Vec<u8>>>),
+}
+
+impl Read for DecryptedReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            DecryptedReader::Stream(decryptor) => decryptor.read(buf),
+            DecryptedReader::Whole(plaintext) => plaintext.read(buf),
+        }
+    }
+}
+
+impl Seek for DecryptedReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            DecryptedReader::Stream(decryptor) => decryptor.seek(pos),
+            DecryptedReader::Whole(plaintext) => plaintext.seek(pos),
+        }
+    }
+}
+
+/// Opens `input_path` for reading its plaintext, in either encryption format.
+pub fn open_decrypted(input_path: &Path, derived_key: &[u8; 32]) -> Result<DecryptedReader> {
+    let mut file = fs::File::open(input_path)?;
+    if is_stream_file(&mut file)? {
+        return Ok(DecryptedReader::Stream(Box::new(stream::Decryptor::new(
+            file,
+            derived_key,
+        )?)));
+    }
+    let mut encrypted_data = Vec::new();
+    file.read_to_end(&mut encrypted_data)?;
+    Ok(DecryptedReader::Whole(Cursor::new(decrypt_whole(
+        &encrypted_data,
+        derived_key,
+    )?)))
+}
+
+/// Decrypts `input_path` into memory; the returned buffer is zeroed when dropped.
+pub fn decrypt_from_file_with_derived_key(
+    input_path: &Path,
+    derived_key: &[u8; 32],
+) -> Result<Zeroizing<Vec<u8>>> {
+    let mut file = fs::File::open(input_path)?;
+    if is_stream_file(&mut file)? {
+        let mut decryptor = stream::Decryptor::new(file, derived_key)?;
+        let mut plaintext = Zeroizing::new(Vec::with_capacity(decryptor.len() as usize));
+        decryptor.read_to_end(&mut plaintext)?;
+        return Ok(plaintext);
+    }
+    let mut encrypted_data = Vec::new();
+    file.read_to_end(&mut encrypted_data)?;
+    decrypt_whole(&encrypted_data, derived_key)
+}
+
+/// Whether `file` starts with the [`stream`] magic, leaving it rewound.
+fn is_stream_file(file: &mut fs::File) -> Result<bool> {
+    let mut header = Vec::with_capacity(stream::MAGIC.len());
+    Read::by_ref(file)
+        .take(stream::MAGIC.len() as u64)
+        .read_to_end(&mut header)?;
+    file.rewind()?;
+    Ok(stream::is_stream(&header))
+}
+
+/// Decrypts data written by [`encrypt_to_file_with_derived_key`].
+fn decrypt_whole(encrypted_data: &[u8], derived_key: &[u8; 32]) -> Result<Zeroizing<Vec<u8>>> {
     if encrypted_data.len() < 44 {
         return Err(anyhow::anyhow!("Invalid encrypted data"));
     }
@@ -81,8 +286,7 @@ pub fn decrypt_file_with_derived_key(
         .decrypt(nonce, payload)
         .map_err(|e| anyhow::anyhow!("Decryption error: {}", e))?;
 
-    fs::write(output_path, plaintext)?;
-    Ok(())
+    Ok(Zeroizing::new(plaintext))
 }
 
 pub fn encrypt_file(input_path: &Path, output_path: &Path, password: &str) -> Result<()> {
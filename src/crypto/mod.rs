@@ -1 +1,3 @@
 pub mod encrypt;
+pub mod nss;
+pub mod stream;
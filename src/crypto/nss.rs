@@ -0,0 +1,492 @@
+use aes::Aes256;
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use cbc::cipher::{block_padding::Pkcs7, BlockDecryptMut, KeyIvInit};
+use des::TdesEde3;
+use hmac::{Hmac, Mac};
+use rusqlite::{Connection, OpenFlags};
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+// 1.2.840.113549.1.5.13
+const OID_PBES2: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x05, 0x0d];
+// 1.2.840.113549.1.5.12
+const OID_PBKDF2: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x05, 0x0c];
+// 1.2.840.113549.1.12.5.1.3
+const OID_PBE_SHA1_3DES: &[u8] = &[
+    0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x0c, 0x05, 0x01, 0x03,
+];
+// 2.16.840.1.101.3.4.1.42
+const OID_AES256_CBC: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x01, 0x2a];
+// 1.2.840.113549.3.7
+const OID_DES_EDE3_CBC: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x03, 0x07];
+
+/// CKA_ID of the key NSS uses to encrypt saved logins.
+const LOGIN_KEY_ID: [u8; 16] = [
+    0xf8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+];
+const PASSWORD_CHECK: &[u8] = b"password-check";
+
+#[derive(Debug)]
+enum Der<'a> {
+    Sequence(Vec<Der<'a>>),
+    OctetString(&'a [u8]),
+    ObjectId(&'a [u8]),
+    Integer(&'a [u8]),
+    Other,
+}
+
+impl<'a> Der<'a> {
+    fn parse(input: &'a [u8]) -> Result<Self> {
+        let (value, _) = parse_der(input)?;
+        Ok(value)
+    }
+
+    fn sequence(&self) -> Result<&[Der<'a>]> {
+        match self {
+            Der::Sequence(items) => Ok(items),
+            _ => Err(anyhow!("Expected DER sequence")),
+        }
+    }
+
+    fn octets(&self) -> Result<&'a [u8]> {
+        match self {
+            Der::OctetString(bytes) => Ok(bytes),
+            _ => Err(anyhow!("Expected DER octet string")),
+        }
+    }
+
+    fn object_id(&self) -> Result<&'a [u8]> {
+        match self {
+            Der::ObjectId(bytes) => Ok(bytes),
+            _ => Err(anyhow!("Expected DER object identifier")),
+        }
+    }
+
+    fn integer(&self) -> Result<u32> {
+        match self {
+            Der::Integer(bytes) if bytes.len() <= 5 => Ok(bytes
+                .iter()
+                .fold(0u64, |value, byte| (value << 8) | u64::from(*byte))
+                .try_into()?),
+            _ => Err(anyhow!("Expected small DER integer")),
+        }
+    }
+}
+
+fn parse_der(input: &[u8]) -> Result<(Der<'_>, &[u8])> {
+    let (&tag, rest) = input
+        .split_first()
+        .ok_or_else(|| anyhow!("Truncated DER value"))?;
+    let (&first, mut rest) = rest
+        .split_first()
+        .ok_or_else(|| anyhow!("Truncated DER length"))?;
+
+    let length = if first & 0x80 == 0 {
+        usize::from(first)
+    } else {
+        let count = usize::from(first & 0x7f);
+        if count == 0 || count > 4 || rest.len() < count {
+            return Err(anyhow!("Unsupported DER length encoding"));
+        }
+        let length = rest[..count]
+            .iter()
+            .fold(0usize, |value, byte| (value << 8) | usize::from(*byte));
+        rest = &rest[count..];
+        length
+    };
+
+    if rest.len() < length {
+        return Err(anyhow!("Truncated DER content"));
+    }
+    let (content, remaining) = rest.split_at(length);
+
+    let value = match tag {
+        0x30 => {
+            let mut items = Vec::new();
+            let mut content = content;
+            while !content.is_empty() {
+                let (item, next) = parse_der(content)?;
+                items.push(item);
+                content = next;
+            }
+            Der::Sequence(items)
+        }
+        0x02 => Der::Integer(content),
+        0x04 => Der::OctetString(content),
+        0x06 => Der::ObjectId(content),
+        _ => Der::Other,
+    };
+
+    Ok((value, remaining))
+}
+
+/// Unlocks the login encryption key stored in an NSS `key4.db`.
+///
+/// An empty `primary_password` unlocks profiles without a primary password.
+pub fn unlock_key4(key4_path: &Path, primary_password: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let connection = Connection::open_with_flags(key4_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    let (global_salt, password_check): (Vec<u8>, Vec<u8>) = connection.query_row(
+        "SELECT item1, item2 FROM metaData WHERE id = 'password'",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    let check = decrypt_pbe(&password_check, &global_salt, primary_password.as_bytes())
+        .map_err(|_| anyhow!("Incorrect Firefox primary password"))?;
+    if !check.starts_with(PASSWORD_CHECK) {
+        return Err(anyhow!("Incorrect Firefox primary password"));
+    }
+
+    let mut statement = connection.prepare("SELECT a11, a102 FROM nssPrivate")?;
+    let rows = statement.query_map([], |row| {
+        Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Vec<u8>>(1)?))
+    })?;
+
+    for row in rows {
+        let (encrypted_key, key_id) = row?;
+        if key_id == LOGIN_KEY_ID {
+            return decrypt_pbe(&encrypted_key, &global_salt, primary_password.as_bytes());
+        }
+    }
+
+    Err(anyhow!("Login encryption key not found in key4.db"))
+}
+
+/// Decrypts a base64 `encryptedUsername`/`encryptedPassword` value from `logins.json`.
+pub fn decrypt_login_field(key: &[u8], encoded: &str) -> Result<String> {
+    let data = general_purpose::STANDARD.decode(encoded)?;
+    let der = Der::parse(&data)?;
+    let fields = der.sequence()?;
+    let [_key_id, algorithm, ciphertext] = fields else {
+        return Err(anyhow!("Unexpected encrypted login layout"));
+    };
+
+    let algorithm = algorithm.sequence()?;
+    let [cipher, iv] = algorithm else {
+        return Err(anyhow!("Unexpected encrypted login algorithm"));
+    };
+    let cipher = cipher.object_id()?;
+    let iv = iv.octets()?;
+    let ciphertext = ciphertext.octets()?;
+
+    let plaintext = if cipher == OID_DES_EDE3_CBC {
+        decrypt_3des_cbc(key.get(..24).ok_or_else(short_key)?, iv, ciphertext)?
+    } else if cipher == OID_AES256_CBC {
+        decrypt_aes256_cbc(key.get(..32).ok_or_else(short_key)?, iv, ciphertext)?
+    } else {
+        return Err(anyhow!("Unsupported login cipher"));
+    };
+
+    Ok(String::from_utf8(plaintext.to_vec())?)
+}
+
+fn short_key() -> anyhow::Error {
+    anyhow!("Login encryption key is too short")
+}
+
+fn decrypt_pbe(data: &[u8], global_salt: &[u8], password: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let der = Der::parse(data)?;
+    let fields = der.sequence()?;
+    let [algorithm, ciphertext] = fields else {
+        return Err(anyhow!("Unexpected NSS encrypted item layout"));
+    };
+    let algorithm = algorithm.sequence()?;
+    let [oid, params] = algorithm else {
+        return Err(anyhow!("Unexpected NSS algorithm layout"));
+    };
+    let oid = oid.object_id()?;
+    let ciphertext = ciphertext.octets()?;
+
+    let mut hasher = Sha1::new();
+    hasher.update(global_salt);
+    hasher.update(password);
+    let password_hash = Zeroizing::new(hasher.finalize().to_vec());
+
+    if oid == OID_PBES2 {
+        decrypt_pbes2(params.sequence()?, &password_hash, ciphertext)
+    } else if oid == OID_PBE_SHA1_3DES {
+        decrypt_pbe_sha1_3des(params.sequence()?, &password_hash, ciphertext)
+    } else {
+        Err(anyhow!("Unsupported NSS encryption scheme"))
+    }
+}
+
+fn decrypt_pbes2(
+    params: &[Der<'_>],
+    password_hash: &[u8],
+    ciphertext: &[u8],
+) -> Result<Zeroizing<Vec<u8>>> {
+    let [kdf, cipher] = params else {
+        return Err(anyhow!("Unexpected PBES2 parameters"));
+    };
+
+    let kdf = kdf.sequence()?;
+    let [kdf_oid, kdf_params] = kdf else {
+        return Err(anyhow!("Unexpected PBES2 key derivation"));
+    };
+    if kdf_oid.object_id()? != OID_PBKDF2 {
+        return Err(anyhow!("Unsupported PBES2 key derivation"));
+    }
+
+    let kdf_params = kdf_params.sequence()?;
+    let salt = kdf_params
+        .first()
+        .ok_or_else(|| anyhow!("Missing PBKDF2 salt"))?
+        .octets()?;
+    let iterations = kdf_params
+        .get(1)
+        .ok_or_else(|| anyhow!("Missing PBKDF2 iteration count"))?
+        .integer()?;
+    let key_length = match kdf_params.get(2) {
+        Some(value @ Der::Integer(_)) => value.integer()? as usize,
+        _ => 32,
+    };
+
+    let cipher = cipher.sequence()?;
+    let [cipher_oid, iv] = cipher else {
+        return Err(anyhow!("Unexpected PBES2 cipher"));
+    };
+    if cipher_oid.object_id()? != OID_AES256_CBC || key_length != 32 {
+        return Err(anyhow!("Unsupported PBES2 cipher"));
+    }
+
+    let mut key = Zeroizing::new(vec![0u8; key_length]);
+    pbkdf2::pbkdf2_hmac::<Sha256>(password_hash, salt, iterations, &mut key);
+
+    // NSS stores only the last 14 bytes of the IV; the DER header is implied
+    let iv = iv.octets()?;
+    let iv = if iv.len() == 14 {
+        [&[0x04, 0x0e], iv].concat()
+    } else {
+        iv.to_vec()
+    };
+
+    decrypt_aes256_cbc(&key, &iv, ciphertext)
+}
+
+fn decrypt_pbe_sha1_3des(
+    params: &[Der<'_>],
+    password_hash: &[u8],
+    ciphertext: &[u8],
+) -> Result<Zeroizing<Vec<u8>>> {
+    let entry_salt = params
+        .first()
+        .ok_or_else(|| anyhow!("Missing 3DES entry salt"))?
+        .octets()?;
+
+    let mut hasher = Sha1::new();
+    hasher.update(password_hash);
+    hasher.update(entry_salt);
+    let chp = Zeroizing::new(hasher.finalize().to_vec());
+
+    let mut padded_salt = entry_salt.to_vec();
+    padded_salt.resize(padded_salt.len().max(20), 0);
+
+    let k1 = hmac_sha1(&chp, &[&padded_salt, entry_salt])?;
+    let tk = hmac_sha1(&chp, &[&padded_salt])?;
+    let k2 = hmac_sha1(&chp, &[&tk, entry_salt])?;
+    let key = Zeroizing::new([k1.as_slice(), k2.as_slice()].concat());
+
+    decrypt_3des_cbc(&key[..24], &key[key.len() - 8..], ciphertext)
+}
+
+fn hmac_sha1(key: &[u8], parts: &[&[u8]]) -> Result<Zeroizing<Vec<u8>>> {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key)?;
+    for part in parts {
+        mac.update(part);
+    }
+    Ok(Zeroizing::new(mac.finalize().into_bytes().to_vec()))
+}
+
+fn decrypt_aes256_cbc(key: &[u8], iv: &[u8], ciphertext: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let plaintext = cbc::Decryptor::<Aes256>::new_from_slices(key, iv)
+        .map_err(|e| anyhow!("Invalid AES key or IV: {}", e))?
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+        .map_err(|_| anyhow!("AES decryption failed"))?;
+    Ok(Zeroizing::new(plaintext))
+}
+
+fn decrypt_3des_cbc(key: &[u8], iv: &[u8], ciphertext: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let plaintext = cbc::Decryptor::<TdesEde3>::new_from_slices(key, iv)
+        .map_err(|e| anyhow!("Invalid 3DES key or IV: {}", e))?
+        .decrypt_padded_vec_mut::<Pkcs7>(ciphertext)
+        .map_err(|_| anyhow!("3DES decryption failed"))?;
+    Ok(Zeroizing::new(plaintext))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use cbc::cipher::BlockEncryptMut;
+
+    fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut out = vec![tag];
+        if content.len() < 0x80 {
+            out.push(content.len() as u8);
+        } else {
+            out.push(0x82);
+            out.extend_from_slice(&(content.len() as u16).to_be_bytes());
+        }
+        out.extend_from_slice(content);
+        out
+    }
+
+    fn seq(items: &[Vec<u8>]) -> Vec<u8> {
+        der(0x30, &items.concat())
+    }
+
+    fn encrypt_pbes2(plaintext: &[u8], global_salt: &[u8], password: &[u8]) -> Vec<u8> {
+        let entry_salt = [9u8; 32];
+        let iv_tail = [3u8; 14];
+        let iterations = 10u16;
+
+        let mut hasher = Sha1::new();
+        hasher.update(global_salt);
+        hasher.update(password);
+        let password_hash = hasher.finalize();
+
+        let mut key = [0u8; 32];
+        pbkdf2::pbkdf2_hmac::<Sha256>(&password_hash, &entry_salt, u32::from(iterations), &mut key);
+        let iv = [&[0x04, 0x0e], &iv_tail[..]].concat();
+        let ciphertext = cbc::Encryptor::<Aes256>::new_from_slices(&key, &iv)
+            .unwrap()
+            .encrypt_padded_vec_mut::<Pkcs7>(plaintext);
+
+        seq(&[
+            seq(&[
+                der(0x06, OID_PBES2),
+                seq(&[
+                    seq(&[
+                        der(0x06, OID_PBKDF2),
+                        seq(&[
+                            der(0x04, &entry_salt),
+                            der(0x02, &iterations.to_be_bytes()),
+                            der(0x02, &[32]),
+                        ]),
+                    ]),
+                    seq(&[der(0x06, OID_AES256_CBC), der(0x04, &iv_tail)]),
+                ]),
+            ]),
+            der(0x04, &ciphertext),
+        ])
+    }
+
+    pub(crate) fn encrypt_login(key: &[u8], plaintext: &str, aes: bool) -> String {
+        let (oid, iv, ciphertext) = if aes {
+            let iv = [5u8; 16];
+            let ciphertext = cbc::Encryptor::<Aes256>::new_from_slices(&key[..32], &iv)
+                .unwrap()
+                .encrypt_padded_vec_mut::<Pkcs7>(plaintext.as_bytes());
+            (OID_AES256_CBC, iv.to_vec(), ciphertext)
+        } else {
+            let iv = [6u8; 8];
+            let ciphertext = cbc::Encryptor::<TdesEde3>::new_from_slices(&key[..24], &iv)
+                .unwrap()
+                .encrypt_padded_vec_mut::<Pkcs7>(plaintext.as_bytes());
+            (OID_DES_EDE3_CBC, iv.to_vec(), ciphertext)
+        };
+
+        general_purpose::STANDARD.encode(seq(&[
+            der(0x04, &LOGIN_KEY_ID),
+            seq(&[der(0x06, oid), der(0x04, &iv)]),
+            der(0x04, &ciphertext),
+        ]))
+    }
+
+    pub(crate) fn create_key4_db(path: &Path, login_key: &[u8], password: &[u8]) {
+        let global_salt = [1u8; 20];
+        let connection = Connection::open(path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE metaData (id PRIMARY KEY UNIQUE ON CONFLICT REPLACE, item1, item2);
+                 CREATE TABLE nssPrivate (id PRIMARY KEY UNIQUE ON CONFLICT ABORT, a11, a102);",
+            )
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO metaData (id, item1, item2) VALUES ('password', ?1, ?2)",
+                rusqlite::params![
+                    global_salt.to_vec(),
+                    encrypt_pbes2(b"password-check\x02\x02", &global_salt, password)
+                ],
+            )
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO nssPrivate (id, a11, a102) VALUES (1, ?1, ?2)",
+                rusqlite::params![
+                    encrypt_pbes2(login_key, &global_salt, password),
+                    LOGIN_KEY_ID.to_vec()
+                ],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_unlock_key4_and_decrypt_logins() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let key4_path = temp_dir.path().join("key4.db");
+        let login_key = [0x42u8; 32];
+        create_key4_db(&key4_path, &login_key, b"");
+
+        let key = unlock_key4(&key4_path, "").unwrap();
+        assert_eq!(key.as_slice(), &login_key);
+
+        let des_value = encrypt_login(&key, "alice", false);
+        let aes_value = encrypt_login(&key, "s3cr3t", true);
+        assert_eq!(decrypt_login_field(&key, &des_value).unwrap(), "alice");
+        assert_eq!(decrypt_login_field(&key, &aes_value).unwrap(), "s3cr3t");
+    }
+
+    #[test]
+    fn test_unlock_key4_with_primary_password() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let key4_path = temp_dir.path().join("key4.db");
+        let login_key = [0x17u8; 32];
+        create_key4_db(&key4_path, &login_key, b"hunter2");
+
+        assert!(unlock_key4(&key4_path, "").is_err());
+        assert!(unlock_key4(&key4_path, "wrong").is_err());
+        assert_eq!(
+            unlock_key4(&key4_path, "hunter2").unwrap().as_slice(),
+            &login_key
+        );
+    }
+
+    #[test]
+    fn test_decrypt_pbe_sha1_3des() {
+        let global_salt = [2u8; 20];
+        let entry_salt = [4u8; 20];
+
+        let mut hasher = Sha1::new();
+        hasher.update(global_salt);
+        let password_hash = hasher.finalize();
+        let mut hasher = Sha1::new();
+        hasher.update(password_hash);
+        hasher.update(entry_salt);
+        let chp = hasher.finalize();
+        let k1 = hmac_sha1(&chp, &[&entry_salt, &entry_salt]).unwrap();
+        let tk = hmac_sha1(&chp, &[&entry_salt]).unwrap();
+        let k2 = hmac_sha1(&chp, &[&tk, &entry_salt]).unwrap();
+        let key = [k1.as_slice(), k2.as_slice()].concat();
+
+        let ciphertext =
+            cbc::Encryptor::<TdesEde3>::new_from_slices(&key[..24], &key[key.len() - 8..])
+                .unwrap()
+                .encrypt_padded_vec_mut::<Pkcs7>(b"password-check");
+        let item = seq(&[
+            seq(&[
+                der(0x06, OID_PBE_SHA1_3DES),
+                seq(&[der(0x04, &entry_salt), der(0x02, &[1])]),
+            ]),
+            der(0x04, &ciphertext),
+        ]);
+
+        let plaintext = decrypt_pbe(&item, &global_salt, b"").unwrap();
+        assert_eq!(plaintext.as_slice(), b"password-check");
+    }
+}
@@ -0,0 +1,409 @@
+//! Segmented AES-256-GCM encryption (the STREAM construction) for archives, so neither the
+//! plaintext nor the ciphertext of a whole archive is ever held in memory.
+//!
+//! A file is [`HEADER_LEN`] bytes of magic and random nonce prefix, followed by the plaintext
+//! in [`SEGMENT_LEN`] segments, each sealed with its own tag. A segment's nonce is the prefix,
+//! its big-endian index, and a flag set only on the last segment, so segments cannot be
+//! reordered, dropped, or cut off at the end without failing authentication.
+
+use aead::AeadInPlace;
+use aes_gcm::{Aes256Gcm, KeyInit, Nonce};
+use anyhow::{anyhow, Result};
+use rand::RngCore;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use zeroize::Zeroizing;
+
+/// Starts every file in this format, which never begins like a legacy single-shot file does.
+pub const MAGIC: [u8; 8] = *b"BRFCSTM1";
+const PREFIX_LEN: usize = 7;
+pub const HEADER_LEN: usize = MAGIC.len() + PREFIX_LEN;
+pub const SEGMENT_LEN: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+const SEALED_LEN: usize = SEGMENT_LEN + TAG_LEN;
+
+/// Whether `header`, the first bytes of a file, starts a file in this format.
+pub fn is_stream(header: &[u8]) -> bool {
+    header.starts_with(&MAGIC)
+}
+
+/// Size of the encrypted file for `plaintext_len` bytes of plaintext.
+pub fn encrypted_len(plaintext_len: u64) -> u64 {
+    // Beyond the first segment, the last one holds whatever is left, possibly nothing
+    let segments = if plaintext_len <= SEGMENT_LEN as u64 {
+        1
+    } else {
+        plaintext_len / SEGMENT_LEN as u64 + 1
+    };
+    HEADER_LEN as u64 + plaintext_len + segments * TAG_LEN as u64
+}
+
+fn nonce(prefix: &[u8], index: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[..PREFIX_LEN].copy_from_slice(prefix);
+    nonce[PREFIX_LEN..11].copy_from_slice(&index.to_be_bytes());
+    nonce[11] = u8::from(last);
+    nonce
+}
+
+fn cipher(key: &[u8; 32]) -> Aes256Gcm {
+    Aes256Gcm::new(aes_gcm::Key::<Aes256Gcm>::from_slice(key))
+}
+
+fn unsupported(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, message)
+}
+
+/// Encrypts everything written to it into `inner`, one segment at a time.
+///
+/// Archive writers seek back to fill in their signature header once the archive is complete,
+/// so the first segment stays in memory until [`Encryptor::finish`] and may be rewritten;
+/// everything after it can only be appended.
+pub struct Encryptor<W: Write + Seek> {
+    inner: W,
+    cipher: Aes256Gcm,
+    header: [u8; HEADER_LEN],
+    first: Zeroizing<Vec<u8>>,
+    current: Zeroizing<Vec<u8>>,
+    /// Segments after the first one that are already written to `inner`.
+    written: u32,
+    position: u64,
+}
+
+impl<W: Write + Seek> Encryptor<W> {
+    pub fn new(mut inner: W, key: &[u8; 32]) -> Result<Self> {
+        let mut header = [0u8; HEADER_LEN];
+        header[..MAGIC.len()].copy_from_slice(&MAGIC);
+        rand::thread_rng().fill_bytes(&mut header[MAGIC.len()..]);
+        inner.write_all(&header)?;
+        // Room for the first segment, which is written last
+        inner.seek(SeekFrom::Start((HEADER_LEN + SEALED_LEN) as u64))?;
+
+        Ok(Self {
+            inner,
+            cipher: cipher(key),
+            header,
+            first: Zeroizing::new(Vec::with_capacity(SEALED_LEN)),
+            current: Zeroizing::new(Vec::with_capacity(SEALED_LEN)),
+            written: 0,
+            position: 0,
+        })
+    }
+
+    fn len(&self) -> u64 {
+        (self.first.len() + self.current.len()) as u64
+            + u64::from(self.written) * SEGMENT_LEN as u64
+    }
+
+    fn seal(&self, buffer: &mut Vec<u8>, index: u32, last: bool) -> io::Result<()> {
+        let nonce = nonce(&self.header[MAGIC.len()..], index, last);
+        self.cipher
+            .encrypt_in_place(Nonce::from_slice(&nonce), &self.header, buffer)
+            .map_err(|e| io::Error::other(format!("Encryption error: {}", e)))
+    }
+
+    /// Seals the last segment and the first one, returning the writer positioned at its end.
+    pub fn finish(mut self) -> Result<W> {
+        let mut first = std::mem::take(&mut self.first);
+        if self.written == 0 && self.current.is_empty() {
+            self.seal(&mut first, 0, true)?;
+            self.inner.seek(SeekFrom::Start(HEADER_LEN as u64))?;
+            self.inner.write_all(&first)?;
+        } else {
+            let mut last = std::mem::take(&mut self.current);
+            let index = self
+                .written
+                .checked_add(1)
+                .ok_or_else(|| anyhow!("Archive is too large to encrypt"))?;
+            self.seal(&mut last, index, true)?;
+            self.inner.write_all(&last)?;
+            self.seal(&mut first, 0, false)?;
+            self.inner.seek(SeekFrom::Start(HEADER_LEN as u64))?;
+            self.inner.write_all(&first)?;
+        }
+        self.inner.seek(SeekFrom::End(0))?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write + Seek> Write for Encryptor<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.len();
+        if self.position < SEGMENT_LEN as u64 {
+            // Inside the first segment, which is still plaintext in memory
+            let start = self.position as usize;
+            let count = buf.len().min(SEGMENT_LEN - start);
+            let end = start + count;
+            if end > self.first.len() {
+                self.first.resize(end, 0);
+            }
+            self.first[start..end].copy_from_slice(&buf[..count]);
+            self.position += count as u64;
+            return Ok(count);
+        }
+        if self.position != len {
+            return Err(unsupported(
+                "Encrypted archives can only be rewritten within their first segment",
+            ));
+        }
+
+        let count = buf.len().min(SEGMENT_LEN - self.current.len());
+        self.current.extend_from_slice(&buf[..count]);
+        self.position += count as u64;
+        if self.current.len() == SEGMENT_LEN {
+            let index = self
+                .written
+                .checked_add(1)
+                .ok_or_else(|| unsupported("Archive is too large to encrypt"))?;
+            let mut segment = std::mem::take(&mut self.current);
+            self.seal(&mut segment, index, false)?;
+            self.inner.write_all(&segment)?;
+            // Sealed in place, so the buffer now only holds ciphertext
+            segment.clear();
+            self.current = segment;
+            self.written = index;
+        }
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write + Seek> Seek for Encryptor<W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let len = self.len();
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid seek position"))?;
+
+        if target > len {
+            // Gaps are only possible while the archive still fits in the first segment
+            if target > SEGMENT_LEN as u64 {
+                return Err(unsupported(
+                    "Encrypted archives can only be extended by appending",
+                ));
+            }
+            self.first.resize(target as usize, 0);
+        }
+        self.position = target;
+        Ok(target)
+    }
+}
+
+/// Decrypts a file written by [`Encryptor`], authenticating each segment as it is read.
+///
+/// Seeking only moves between segments, so readers that jump around an archive (7z keeps its
+/// index at the end) never need more than one decrypted segment in memory.
+pub struct Decryptor<R: Read + Seek> {
+    inner: R,
+    cipher: Aes256Gcm,
+    header: [u8; HEADER_LEN],
+    segments: u64,
+    len: u64,
+    /// Index and plaintext of the segment last decrypted.
+    segment: Option<u64>,
+    buffer: Zeroizing<Vec<u8>>,
+    position: u64,
+}
+
+impl<R: Read + Seek> Decryptor<R> {
+    pub fn new(mut inner: R, key: &[u8; 32]) -> Result<Self> {
+        let file_len = inner.seek(SeekFrom::End(0))?;
+        inner.seek(SeekFrom::Start(0))?;
+        let mut header = [0u8; HEADER_LEN];
+        inner
+            .read_exact(&mut header)
+            .map_err(|_| anyhow!("Invalid encrypted data"))?;
+        if !is_stream(&header) {
+            return Err(anyhow!("Invalid encrypted data"));
+        }
+
+        let sealed = file_len - HEADER_LEN as u64;
+        let full = sealed / SEALED_LEN as u64;
+        let remainder = sealed % SEALED_LEN as u64;
+        let segments = match remainder {
+            0 if full > 0 => full,
+            remainder if remainder >= TAG_LEN as u64 => full + 1,
+            _ => return Err(anyhow!("Encrypted data is truncated")),
+        };
+        if segments > u64::from(u32::MAX) + 1 {
+            return Err(anyhow!("Invalid encrypted data"));
+        }
+
+        Ok(Self {
+            inner,
+            cipher: cipher(key),
+            header,
+            segments,
+            len: sealed - segments * TAG_LEN as u64,
+            segment: None,
+            buffer: Zeroizing::new(Vec::with_capacity(SEALED_LEN)),
+            position: 0,
+        })
+    }
+
+    /// Size of the plaintext.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn load(&mut self, index: u64) -> io::Result<()> {
+        if self.segment == Some(index) {
+            return Ok(());
+        }
+        self.segment = None;
+
+        let last = index + 1 == self.segments;
+        let sealed_len = if last {
+            (self.len - index * SEGMENT_LEN as u64) as usize + TAG_LEN
+        } else {
+            SEALED_LEN
+        };
+        self.inner.seek(SeekFrom::Start(
+            HEADER_LEN as u64 + index * SEALED_LEN as u64,
+        ))?;
+        self.buffer.resize(sealed_len, 0);
+        self.inner.read_exact(&mut self.buffer)?;
+
+        let nonce = nonce(&self.header[MAGIC.len()..], index as u32, last);
+        self.cipher
+            .decrypt_in_place(Nonce::from_slice(&nonce), &self.header, &mut *self.buffer)
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Decryption error: {}", e),
+                )
+            })?;
+        self.segment = Some(index);
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for Decryptor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        let index = self.position / SEGMENT_LEN as u64;
+        self.load(index)?;
+
+        let offset = (self.position % SEGMENT_LEN as u64) as usize;
+        let count = buf.len().min(self.buffer.len() - offset);
+        buf[..count].copy_from_slice(&self.buffer[offset..offset + count]);
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl<R: Read + Seek> Seek for Decryptor<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid seek position"))?;
+        self.position = target;
+        Ok(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const KEY: [u8; 32] = [9u8; 32];
+
+    fn encrypt(data: &[u8]) -> Vec<u8> {
+        let mut encryptor = Encryptor::new(Cursor::new(Vec::new()), &KEY).unwrap();
+        encryptor.write_all(data).unwrap();
+        encryptor.finish().unwrap().into_inner()
+    }
+
+    fn decrypt(encrypted: Vec<u8>) -> Result<Vec<u8>> {
+        let mut decryptor = Decryptor::new(Cursor::new(encrypted), &KEY)?;
+        let mut plaintext = Vec::new();
+        decryptor.read_to_end(&mut plaintext)?;
+        Ok(plaintext)
+    }
+
+    #[test]
+    fn test_round_trips_at_segment_boundaries() {
+        for len in [
+            0,
+            1,
+            SEGMENT_LEN - 1,
+            SEGMENT_LEN,
+            SEGMENT_LEN + 1,
+            3 * SEGMENT_LEN,
+            3 * SEGMENT_LEN + 17,
+        ] {
+            let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            let encrypted = encrypt(&data);
+            assert_eq!(encrypted.len() as u64, encrypted_len(len as u64), "{}", len);
+            assert!(is_stream(&encrypted));
+            assert_eq!(decrypt(encrypted).unwrap(), data, "{}", len);
+        }
+    }
+
+    #[test]
+    fn test_first_segment_can_be_rewritten_after_appending() {
+        let data: Vec<u8> = (0..2 * SEGMENT_LEN + 5).map(|i| (i % 13) as u8).collect();
+        let mut encryptor = Encryptor::new(Cursor::new(Vec::new()), &KEY).unwrap();
+        // Like an archive writer reserving its signature header and filling it in at the end
+        encryptor.seek(SeekFrom::Start(32)).unwrap();
+        encryptor.write_all(&data[32..]).unwrap();
+        assert!(encryptor
+            .seek(SeekFrom::Start(SEGMENT_LEN as u64 + 1))
+            .is_ok());
+        assert!(encryptor.write_all(b"x").is_err());
+        encryptor.seek(SeekFrom::Start(0)).unwrap();
+        encryptor.write_all(&data[..32]).unwrap();
+        encryptor.seek(SeekFrom::End(0)).unwrap();
+        let encrypted = encryptor.finish().unwrap().into_inner();
+
+        let mut decryptor = Decryptor::new(Cursor::new(encrypted), &KEY).unwrap();
+        assert_eq!(decryptor.len(), data.len() as u64);
+        decryptor
+            .seek(SeekFrom::Start(SEGMENT_LEN as u64 + 3))
+            .unwrap();
+        let mut tail = Vec::new();
+        decryptor.read_to_end(&mut tail).unwrap();
+        assert_eq!(tail, data[SEGMENT_LEN + 3..]);
+        decryptor.rewind().unwrap();
+        let mut all = Vec::new();
+        decryptor.read_to_end(&mut all).unwrap();
+        assert_eq!(all, data);
+    }
+
+    #[test]
+    fn test_tampering_truncation_and_wrong_key_fail() {
+        let data = vec![42u8; 2 * SEGMENT_LEN + 100];
+        let encrypted = encrypt(&data);
+
+        let mut tampered = encrypted.clone();
+        tampered[HEADER_LEN + SEALED_LEN + 10] ^= 1;
+        assert!(decrypt(tampered).is_err());
+
+        // Cut at a segment boundary, so the remaining last segment is not marked as last
+        let truncated = encrypted[..HEADER_LEN + 2 * SEALED_LEN].to_vec();
+        assert!(decrypt(truncated).is_err());
+
+        let mut header = encrypted.clone();
+        header[MAGIC.len()] ^= 1;
+        assert!(decrypt(header).is_err());
+
+        let mut decryptor = Decryptor::new(Cursor::new(encrypted), &[1u8; 32]).unwrap();
+        assert!(decryptor.read(&mut [0u8; 16]).is_err());
+    }
+}
@@ -2,6 +2,7 @@ pub mod backup;
 pub mod cli;
 pub mod config;
 pub mod crypto;
+pub mod lock;
 pub mod logging;
 pub mod models;
 pub mod scheduler;
@@ -0,0 +1,134 @@
+use anyhow::{anyhow, Result};
+use log::info;
+use nix::errno::Errno;
+use nix::fcntl::{flock, FlockArg};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// Lock file next to the data directory, so it is never synced with the archives.
+pub const LOCK_FILE: &str = "briefcase.lock";
+
+/// What to do when another process holds the lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    /// Block until the other process releases it.
+    Wait,
+    /// Return an error naming the other process.
+    FailFast,
+}
+
+/// Advisory lock held while a process writes the data directory or the config.
+///
+/// The lock belongs to the open file, so it is released when this is dropped or the process
+/// exits, however it ends. The file holds the PID of the current holder.
+#[derive(Debug)]
+pub struct StateLock {
+    _file: File,
+}
+
+/// Path of the lock file guarding `data_dir`.
+pub fn lock_path(data_dir: &Path) -> PathBuf {
+    data_dir.with_file_name(LOCK_FILE)
+}
+
+/// Takes the lock guarding `data_dir` and the config.
+pub async fn acquire(data_dir: &Path, mode: LockMode) -> Result<StateLock> {
+    let path = lock_path(data_dir);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(&path)?;
+
+    match flock(file.as_raw_fd(), FlockArg::LockExclusiveNonblock) {
+        Ok(()) => {}
+        Err(Errno::EWOULDBLOCK) => {
+            let holder = describe_holder(&path);
+            if mode == LockMode::FailFast {
+                return Err(anyhow!(
+                    "Another briefcase process{} is using {:?}; try again once it finishes",
+                    holder,
+                    data_dir
+                ));
+            }
+            info!("Waiting for briefcase process{} to finish", holder);
+            file = tokio::task::spawn_blocking(move || lock_blocking(&file).map(|()| file))
+                .await?
+                .map_err(|e| anyhow!("Failed to lock {:?}: {}", path, e))?;
+        }
+        Err(e) => {
+            return Err(anyhow!("Failed to lock {:?}: {}", path, e));
+        }
+    }
+
+    file.set_len(0)?;
+    write!(file, "{}", std::process::id())?;
+    file.sync_all()?;
+    Ok(StateLock { _file: file })
+}
+
+fn lock_blocking(file: &File) -> nix::Result<()> {
+    loop {
+        match flock(file.as_raw_fd(), FlockArg::LockExclusive) {
+            Err(Errno::EINTR) => continue,
+            result => return result,
+        }
+    }
+}
+
+/// ` (PID <pid>)` for the process recorded in the lock file, or nothing when it is unknown.
+fn describe_holder(path: &Path) -> String {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|content| content.trim().parse::<u32>().ok())
+        .map(|pid| format!(" (PID {})", pid))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_second_lock_fails_fast_with_holder_pid() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+
+        let lock = acquire(&data_dir, LockMode::FailFast).await.unwrap();
+        assert_eq!(
+            fs::read_to_string(temp_dir.path().join(LOCK_FILE)).unwrap(),
+            std::process::id().to_string()
+        );
+
+        let error = acquire(&data_dir, LockMode::FailFast).await.unwrap_err();
+        assert!(error
+            .to_string()
+            .contains(&format!("(PID {})", std::process::id())));
+
+        drop(lock);
+        acquire(&data_dir, LockMode::FailFast).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_waiting_lock_is_granted_on_release() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path().join("data");
+
+        let lock = acquire(&data_dir, LockMode::Wait).await.unwrap();
+        let waiter = {
+            let data_dir = data_dir.clone();
+            tokio::spawn(async move { acquire(&data_dir, LockMode::Wait).await })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        assert!(!waiter.is_finished());
+
+        drop(lock);
+        waiter.await.unwrap().unwrap();
+    }
+}
@@ -1,20 +1,77 @@
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Local, NaiveDateTime};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
+/// Timestamp in archive and snapshot names.
+pub const TIMESTAMP_FORMAT: &str = "%Y-%m-%d_%H-%M-%S";
+
+/// Suffix of incremental archive names, before the extension.
+const INCREMENTAL_SUFFIX: &str = "_inc";
+
+/// Longest host id, the limit of a DNS label.
+const MAX_HOST_ID_LEN: usize = 63;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BackupFile {
     pub path: PathBuf,
     pub datetime: DateTime<Local>,
     pub size: u64,
     pub source_type: SourceType,
+    /// Host the backup was taken on; `None` for backups named before host ids existed.
+    #[serde(default)]
+    pub host: Option<String>,
     pub hash: String,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum SourceType {
     Firefox,
     Folder,
+    Chromium,
+    Thunderbird,
+    Ssh,
+    Gpg,
+    Command,
+    Git,
+    Files,
+}
+
+impl SourceType {
+    pub const ALL: [SourceType; 9] = [
+        SourceType::Firefox,
+        SourceType::Folder,
+        SourceType::Chromium,
+        SourceType::Thunderbird,
+        SourceType::Ssh,
+        SourceType::Gpg,
+        SourceType::Command,
+        SourceType::Git,
+        SourceType::Files,
+    ];
+
+    /// Name used as the archive filename prefix and in log messages.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SourceType::Firefox => "Firefox",
+            SourceType::Folder => "Folder",
+            SourceType::Chromium => "Chromium",
+            SourceType::Thunderbird => "Thunderbird",
+            SourceType::Ssh => "Ssh",
+            SourceType::Gpg => "Gpg",
+            SourceType::Command => "Command",
+            SourceType::Git => "Git",
+            SourceType::Files => "Files",
+        }
+    }
+
+    /// Determines the source type from an archive filename such as `Firefox_<host>_<timestamp>.7z`.
+    pub fn from_filename(filename: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|source_type| {
+            filename
+                .strip_prefix(source_type.name())
+                .is_some_and(|rest| rest.starts_with('_'))
+        })
+    }
 }
 
 impl BackupFile {
@@ -24,19 +81,229 @@ impl BackupFile {
             datetime: Local::now(),
             size: 0,
             source_type,
+            host: None,
             hash: String::new(),
         }
     }
 
     pub fn filename(&self) -> String {
-        let source = match self.source_type {
-            SourceType::Firefox => "Firefox",
-            SourceType::Folder => "Folder",
-        };
         format!(
-            "{}_{}.7z",
-            source,
-            self.datetime.format("%Y-%m-%d_%H-%M-%S")
+            "{}.7z",
+            backup_stem(
+                self.source_type,
+                self.host.as_deref(),
+                &self.datetime.naive_local(),
+                false
+            )
         )
     }
 }
+
+/// The parts of an archive or snapshot name, `<Source>_<host>_<timestamp>[_inc].<extension>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupName {
+    pub source_type: SourceType,
+    /// `None` for names written before host ids existed, `<Source>_<timestamp>`.
+    pub host: Option<String>,
+    pub timestamp: NaiveDateTime,
+    pub incremental: bool,
+}
+
+impl BackupName {
+    /// Parses a backup file name, or returns `None` for anything briefcase did not name.
+    pub fn parse(filename: &str) -> Option<Self> {
+        // Neither source names, host ids, nor timestamps contain dots
+        let stem = filename.split('.').next()?;
+        let (stem, incremental) = match stem.strip_suffix(INCREMENTAL_SUFFIX) {
+            Some(stem) => (stem, true),
+            None => (stem, false),
+        };
+
+        let timestamp_start = stem.len().checked_sub("0000-00-00_00-00-00".len())?;
+        let timestamp =
+            NaiveDateTime::parse_from_str(stem.get(timestamp_start..)?, TIMESTAMP_FORMAT).ok()?;
+        let prefix = stem.get(..timestamp_start)?.strip_suffix('_')?;
+
+        SourceType::ALL.into_iter().find_map(|source_type| {
+            let host = match prefix.strip_prefix(source_type.name())? {
+                "" => None,
+                rest => Some(
+                    rest.strip_prefix('_')
+                        .filter(|host| is_valid_host_id(host))?
+                        .to_string(),
+                ),
+            };
+            Some(Self {
+                source_type,
+                host,
+                timestamp,
+                incremental,
+            })
+        })
+    }
+
+    /// Whether the backup belongs to `host`. Backups without a host predate host ids and were
+    /// taken on this machine, since other hosts' backups are only ever downloaded for restore.
+    pub fn is_from_host(&self, host: &str) -> bool {
+        match self.host.as_deref() {
+            Some(own) => own == host,
+            None => true,
+        }
+    }
+}
+
+/// Name of a backup without its extension, `<Source>_<host>_<timestamp>[_inc]`.
+pub fn backup_stem(
+    source_type: SourceType,
+    host: Option<&str>,
+    timestamp: &NaiveDateTime,
+    incremental: bool,
+) -> String {
+    let mut stem = source_type.name().to_string();
+    if let Some(host) = host {
+        stem.push('_');
+        stem.push_str(host);
+    }
+    stem.push('_');
+    stem.push_str(&timestamp.format(TIMESTAMP_FORMAT).to_string());
+    if incremental {
+        stem.push_str(INCREMENTAL_SUFFIX);
+    }
+    stem
+}
+
+/// Whether `host_id` can go into backup names and remote paths: lowercase letters, digits, and
+/// inner dashes, like a DNS label.
+pub fn is_valid_host_id(host_id: &str) -> bool {
+    !host_id.is_empty()
+        && host_id.len() <= MAX_HOST_ID_LEN
+        && !host_id.starts_with('-')
+        && !host_id.ends_with('-')
+        && host_id
+            .bytes()
+            .all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'-')
+}
+
+/// Host id derived from a hostname: its first label, lowercased, with other characters
+/// replaced by dashes.
+pub fn host_id_from_hostname(hostname: &str) -> String {
+    let label = hostname.split('.').next().unwrap_or_default();
+    let mut host_id: String = label
+        .chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            c @ ('a'..='z' | '0'..='9') => c,
+            _ => '-',
+        })
+        .collect();
+    host_id.truncate(MAX_HOST_ID_LEN);
+    let host_id = host_id.trim_matches('-');
+    if host_id.is_empty() {
+        "localhost".to_string()
+    } else {
+        host_id.to_string()
+    }
+}
+
+/// Host id of this machine, derived from its hostname.
+pub fn machine_host_id() -> String {
+    let hostname = nix::unistd::gethostname()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    host_id_from_hostname(&hostname)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timestamp(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, TIMESTAMP_FORMAT).unwrap()
+    }
+
+    #[test]
+    fn test_backup_name_round_trips() {
+        let at = timestamp("2026-04-22_10-00-00");
+        for (host, incremental, filename) in [
+            (
+                Some("laptop"),
+                false,
+                "Firefox_laptop_2026-04-22_10-00-00.7z",
+            ),
+            (
+                Some("work-pc-2"),
+                true,
+                "Firefox_work-pc-2_2026-04-22_10-00-00_inc.tar.zst",
+            ),
+            (None, false, "Firefox_2026-04-22_10-00-00.snapshot"),
+            (None, true, "Firefox_2026-04-22_10-00-00_inc.7z"),
+        ] {
+            let stem = backup_stem(SourceType::Firefox, host, &at, incremental);
+            assert!(filename.starts_with(&format!("{}.", stem)), "{}", filename);
+            assert_eq!(
+                BackupName::parse(filename),
+                Some(BackupName {
+                    source_type: SourceType::Firefox,
+                    host: host.map(str::to_string),
+                    timestamp: at,
+                    incremental,
+                }),
+                "{}",
+                filename
+            );
+        }
+    }
+
+    #[test]
+    fn test_backup_name_rejects_foreign_names() {
+        for filename in [
+            "Firefox.index",
+            "Firefox_laptop.7z",
+            "Firefox_Laptop_2026-04-22_10-00-00.7z",
+            "Firefox__2026-04-22_10-00-00.7z",
+            "Firefox_2026-13-22_10-00-00.7z",
+            "Unknown_laptop_2026-04-22_10-00-00.7z",
+            "2026-04-22_10-00-00.7z",
+        ] {
+            assert_eq!(BackupName::parse(filename), None, "{}", filename);
+        }
+    }
+
+    #[test]
+    fn test_backup_name_is_from_host() {
+        let own = BackupName::parse("Ssh_laptop_2026-04-22_10-00-00.7z").unwrap();
+        let other = BackupName::parse("Ssh_desktop_2026-04-22_10-00-00.7z").unwrap();
+        let legacy = BackupName::parse("Ssh_2026-04-22_10-00-00.7z").unwrap();
+        assert!(own.is_from_host("laptop"));
+        assert!(!other.is_from_host("laptop"));
+        assert!(legacy.is_from_host("laptop"));
+    }
+
+    #[test]
+    fn test_backup_file_filename_includes_host() {
+        let mut backup = BackupFile::new(PathBuf::from("/tmp/test.7z"), SourceType::Folder);
+        backup.datetime = chrono::DateTime::parse_from_rfc3339("2023-01-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Local);
+        backup.host = Some("laptop".to_string());
+        assert_eq!(
+            backup.filename(),
+            format!(
+                "Folder_laptop_{}.7z",
+                backup.datetime.format(TIMESTAMP_FORMAT)
+            )
+        );
+    }
+
+    #[test]
+    fn test_host_id_from_hostname() {
+        assert_eq!(
+            host_id_from_hostname("Work-Laptop.example.com"),
+            "work-laptop"
+        );
+        assert_eq!(host_id_from_hostname("jane_s mac"), "jane-s-mac");
+        assert_eq!(host_id_from_hostname("--"), "localhost");
+        assert_eq!(host_id_from_hostname(""), "localhost");
+        assert!(is_valid_host_id(&host_id_from_hostname(&"x".repeat(100))));
+        assert!(is_valid_host_id(&machine_host_id()));
+    }
+}
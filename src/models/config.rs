@@ -1,3 +1,4 @@
+use crate::models::backup_file::{self, SourceType};
 use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::collections::HashMap;
@@ -19,12 +20,65 @@ pub struct GeneralConfig {
     pub encryption_key: String, // Derived key for AES encryption/decryption
     pub max_retention: u32,
     pub text_editor: Option<String>,
+    #[serde(default)]
+    pub repository: RepositoryFormat,
+    /// How many sources are backed up at the same time.
+    #[serde(default = "default_max_concurrent_backups")]
+    pub max_concurrent_backups: u32,
+    /// Names this machine in backup names and remote paths; empty uses the hostname.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub host_id: String,
+    /// Hooks run before every source's backup, ahead of the source's own hooks.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre_backup: Vec<Hook>,
+    /// Hooks run after every source's backup, following the source's own hooks.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_backup: Vec<Hook>,
+}
+
+fn default_max_concurrent_backups() -> u32 {
+    2
+}
+
+impl GeneralConfig {
+    /// The configured host id, or one derived from the machine's hostname.
+    pub fn effective_host_id(&self) -> String {
+        if self.host_id.is_empty() {
+            backup_file::machine_host_id()
+        } else {
+            self.host_id.clone()
+        }
+    }
+}
+
+/// How backups are stored in the data directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RepositoryFormat {
+    /// One encrypted 7z archive per backup.
+    #[default]
+    Archives,
+    /// Deduplicated, encrypted content-defined chunks plus one encrypted manifest per snapshot.
+    Chunks,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SourceConfig {
     pub firefox: FirefoxSource,
     pub folder: FolderSource,
+    #[serde(default)]
+    pub chromium: ChromiumSource,
+    #[serde(default)]
+    pub thunderbird: ThunderbirdSource,
+    #[serde(default)]
+    pub ssh: SshSource,
+    #[serde(default)]
+    pub gpg: GpgSource,
+    #[serde(default)]
+    pub command: CommandSource,
+    #[serde(default)]
+    pub git: GitSource,
+    #[serde(default)]
+    pub files: FilesSource,
     #[serde(
         default,
         skip_serializing,
@@ -44,12 +98,24 @@ pub struct FirefoxSource {
     pub enabled: bool,
     pub dir: PathBuf,
     pub frequency: Frequency,
+    #[serde(default)]
+    pub export_logins: bool, // Decrypt saved logins into logins.csv
+    /// Stored in plain text, readable by anyone who can read this file. Prefer
+    /// `primary_password_file` or the `BRIEFCASE_FIREFOX_PRIMARY_PASSWORD` environment variable.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary_password: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary_password_file: Option<PathBuf>, // File whose first line is the primary password
     #[serde(
         default,
         serialize_with = "serialize_optional_local_datetime",
         deserialize_with = "deserialize_optional_local_datetime"
     )]
     pub last_backup: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_fingerprint: Option<String>,
+    #[serde(flatten)]
+    pub options: SourceOptions,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -63,9 +129,627 @@ pub struct FolderSource {
         deserialize_with = "deserialize_optional_local_datetime"
     )]
     pub last_backup: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_fingerprint: Option<String>,
+    #[serde(flatten)]
+    pub options: SourceOptions,
+}
+
+/// Chromium-family browser profile (Chrome, Chromium, Brave, Edge).
+///
+/// An empty `dir` backs up every profile found by auto-detection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChromiumSource {
+    pub enabled: bool,
+    pub dir: PathBuf,
+    pub frequency: Frequency,
+    #[serde(
+        default,
+        serialize_with = "serialize_optional_local_datetime",
+        deserialize_with = "deserialize_optional_local_datetime"
+    )]
+    pub last_backup: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_fingerprint: Option<String>,
+    #[serde(flatten)]
+    pub options: SourceOptions,
+}
+
+impl Default for ChromiumSource {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: PathBuf::new(),
+            frequency: Frequency::Daily,
+            last_backup: None,
+            last_fingerprint: None,
+            options: SourceOptions::default(),
+        }
+    }
+}
+
+/// Thunderbird profile; an empty `dir` uses the default profile from `profiles.ini`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ThunderbirdSource {
+    pub enabled: bool,
+    pub dir: PathBuf,
+    pub frequency: Frequency,
+    #[serde(
+        default,
+        serialize_with = "serialize_optional_local_datetime",
+        deserialize_with = "deserialize_optional_local_datetime"
+    )]
+    pub last_backup: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_fingerprint: Option<String>,
+    #[serde(flatten)]
+    pub options: SourceOptions,
 }
 
+impl Default for ThunderbirdSource {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: PathBuf::new(),
+            frequency: Frequency::Daily,
+            last_backup: None,
+            last_fingerprint: None,
+            options: SourceOptions::default(),
+        }
+    }
+}
+
+/// SSH keys and config; an empty `dir` means `~/.ssh`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SshSource {
+    pub enabled: bool,
+    pub dir: PathBuf,
+    pub frequency: Frequency,
+    #[serde(
+        default,
+        serialize_with = "serialize_optional_local_datetime",
+        deserialize_with = "deserialize_optional_local_datetime"
+    )]
+    pub last_backup: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_fingerprint: Option<String>,
+    #[serde(flatten)]
+    pub options: SourceOptions,
+}
+
+impl Default for SshSource {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: PathBuf::new(),
+            frequency: Frequency::Weekly,
+            last_backup: None,
+            last_fingerprint: None,
+            options: SourceOptions::default(),
+        }
+    }
+}
+
+/// GnuPG keyring; an empty `dir` means `$GNUPGHOME` or `~/.gnupg`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GpgSource {
+    pub enabled: bool,
+    pub dir: PathBuf,
+    pub frequency: Frequency,
+    #[serde(
+        default,
+        serialize_with = "serialize_optional_local_datetime",
+        deserialize_with = "deserialize_optional_local_datetime"
+    )]
+    pub last_backup: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_fingerprint: Option<String>,
+    #[serde(flatten)]
+    pub options: SourceOptions,
+}
+
+impl Default for GpgSource {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: PathBuf::new(),
+            frequency: Frequency::Weekly,
+            last_backup: None,
+            last_fingerprint: None,
+            options: SourceOptions::default(),
+        }
+    }
+}
+
+/// Output of CLI tools such as `crontab -l` or `bw export`, captured at backup time.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandSource {
+    pub enabled: bool,
+    pub frequency: Frequency,
+    #[serde(default = "default_command_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(
+        default,
+        serialize_with = "serialize_optional_local_datetime",
+        deserialize_with = "deserialize_optional_local_datetime"
+    )]
+    pub last_backup: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_fingerprint: Option<String>,
+    #[serde(default)]
+    pub commands: Vec<CommandOutput>,
+    #[serde(flatten)]
+    pub options: SourceOptions,
+}
+
+/// A shell command whose stdout is stored in the archive as `commands/<output>`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandOutput {
+    pub command: String,
+    pub output: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>, // Overrides the source-wide timeout
+}
+
+fn default_command_timeout_secs() -> u64 {
+    60
+}
+
+/// A shell command run before or after a backup, see [`crate::backup::hooks`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hook {
+    pub command: String,
+    #[serde(default = "default_command_timeout_secs")]
+    pub timeout_secs: u64,
+    #[serde(default, skip_serializing_if = "HookFailure::is_default")]
+    pub on_failure: HookFailure,
+}
+
+/// What a failing or timed-out hook does to its backup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HookFailure {
+    /// Fail the source's backup.
+    #[default]
+    Abort,
+    /// Log a warning and carry on.
+    Warn,
+}
+
+impl HookFailure {
+    pub fn is_default(&self) -> bool {
+        *self == HookFailure::default()
+    }
+}
+
+impl Default for CommandSource {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frequency: Frequency::Daily,
+            timeout_secs: default_command_timeout_secs(),
+            last_backup: None,
+            last_fingerprint: None,
+            commands: Vec::new(),
+            options: SourceOptions::default(),
+        }
+    }
+}
+
+/// Git repositories, each stored as a `git bundle --all` plus a patch of uncommitted changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GitSource {
+    pub enabled: bool,
+    pub frequency: Frequency,
+    #[serde(
+        default,
+        serialize_with = "serialize_optional_local_datetime",
+        deserialize_with = "deserialize_optional_local_datetime"
+    )]
+    pub last_backup: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_fingerprint: Option<String>,
+    #[serde(default)]
+    pub repos: Vec<PathBuf>,
+    #[serde(flatten)]
+    pub options: SourceOptions,
+}
+
+impl Default for GitSource {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frequency: Frequency::Daily,
+            last_backup: None,
+            last_fingerprint: None,
+            repos: Vec::new(),
+            options: SourceOptions::default(),
+        }
+    }
+}
+
+/// Individual files such as KeePass databases, copied once they stop changing.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilesSource {
+    pub enabled: bool,
+    pub frequency: Frequency,
+    #[serde(default = "default_stable_window_secs")]
+    pub stable_window_secs: u64, // Size and mtime must be unchanged for this long
+    #[serde(default = "default_stable_timeout_secs")]
+    pub stable_timeout_secs: u64,
+    #[serde(default, skip_serializing_if = "OpenDatabase::is_default")]
+    pub open_database: OpenDatabase,
+    #[serde(
+        default,
+        serialize_with = "serialize_optional_local_datetime",
+        deserialize_with = "deserialize_optional_local_datetime"
+    )]
+    pub last_backup: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_fingerprint: Option<String>,
+    #[serde(default)]
+    pub paths: Vec<PathBuf>,
+    #[serde(flatten)]
+    pub options: SourceOptions,
+}
+
+/// What a files source does with a database that KeePassXC has open.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OpenDatabase {
+    /// Log a warning and copy it once it is stable with the lock file in place.
+    #[default]
+    Warn,
+    /// Fail the source's backup.
+    Fail,
+}
+
+impl OpenDatabase {
+    pub fn is_default(&self) -> bool {
+        *self == OpenDatabase::default()
+    }
+}
+
+fn default_stable_window_secs() -> u64 {
+    2
+}
+
+fn default_stable_timeout_secs() -> u64 {
+    60
+}
+
+impl Default for FilesSource {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            frequency: Frequency::Daily,
+            stable_window_secs: default_stable_window_secs(),
+            stable_timeout_secs: default_stable_timeout_secs(),
+            open_database: OpenDatabase::default(),
+            last_backup: None,
+            last_fingerprint: None,
+            paths: Vec::new(),
+            options: SourceOptions::default(),
+        }
+    }
+}
+
+/// Settings every source accepts, flattened into its `[source.<name>]` table.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SourceOptions {
+    /// Largest amount of data the source may stage, such as `"500MiB"`; unset means no limit
+    /// except the built-in 32 MiB for Firefox.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_optional_size",
+        deserialize_with = "deserialize_optional_size"
+    )]
+    pub max_size: Option<u64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub max_size_warn_only: bool, // Log oversized backups instead of failing them
+    /// Archive only what changed since the previous backup of this source.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub incremental: bool,
+    /// Incremental backups taken before the next full one; unset means
+    /// [`DEFAULT_FULL_EVERY`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_every: Option<u32>,
+    /// Compression method for this source's archives.
+    #[serde(default, skip_serializing_if = "Compression::is_default")]
+    pub compression: Compression,
+    /// Method-specific level; unset means [`Compression::default_level`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression_level: Option<u32>,
+    /// Archive container for this source's backups.
+    #[serde(default, skip_serializing_if = "Container::is_default")]
+    pub container: Container,
+    /// Hooks run before the source is exported.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre_backup: Vec<Hook>,
+    /// Hooks run once the source's backup has finished or failed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_backup: Vec<Hook>,
+    /// Which backups of this source to keep; unset keeps the newest `general.max_retention`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention: Option<RetentionPolicy>,
+}
+
+/// Grandfather-father-son retention for one source.
+///
+/// `keep_last` keeps the newest backups, and each other `keep_*` rule keeps the newest backup
+/// of that many distinct hours, days, ISO weeks, months, or years. A backup kept by any rule
+/// survives; with no `keep_*` rule, all do. `max_age_days` and `max_total_size` then remove
+/// survivors that are too old or beyond the size budget. The newest backup is always kept.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionPolicy {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_last: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_hourly: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_daily: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_weekly: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_monthly: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_yearly: Option<u32>,
+    /// Backups taken more than this many days ago are removed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age_days: Option<u32>,
+    /// Size budget such as `"5GiB"`, filled from the newest backup; older ones that do not fit
+    /// are removed.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_optional_size",
+        deserialize_with = "deserialize_optional_size"
+    )]
+    pub max_total_size: Option<u64>,
+}
+
+impl RetentionPolicy {
+    /// Keeps the newest `count` backups, the policy `general.max_retention` stands for.
+    pub fn keep_last(count: u32) -> Self {
+        Self {
+            keep_last: Some(count),
+            ..Self::default()
+        }
+    }
+
+    /// Whether any `keep_*` rule is set, so backups no rule selects are removed.
+    pub fn has_keep_rules(&self) -> bool {
+        [
+            self.keep_last,
+            self.keep_hourly,
+            self.keep_daily,
+            self.keep_weekly,
+            self.keep_monthly,
+            self.keep_yearly,
+        ]
+        .iter()
+        .any(Option::is_some)
+    }
+}
+
+/// File format the archive entries are stored in, before encryption.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Container {
+    /// 7z archive, named `.7z`.
+    #[default]
+    SevenZ,
+    /// Zstandard-compressed tar stream, named `.tar.zst`, which any `tar` can unpack.
+    TarZst,
+}
+
+impl Container {
+    pub const ALL: [Container; 2] = [Container::SevenZ, Container::TarZst];
+
+    pub fn is_default(&self) -> bool {
+        *self == Container::default()
+    }
+
+    /// File name extension of archives in this container, without the leading dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Container::SevenZ => "7z",
+            Container::TarZst => "tar.zst",
+        }
+    }
+}
+
+/// Effective archive settings of a source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArchiveSettings {
+    pub container: Container,
+    pub compression: Compression,
+    pub level: u32,
+}
+
+impl Default for ArchiveSettings {
+    fn default() -> Self {
+        SourceOptions::default().archive_settings()
+    }
+}
+
+/// How archive contents are compressed. The method is recorded per block in the archive, so
+/// extraction never depends on the current setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Compression {
+    /// LZMA2, levels 0-9.
+    #[default]
+    Lzma2,
+    /// Zstandard, levels 1-22; much faster than LZMA2 at a slightly larger size. Only offered
+    /// through the TarZst container, as mainline 7-Zip has no Zstandard coder.
+    Zstd,
+    /// No compression, for data that is already compressed.
+    Store,
+}
+
+impl Compression {
+    pub fn is_default(&self) -> bool {
+        *self == Compression::default()
+    }
+
+    pub fn levels(self) -> std::ops::RangeInclusive<u32> {
+        match self {
+            Compression::Lzma2 => 0..=9,
+            Compression::Zstd => 1..=22,
+            Compression::Store => 0..=0,
+        }
+    }
+
+    pub fn default_level(self) -> u32 {
+        match self {
+            Compression::Lzma2 => 6,
+            Compression::Zstd => 3,
+            Compression::Store => 0,
+        }
+    }
+}
+
+impl SourceOptions {
+    /// The configured container and compression method, with the effective level.
+    pub fn archive_settings(&self) -> ArchiveSettings {
+        ArchiveSettings {
+            container: self.container,
+            compression: self.compression,
+            level: self
+                .compression_level
+                .unwrap_or(self.compression.default_level()),
+        }
+    }
+}
+
+/// Incremental backups between two full backups when `full_every` is not configured.
+pub const DEFAULT_FULL_EVERY: u32 = 6;
+
+/// Limit applied to Firefox when `max_size` is not configured.
+pub const DEFAULT_FIREFOX_MAX_SIZE: u64 = 32 * 1024 * 1024;
+
+impl SourceConfig {
+    pub fn options(&self, source_type: SourceType) -> &SourceOptions {
+        match source_type {
+            SourceType::Firefox => &self.firefox.options,
+            SourceType::Folder => &self.folder.options,
+            SourceType::Chromium => &self.chromium.options,
+            SourceType::Thunderbird => &self.thunderbird.options,
+            SourceType::Ssh => &self.ssh.options,
+            SourceType::Gpg => &self.gpg.options,
+            SourceType::Command => &self.command.options,
+            SourceType::Git => &self.git.options,
+            SourceType::Files => &self.files.options,
+        }
+    }
+
+    /// Configured size limit of a source, with the Firefox default applied.
+    pub fn max_size(&self, source_type: SourceType) -> Option<u64> {
+        let max_size = self.options(source_type).max_size;
+        match source_type {
+            SourceType::Firefox => max_size.or(Some(DEFAULT_FIREFOX_MAX_SIZE)),
+            _ => max_size,
+        }
+    }
+
+    /// Paths whose size is known before export, so the limit can be checked up front.
+    pub fn input_paths(&self, source_type: SourceType) -> Vec<PathBuf> {
+        match source_type {
+            SourceType::Folder => vec![self.folder.dir.clone()],
+            SourceType::Files => self.files.paths.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn is_enabled(&self, source_type: SourceType) -> bool {
+        match source_type {
+            SourceType::Firefox => self.firefox.enabled,
+            SourceType::Folder => self.folder.enabled,
+            SourceType::Chromium => self.chromium.enabled,
+            SourceType::Thunderbird => self.thunderbird.enabled,
+            SourceType::Ssh => self.ssh.enabled,
+            SourceType::Gpg => self.gpg.enabled,
+            SourceType::Command => self.command.enabled,
+            SourceType::Git => self.git.enabled,
+            SourceType::Files => self.files.enabled,
+        }
+    }
+
+    pub fn frequency(&self, source_type: SourceType) -> Frequency {
+        match source_type {
+            SourceType::Firefox => self.firefox.frequency,
+            SourceType::Folder => self.folder.frequency,
+            SourceType::Chromium => self.chromium.frequency,
+            SourceType::Thunderbird => self.thunderbird.frequency,
+            SourceType::Ssh => self.ssh.frequency,
+            SourceType::Gpg => self.gpg.frequency,
+            SourceType::Command => self.command.frequency,
+            SourceType::Git => self.git.frequency,
+            SourceType::Files => self.files.frequency,
+        }
+    }
+
+    /// Last backup of a source, falling back to the legacy shared `source.last_backup`.
+    pub fn last_backup(&self, source_type: SourceType) -> Option<DateTime<Local>> {
+        let last_backup = match source_type {
+            SourceType::Firefox => self.firefox.last_backup,
+            SourceType::Folder => self.folder.last_backup,
+            SourceType::Chromium => self.chromium.last_backup,
+            SourceType::Thunderbird => self.thunderbird.last_backup,
+            SourceType::Ssh => self.ssh.last_backup,
+            SourceType::Gpg => self.gpg.last_backup,
+            SourceType::Command => self.command.last_backup,
+            SourceType::Git => self.git.last_backup,
+            SourceType::Files => self.files.last_backup,
+        };
+        last_backup.or(self.last_backup)
+    }
+
+    pub fn set_last_backup(&mut self, source_type: SourceType, datetime: DateTime<Local>) {
+        match source_type {
+            SourceType::Firefox => self.firefox.last_backup = Some(datetime),
+            SourceType::Folder => self.folder.last_backup = Some(datetime),
+            SourceType::Chromium => self.chromium.last_backup = Some(datetime),
+            SourceType::Thunderbird => self.thunderbird.last_backup = Some(datetime),
+            SourceType::Ssh => self.ssh.last_backup = Some(datetime),
+            SourceType::Gpg => self.gpg.last_backup = Some(datetime),
+            SourceType::Command => self.command.last_backup = Some(datetime),
+            SourceType::Git => self.git.last_backup = Some(datetime),
+            SourceType::Files => self.files.last_backup = Some(datetime),
+        }
+    }
+
+    /// Content fingerprint of the data last archived for a source, stored in each source's
+    /// `last_fingerprint` field. A backup whose data has the same fingerprint is skipped.
+    pub fn last_fingerprint(&self, source_type: SourceType) -> Option<&str> {
+        match source_type {
+            SourceType::Firefox => self.firefox.last_fingerprint.as_deref(),
+            SourceType::Folder => self.folder.last_fingerprint.as_deref(),
+            SourceType::Chromium => self.chromium.last_fingerprint.as_deref(),
+            SourceType::Thunderbird => self.thunderbird.last_fingerprint.as_deref(),
+            SourceType::Ssh => self.ssh.last_fingerprint.as_deref(),
+            SourceType::Gpg => self.gpg.last_fingerprint.as_deref(),
+            SourceType::Command => self.command.last_fingerprint.as_deref(),
+            SourceType::Git => self.git.last_fingerprint.as_deref(),
+            SourceType::Files => self.files.last_fingerprint.as_deref(),
+        }
+    }
+
+    pub fn set_last_fingerprint(&mut self, source_type: SourceType, fingerprint: String) {
+        let last_fingerprint = match source_type {
+            SourceType::Firefox => &mut self.firefox.last_fingerprint,
+            SourceType::Folder => &mut self.folder.last_fingerprint,
+            SourceType::Chromium => &mut self.chromium.last_fingerprint,
+            SourceType::Thunderbird => &mut self.thunderbird.last_fingerprint,
+            SourceType::Ssh => &mut self.ssh.last_fingerprint,
+            SourceType::Gpg => &mut self.gpg.last_fingerprint,
+            SourceType::Command => &mut self.command.last_fingerprint,
+            SourceType::Git => &mut self.git.last_fingerprint,
+            SourceType::Files => &mut self.files.last_fingerprint,
+        };
+        *last_fingerprint = Some(fingerprint);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum Frequency {
     Hourly,
     Daily,
@@ -90,6 +774,17 @@ pub struct RemoteProvider {
     pub last_sync: Option<DateTime<Local>>,
 }
 
+impl Config {
+    /// The source's retention policy, falling back to keeping `general.max_retention` backups.
+    pub fn retention_policy(&self, source_type: SourceType) -> RetentionPolicy {
+        self.source
+            .options(source_type)
+            .retention
+            .clone()
+            .unwrap_or_else(|| RetentionPolicy::keep_last(self.general.max_retention))
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -99,20 +794,39 @@ impl Default for Config {
                 encryption_key: String::new(),
                 max_retention: 10,
                 text_editor: None,
+                repository: RepositoryFormat::default(),
+                max_concurrent_backups: default_max_concurrent_backups(),
+                host_id: String::new(),
+                pre_backup: Vec::new(),
+                post_backup: Vec::new(),
             },
             source: SourceConfig {
                 firefox: FirefoxSource {
                     enabled: false,
                     dir: PathBuf::from("/path/to/firefox/profile"),
                     frequency: Frequency::Daily,
+                    export_logins: false,
+                    primary_password: None,
+                    primary_password_file: None,
                     last_backup: None,
+                    last_fingerprint: None,
+                    options: SourceOptions::default(),
                 },
                 folder: FolderSource {
                     enabled: false,
                     dir: PathBuf::from("/path/to/sensitive/folder"),
                     frequency: Frequency::Daily,
                     last_backup: None,
+                    last_fingerprint: None,
+                    options: SourceOptions::default(),
                 },
+                chromium: ChromiumSource::default(),
+                thunderbird: ThunderbirdSource::default(),
+                ssh: SshSource::default(),
+                gpg: GpgSource::default(),
+                command: CommandSource::default(),
+                git: GitSource::default(),
+                files: FilesSource::default(),
                 last_backup: None,
                 last_sync: None,
             },
@@ -196,3 +910,71 @@ where
         .map(Some)
         .ok_or_else(|| serde::de::Error::custom("invalid local timestamp"))
 }
+
+const SIZE_UNITS: [(&str, u64); 7] = [
+    ("GiB", 1024 * 1024 * 1024),
+    ("MiB", 1024 * 1024),
+    ("KiB", 1024),
+    ("GB", 1000 * 1000 * 1000),
+    ("MB", 1000 * 1000),
+    ("KB", 1000),
+    ("B", 1),
+];
+
+/// Parses sizes such as `"500MiB"`, `"2 GB"`, or `"1048576"` into bytes.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let (number, multiplier) = SIZE_UNITS
+        .iter()
+        .find_map(|(unit, multiplier)| {
+            value
+                .strip_suffix(unit)
+                .map(|number| (number.trim_end(), *multiplier))
+        })
+        .unwrap_or((value, 1));
+
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid size: {:?}", value))
+}
+
+/// Formats a byte count with the largest binary unit that keeps it whole, e.g. `"32MiB"`.
+pub fn format_size(bytes: u64) -> String {
+    SIZE_UNITS[..3]
+        .iter()
+        .find(|(_, multiplier)| bytes > 0 && bytes % multiplier == 0)
+        .map(|(unit, multiplier)| format!("{}{}", bytes / multiplier, unit))
+        .unwrap_or_else(|| bytes.to_string())
+}
+
+fn serialize_optional_size<S>(value: &Option<u64>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match value {
+        Some(bytes) => serializer.serialize_some(&format_size(*bytes)),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_optional_size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Text(String),
+    }
+
+    match Option::<Size>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Size::Bytes(bytes)) => Ok(Some(bytes)),
+        Some(Size::Text(text)) => parse_size(&text)
+            .map(Some)
+            .map_err(serde::de::Error::custom),
+    }
+}
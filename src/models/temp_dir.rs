@@ -1,7 +1,11 @@
 use chrono::{DateTime, Local};
+use log::warn;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// Staging directories are named `briefcase_<creation time in ns>_<random>`.
+const STAGING_PREFIX: &str = "briefcase_";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TempDir {
@@ -11,16 +15,20 @@ pub struct TempDir {
 }
 
 impl TempDir {
+    /// Creates a 0700 staging directory, on tmpfs whenever one is available so staged
+    /// plaintext never reaches persistent storage.
     pub fn new(size_limit: u64) -> std::io::Result<Self> {
+        let root = staging_root();
         let mut rng = rand::thread_rng();
         let temp_path = loop {
-            let candidate = std::env::temp_dir().join(format!(
-                "briefcase_{}_{}",
+            let candidate = root.join(format!(
+                "{}{}_{}",
+                STAGING_PREFIX,
                 Local::now().timestamp_nanos_opt().unwrap_or_default(),
                 rng.gen::<u64>()
             ));
 
-            match std::fs::create_dir(&candidate) {
+            match create_private_dir(&candidate) {
                 Ok(()) => break candidate,
                 Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => continue,
                 Err(error) => return Err(error),
@@ -56,10 +64,135 @@ impl TempDir {
     }
 }
 
+/// Staging directories created more than `max_age` ago, left behind by a process that died
+/// before removing them.
+pub fn stale_staging_dirs(max_age: chrono::Duration) -> Vec<PathBuf> {
+    let roots: Vec<PathBuf> = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain([PathBuf::from("/dev/shm"), std::env::temp_dir()])
+        .collect();
+    stale_staging_dirs_in(&roots, Local::now() - max_age)
+}
+
+fn stale_staging_dirs_in(roots: &[PathBuf], cutoff: DateTime<Local>) -> Vec<PathBuf> {
+    let cutoff = cutoff.timestamp_nanos_opt().unwrap_or_default();
+    let mut stale = Vec::new();
+    for root in roots {
+        let Ok(entries) = std::fs::read_dir(root) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let created = name
+                .to_str()
+                .and_then(|name| name.strip_prefix(STAGING_PREFIX))
+                .and_then(|rest| rest.split_once('_'))
+                .and_then(|(nanos, _)| nanos.parse::<i64>().ok());
+            let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+            if is_dir && created.is_some_and(|created| created < cutoff) {
+                stale.push(entry.path());
+            }
+        }
+    }
+    stale.sort();
+    stale.dedup();
+    stale
+}
+
+/// Memory-backed locations tried before falling back to the system temp directory.
+fn staging_root() -> PathBuf {
+    let candidates = std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain([PathBuf::from("/dev/shm")]);
+    for candidate in candidates {
+        if is_tmpfs(&candidate) {
+            return candidate;
+        }
+    }
+
+    let fallback = std::env::temp_dir();
+    warn!(
+        "No tmpfs available for staging; using {:?}, which may be on persistent storage",
+        fallback
+    );
+    fallback
+}
+
+#[cfg(target_os = "linux")]
+fn is_tmpfs(path: &Path) -> bool {
+    use nix::sys::statfs::{statfs, TMPFS_MAGIC};
+
+    statfs(path).is_ok_and(|stat| stat.filesystem_type() == TMPFS_MAGIC)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_tmpfs(_path: &Path) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn create_private_dir(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    std::fs::DirBuilder::new().mode(0o700).create(path)
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(path: &Path) -> std::io::Result<()> {
+    std::fs::create_dir(path)
+}
+
 impl Drop for TempDir {
     fn drop(&mut self) {
-        if self.path.exists() {
+        if self.path.exists() && std::fs::remove_dir_all(&self.path).is_err() {
+            // Staged folders keep their modes; read-only directories block removal
+            make_dirs_writable(&self.path);
             let _ = std::fs::remove_dir_all(&self.path);
         }
     }
 }
+
+#[cfg(unix)]
+pub(crate) fn make_dirs_writable(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let _ = std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o700));
+    if let Ok(entries) = std::fs::read_dir(path) {
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                make_dirs_writable(&entry.path());
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn make_dirs_writable(_path: &Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stale_staging_dirs_are_found_by_creation_time() {
+        let root = tempfile::tempdir().unwrap();
+        let now = Local::now().timestamp_nanos_opt().unwrap();
+        let old = root
+            .path()
+            .join(format!("{}{}_1", STAGING_PREFIX, now - 1_000));
+        let new = root
+            .path()
+            .join(format!("{}{}_2", STAGING_PREFIX, now + 1_000));
+        for dir in [&old, &new, &root.path().join("briefcase_other")] {
+            std::fs::create_dir(dir).unwrap();
+        }
+        std::fs::write(root.path().join(format!("{}1_3", STAGING_PREFIX)), "").unwrap();
+
+        let cutoff = DateTime::from_timestamp_nanos(now).with_timezone(&Local);
+        assert_eq!(
+            stale_staging_dirs_in(&[root.path().to_path_buf()], cutoff),
+            vec![old]
+        );
+    }
+}
@@ -1,4 +1,6 @@
+use crate::backup::recovery;
 use crate::backup::service::BackupService;
+use crate::lock::{self, LockMode};
 use crate::models::backup_file::{BackupFile, SourceType};
 use crate::models::config::Config;
 use crate::scheduler::service::SchedulerService;
@@ -50,43 +52,47 @@ impl Daemon {
     }
 
     async fn check_and_run_backups(&self) {
-        let config: Config = self.config.lock().await.clone();
-        let legacy_last_backup = config.source.last_backup;
+        // Picks up edits made since the last check, such as newly enabled sources
+        let config = match self.reload_config().await {
+            Ok(config) => config,
+            Err(e) => {
+                error!("Failed to reload config, using the previous one: {}", e);
+                self.config.lock().await.clone()
+            }
+        };
 
         let force = self.force_backup;
 
-        if config.source.firefox.enabled {
-            let last_backup = config.source.firefox.last_backup.or(legacy_last_backup);
-            if force
-                || SchedulerService::is_backup_due(last_backup, config.source.firefox.frequency)
-            {
-                info!("Firefox backup is due, starting backup");
-                if let Err(e) = self.run_backup(SourceType::Firefox).await {
-                    error!("Firefox backup failed: {}", e);
-                }
-            } else {
-                info!("Firefox backup not due yet");
+        for source_type in SourceType::ALL {
+            if !config.source.is_enabled(source_type) {
+                continue;
             }
-        }
 
-        if config.source.folder.enabled {
-            let last_backup = config.source.folder.last_backup.or(legacy_last_backup);
-            if force || SchedulerService::is_backup_due(last_backup, config.source.folder.frequency)
+            let last_backup = config.source.last_backup(source_type);
+            if force
+                || SchedulerService::is_backup_due(
+                    last_backup,
+                    config.source.frequency(source_type),
+                )
             {
-                info!("Folder backup is due, starting backup");
-                if let Err(e) = self.run_backup(SourceType::Folder).await {
-                    error!("Folder backup failed: {}", e);
+                info!("{} backup is due, starting backup", source_type.name());
+                if let Err(e) = self.run_backup(source_type).await {
+                    error!("{} backup failed: {}", source_type.name(), e);
                 }
             } else {
-                info!("Folder backup not due yet");
+                info!("{} backup not due yet", source_type.name());
             }
         }
     }
 
     async fn run_backup(&self, source_type: SourceType) -> anyhow::Result<()> {
-        info!("Running scheduled backup for {}", source_name(source_type));
+        info!("Running scheduled backup for {}", source_type.name());
+        // Scheduled backups wait for a manual backup or sync to finish
+        let _lock = lock::acquire(&self.data_dir, LockMode::Wait).await?;
+        recovery::clean_up(&self.data_dir)?;
 
-        let config = self.config.lock().await;
+        // A manual run may have written the config while this one waited for the lock
+        let config = self.reload_config().await?;
         if config.general.encryption_key.is_empty() {
             return Err(anyhow::anyhow!(
                 "Config not initialized - no encryption key found for automated backup"
@@ -104,7 +110,6 @@ impl Daemon {
             .providers
             .values()
             .any(|remote| remote.enabled);
-        drop(config);
 
         let backup_files = self
             .backup_service
@@ -112,23 +117,33 @@ impl Daemon {
             .await?;
         info!("Created {} backup files", backup_files.len());
 
-        let config = self.config.lock().await.clone();
-        if let Err(e) = crate::config::save_current_config(&config) {
+        let backed_up = self.config.lock().await.source.clone();
+        let persisted = self
+            .persist(|config| {
+                if let Some(last_backup) = backed_up.last_backup(source_type) {
+                    config.source.set_last_backup(source_type, last_backup);
+                }
+                if let Some(fingerprint) = backed_up.last_fingerprint(source_type) {
+                    config
+                        .source
+                        .set_last_fingerprint(source_type, fingerprint.to_string());
+                }
+            })
+            .await;
+        if let Err(e) = persisted {
             error!(
                 "Failed to persist {} last_backup time: {}",
-                source_name(source_type),
+                source_type.name(),
                 e
             );
         }
 
-        if has_remotes {
+        // Unchanged sources produce no archive, so there is nothing new to sync
+        if has_remotes && !backup_files.is_empty() {
             self.run_sync(&backup_files).await?;
         }
 
-        info!(
-            "Scheduled backup completed for {}",
-            source_name(source_type)
-        );
+        info!("Scheduled backup completed for {}", source_type.name());
         Ok(())
     }
 
@@ -146,17 +161,17 @@ impl Daemon {
             let synced_remotes = service
                 .sync_backups(backup_files, &self.data_dir, false)
                 .await?;
-            let persisted_config = {
-                let mut config = self.config.lock().await;
-                let sync_time = Local::now();
-                for remote_key in synced_remotes {
-                    if let Some(remote) = config.remote.providers.get_mut(&remote_key) {
-                        remote.last_sync = Some(sync_time);
+            let sync_time = Local::now();
+            let persisted = self
+                .persist(|config| {
+                    for remote_key in synced_remotes {
+                        if let Some(remote) = config.remote.providers.get_mut(&remote_key) {
+                            remote.last_sync = Some(sync_time);
+                        }
                     }
-                }
-                config.clone()
-            };
-            if let Err(e) = crate::config::save_current_config(&persisted_config) {
+                })
+                .await;
+            if let Err(e) = persisted {
                 error!("Failed to persist remote last_sync times: {}", e);
             }
             info!("Automated sync completed");
@@ -164,12 +179,23 @@ impl Daemon {
 
         Ok(())
     }
-}
 
-fn source_name(source_type: SourceType) -> &'static str {
-    match source_type {
-        SourceType::Firefox => "firefox",
-        SourceType::Folder => "folder",
+    /// Replaces the daemon's config with the one on disk and returns it.
+    async fn reload_config(&self) -> anyhow::Result<Config> {
+        let on_disk = crate::config::load_config(&crate::config::get_config_path()?)?;
+        *self.config.lock().await = on_disk.clone();
+        Ok(on_disk)
+    }
+
+    /// Applies `update` to the config on disk and saves it, so fields the daemon did not change
+    /// keep what other briefcase commands wrote. Must be called while holding the lock.
+    async fn persist(&self, update: impl FnOnce(&mut Config)) -> anyhow::Result<()> {
+        let mut config = self.config.lock().await;
+        let mut on_disk = crate::config::load_config(&crate::config::get_config_path()?)?;
+        update(&mut on_disk);
+        crate::config::save_current_config(&on_disk)?;
+        *config = on_disk;
+        Ok(())
     }
 }
 
@@ -178,7 +204,7 @@ mod tests {
     use super::*;
     use crate::config;
     use base64::engine::general_purpose;
-    use chrono::{Duration as ChronoDuration, Local};
+    use chrono::{Duration as ChronoDuration, Local, Timelike};
     use rusqlite::Connection;
     use std::sync::OnceLock;
     use tokio::sync::Mutex;
@@ -245,7 +271,10 @@ mod tests {
         config.source.folder.enabled = true;
         config.source.folder.dir = folder_dir;
         config.source.folder.frequency = crate::models::config::Frequency::Daily;
-        let initial_folder_backup = Local::now() - ChronoDuration::hours(1);
+        // The config on disk keeps whole seconds
+        let initial_folder_backup = (Local::now() - ChronoDuration::hours(1))
+            .with_nanosecond(0)
+            .unwrap();
         config.source.folder.last_backup = Some(initial_folder_backup);
 
         let config_path = config::get_config_path().unwrap();
@@ -292,4 +321,138 @@ mod tests {
         assert_eq!(firefox_count, 1);
         assert_eq!(folder_count, 0);
     }
+
+    #[tokio::test]
+    async fn test_unchanged_source_only_bumps_last_backup() {
+        let _guard = env_lock().lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        configure_test_env(temp_dir.path());
+
+        let folder_dir = temp_dir.path().join("sensitive_data");
+        std::fs::create_dir_all(&folder_dir).unwrap();
+        std::fs::write(folder_dir.join("secret.txt"), "sensitive information").unwrap();
+
+        let mut config = Config::default();
+        config.general.encryption_key = general_purpose::STANDARD.encode([7u8; 32]);
+        config.source.folder.enabled = true;
+        config.source.folder.dir = folder_dir.clone();
+
+        let config_path = config::get_config_path().unwrap();
+        config::save_config(&config, &config_path).unwrap();
+
+        let data_dir = temp_dir
+            .path()
+            .join("data-home")
+            .join("briefcase")
+            .join("data");
+        let archive_count = || std::fs::read_dir(&data_dir).unwrap().count();
+
+        let daemon = Daemon::new(config, true);
+        daemon.check_and_run_backups().await;
+        let first = daemon.config.lock().await.source.folder.clone();
+        assert_eq!(archive_count(), 1);
+        assert!(first.last_fingerprint.is_some());
+
+        // Archive names have one-second resolution
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        filetime::set_file_mtime(
+            folder_dir.join("secret.txt"),
+            filetime::FileTime::from_unix_time(1_600_000_000, 0),
+        )
+        .unwrap();
+        daemon.check_and_run_backups().await;
+        let second = daemon.config.lock().await.source.folder.clone();
+        assert_eq!(archive_count(), 1);
+        assert!(second.last_backup.unwrap() > first.last_backup.unwrap());
+        assert_eq!(second.last_fingerprint, first.last_fingerprint);
+
+        std::fs::write(folder_dir.join("secret.txt"), "rotated information").unwrap();
+        daemon.check_and_run_backups().await;
+        let third = daemon.config.lock().await.source.folder.clone();
+        assert_eq!(archive_count(), 2);
+        assert_ne!(third.last_fingerprint, first.last_fingerprint);
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_backup_keeps_config_written_by_other_commands() {
+        let _guard = env_lock().lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        configure_test_env(temp_dir.path());
+
+        let folder_dir = temp_dir.path().join("sensitive_data");
+        std::fs::create_dir_all(&folder_dir).unwrap();
+        std::fs::write(folder_dir.join("secret.txt"), "sensitive information").unwrap();
+
+        let mut config = Config::default();
+        config.general.encryption_key = general_purpose::STANDARD.encode([7u8; 32]);
+        config.source.folder.enabled = true;
+        config.source.folder.dir = folder_dir;
+
+        let config_path = config::get_config_path().unwrap();
+        config::save_config(&config, &config_path).unwrap();
+        let daemon = Daemon::new(config.clone(), true);
+
+        // A manual backup and a config edit after the daemon started
+        let manual_backup = (Local::now() - ChronoDuration::minutes(5))
+            .with_nanosecond(0)
+            .unwrap();
+        config.source.ssh.last_backup = Some(manual_backup);
+        config.general.text_editor = Some("nano".to_string());
+        config::save_config(&config, &config_path).unwrap();
+
+        daemon.check_and_run_backups().await;
+
+        let saved = config::load_config(&config_path).unwrap();
+        assert_eq!(saved.source.ssh.last_backup, Some(manual_backup));
+        assert_eq!(saved.general.text_editor.as_deref(), Some("nano"));
+        assert!(saved.source.folder.last_backup.is_some());
+        assert!(saved.source.folder.last_fingerprint.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_backup_runs_hooks_around_source() {
+        use crate::models::config::Hook;
+
+        let _guard = env_lock().lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        configure_test_env(temp_dir.path());
+
+        let folder_dir = temp_dir.path().join("sensitive_data");
+        std::fs::create_dir_all(&folder_dir).unwrap();
+        std::fs::write(folder_dir.join("secret.txt"), "sensitive information").unwrap();
+        let notified = temp_dir.path().join("notified.txt");
+        let hook = |command: String| Hook {
+            command,
+            timeout_secs: 10,
+            on_failure: Default::default(),
+        };
+
+        let mut config = Config::default();
+        config.general.encryption_key = general_purpose::STANDARD.encode([7u8; 32]);
+        config.source.folder.enabled = true;
+        config.source.folder.dir = folder_dir;
+        config.source.folder.options.pre_backup = vec![hook(
+            "echo dump > \"$BRIEFCASE_TEMP_DIR/database.sql\"".to_string(),
+        )];
+        config.general.post_backup = vec![hook(format!(
+            "printf '%s %s' \"$BRIEFCASE_STATUS\" \"$BRIEFCASE_ARCHIVE\" > {:?}",
+            notified
+        ))];
+
+        let config_path = config::get_config_path().unwrap();
+        config::save_config(&config, &config_path).unwrap();
+
+        let daemon = Daemon::new(config, true);
+        daemon.check_and_run_backups().await;
+
+        let notification = std::fs::read_to_string(&notified).unwrap();
+        let archive = notification.strip_prefix("created ").unwrap();
+        let restored = temp_dir.path().join("restored");
+        crate::backup::incremental::restore(std::path::Path::new(archive), &restored, &[7u8; 32])
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(restored.join("database.sql")).unwrap(),
+            "dump\n"
+        );
+    }
 }
@@ -2,9 +2,12 @@ use anyhow::Result;
 use std::path::Path;
 use tokio::process::Command;
 
+/// Mirrors `local_folder` to `remote_path`, leaving out the `excluded` paths, which are relative
+/// to `local_folder`. Excluded files already on the remote are left in place.
 pub async fn sync_folder_to_remote(
     local_folder: &Path,
     remote_path: &str,
+    excluded: &[String],
     dry_run: bool,
 ) -> Result<()> {
     let source = local_folder.display().to_string();
@@ -15,6 +18,11 @@ pub async fn sync_folder_to_remote(
         cmd.arg("--dry-run");
     }
 
+    // Anchored at the root; backup names hold no glob characters
+    for path in excluded {
+        cmd.arg("--exclude").arg(format!("/{}", path));
+    }
+
     cmd.arg(&source).arg(remote_path);
 
     let output = cmd
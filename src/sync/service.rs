@@ -1,8 +1,10 @@
-use crate::models::backup_file::BackupFile;
+use crate::backup::{chunks, compress, pins};
+use crate::models::backup_file::{BackupFile, BackupName};
 use crate::models::config::Config;
 use crate::sync::rclone;
 use anyhow::Result;
 use log::{error, info};
+use std::fs;
 use std::path::Path;
 
 pub struct SyncService {
@@ -43,11 +45,9 @@ impl SyncService {
         remote_base: &str,
         dry_run: bool,
     ) -> Result<bool> {
-        let briefcase_dir = if remote_base.starts_with("sftp:") {
-            format!("{}/upload/briefcase", remote_base.trim_end_matches('/'))
-        } else {
-            format!("{}/briefcase", remote_base.trim_end_matches('/'))
-        };
+        let host = self.config.general.effective_host_id();
+        let briefcase_dir = briefcase_dir(remote_base, &host);
+        let excluded = excluded_from_sync(data_dir, &host)?;
 
         if dry_run {
             info!(
@@ -67,7 +67,7 @@ impl SyncService {
 
         info!("Syncing folder {} to {}", data_dir.display(), briefcase_dir);
 
-        match rclone::sync_folder_to_remote(data_dir, &briefcase_dir, dry_run).await {
+        match rclone::sync_folder_to_remote(data_dir, &briefcase_dir, &excluded, dry_run).await {
             Ok(_) => {
                 info!("Successfully synced folder {}", data_dir.display());
                 return Ok(!dry_run);
@@ -78,6 +78,24 @@ impl SyncService {
         Ok(false)
     }
 
+    /// Enabled remotes with the directory a sync writes to on each, sorted by remote key.
+    pub fn destinations(&self) -> Vec<(String, String)> {
+        let host = self.config.general.effective_host_id();
+        let mut destinations: Vec<_> = self
+            .config
+            .remote
+            .providers
+            .iter()
+            .filter(|(_, provider)| provider.enabled)
+            .map(|(key, provider)| {
+                let remote_base = format!("{}:", provider.name);
+                (key.clone(), briefcase_dir(&remote_base, &host))
+            })
+            .collect();
+        destinations.sort();
+        destinations
+    }
+
     pub async fn validate_remotes(&self) -> Result<()> {
         for remote_provider in self.config.remote.providers.values() {
             if remote_provider.enabled {
@@ -94,3 +112,109 @@ impl SyncService {
         Ok(())
     }
 }
+
+/// Directory under a remote that mirrors the data directory of `host`. Each host syncs to its
+/// own directory, so hosts sharing a remote never delete each other's archives.
+fn briefcase_dir(remote_base: &str, host: &str) -> String {
+    if remote_base.starts_with("sftp:") {
+        format!(
+            "{}/upload/briefcase/{}",
+            remote_base.trim_end_matches('/'),
+            host
+        )
+    } else {
+        format!("{}/briefcase/{}", remote_base.trim_end_matches('/'), host)
+    }
+}
+
+/// Paths, relative to the data directory, that a sync leaves alone on the remote.
+///
+/// Archives and snapshots other hosts took, and their pins, are there to be restored and are
+/// kept out of this host's remote directory. Pinned backups missing locally are excluded so the
+/// sync does not delete the remote copy.
+fn excluded_from_sync(data_dir: &Path, host: &str) -> Result<Vec<String>> {
+    let mut excluded = Vec::new();
+    let snapshots_dir = chunks::snapshots_dir(data_dir);
+    for dir in [data_dir, snapshots_dir.as_path()] {
+        if !dir.exists() {
+            continue;
+        }
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let pinned = pins::pinned_backup(&path);
+            let backup = pinned.as_deref().unwrap_or(&path);
+            let name = backup.file_name().unwrap_or_default().to_string_lossy();
+            let is_backup = compress::is_archive(&name) || chunks::is_snapshot(backup);
+            if !is_backup {
+                continue;
+            }
+
+            let is_other_host =
+                BackupName::parse(&name).is_some_and(|name| !name.is_from_host(host));
+            let mut paths = Vec::new();
+            if is_other_host {
+                paths.push(path.as_path());
+            }
+            if pinned.is_some() && !backup.exists() {
+                paths.push(backup);
+            }
+            for path in paths {
+                if let Ok(relative) = path.strip_prefix(data_dir) {
+                    excluded.push(relative.to_string_lossy().into_owned());
+                }
+            }
+        }
+    }
+    excluded.sort();
+    excluded.dedup();
+    Ok(excluded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_briefcase_dir_is_per_host() {
+        assert_eq!(
+            briefcase_dir("gdrive:", "laptop"),
+            "gdrive:/briefcase/laptop"
+        );
+        assert_eq!(
+            briefcase_dir("sftp:", "laptop"),
+            "sftp:/upload/briefcase/laptop"
+        );
+    }
+
+    #[test]
+    fn test_other_hosts_and_missing_pinned_backups_are_excluded() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let data_dir = temp_dir.path();
+        let snapshots_dir = chunks::snapshots_dir(data_dir);
+        fs::create_dir_all(&snapshots_dir).unwrap();
+        for path in [
+            data_dir.join("Firefox_2026-04-22_10-00-00.7z"),
+            data_dir.join("Firefox_laptop_2026-04-22_11-00-00.7z"),
+            data_dir.join("Firefox_desktop_2026-04-22_12-00-00.tar.zst"),
+            data_dir.join("Firefox.index"),
+            snapshots_dir.join("Folder_laptop_2026-04-22_11-00-00.snapshot"),
+            snapshots_dir.join("Folder_desktop_2026-04-22_12-00-00.snapshot"),
+            data_dir.join("Firefox_desktop_2026-04-22_12-00-00.tar.zst.pin"),
+            data_dir.join("Firefox_laptop_2026-04-22_11-00-00.7z.pin"),
+            // Pinned, then removed locally; the remote copy must survive
+            data_dir.join("Git_laptop_2026-04-20_11-00-00.7z.pin"),
+        ] {
+            fs::write(path, b"backup").unwrap();
+        }
+
+        assert_eq!(
+            excluded_from_sync(data_dir, "laptop").unwrap(),
+            vec![
+                "Firefox_desktop_2026-04-22_12-00-00.tar.zst".to_string(),
+                "Firefox_desktop_2026-04-22_12-00-00.tar.zst.pin".to_string(),
+                "Git_laptop_2026-04-20_11-00-00.7z".to_string(),
+                "repository/snapshots/Folder_desktop_2026-04-22_12-00-00.snapshot".to_string(),
+            ]
+        );
+    }
+}
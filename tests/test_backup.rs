@@ -1,5 +1,6 @@
-use briefcase::backup::service::BackupService;
-use briefcase::models::config::Config;
+use briefcase::backup::service::{BackupService, SourceOutcome};
+use briefcase::models::backup_file::SourceType;
+use briefcase::models::config::{CommandOutput, Config, Hook, HookFailure};
 use rusqlite::Connection;
 use std::sync::Arc;
 use tempfile::tempdir;
@@ -56,7 +57,7 @@ mod tests {
         let result = service.perform_backup("testpassword").await;
         assert!(result.is_ok());
 
-        let files = result.unwrap();
+        let files = result.unwrap().into_backup_files();
         assert_eq!(files.len(), 1);
         assert!(files[0].path.exists());
         assert!(files[0].filename().starts_with("Firefox_"));
@@ -69,6 +70,7 @@ mod tests {
         std::fs::create_dir(&backup_dir).unwrap();
 
         let mut config = Config::default();
+        config.general.host_id = "work-laptop".to_string();
         config.source.folder.enabled = true;
         config.source.folder.dir = temp_dir.path().join("sensitive_data");
         std::fs::create_dir(&config.source.folder.dir).unwrap();
@@ -81,10 +83,14 @@ mod tests {
         let result = service.perform_backup("testpassword").await;
         assert!(result.is_ok());
 
-        let files = result.unwrap();
+        let files = result.unwrap().into_backup_files();
         assert_eq!(files.len(), 1);
         assert!(files[0].path.exists());
-        assert!(files[0].filename().starts_with("Folder_"));
+        assert!(files[0].filename().starts_with("Folder_work-laptop_"));
+        assert_eq!(
+            files[0].path.file_name().unwrap().to_str(),
+            Some(files[0].filename().as_str())
+        );
     }
 
     #[tokio::test]
@@ -147,4 +153,215 @@ mod tests {
         assert_eq!(firefox_count, 2);
         assert_eq!(folder_count, 2);
     }
+
+    #[tokio::test]
+    async fn test_failing_source_does_not_stop_other_sources() {
+        let temp_dir = tempdir().unwrap();
+        let backup_dir = temp_dir.path().join("backups");
+        std::fs::create_dir(&backup_dir).unwrap();
+
+        let mut config = Config::default();
+        config.source.firefox.enabled = true;
+        config.source.firefox.dir = temp_dir.path().join("missing_profile");
+        config.source.folder.enabled = true;
+        config.source.folder.dir = temp_dir.path().join("sensitive_data");
+        std::fs::create_dir(&config.source.folder.dir).unwrap();
+        std::fs::write(config.source.folder.dir.join("secret.txt"), "sensitive").unwrap();
+
+        let config = Arc::new(Mutex::new(config));
+        let service = BackupService::new(config.clone(), backup_dir.clone());
+        let report = service.perform_backup("testpassword").await.unwrap();
+
+        assert_eq!(report.sources.len(), 2);
+        assert_eq!(report.sources[0].source_type, SourceType::Firefox);
+        assert!(matches!(
+            report.sources[0].outcome,
+            SourceOutcome::Failed(_)
+        ));
+        assert_eq!(report.sources[1].source_type, SourceType::Folder);
+        assert!(matches!(
+            report.sources[1].outcome,
+            SourceOutcome::Created(_)
+        ));
+        assert_eq!(report.failures().count(), 1);
+
+        let config = config.lock().await;
+        assert!(config.source.firefox.last_backup.is_none());
+        assert!(config.source.folder.last_backup.is_some());
+
+        // Unchanged data is reported as skipped on the next run
+        drop(config);
+        let report = service.perform_backup("testpassword").await.unwrap();
+        assert!(matches!(report.sources[1].outcome, SourceOutcome::Skipped));
+    }
+
+    #[tokio::test]
+    async fn test_post_backup_hooks_run_when_a_pre_backup_hook_aborts() {
+        let temp_dir = tempdir().unwrap();
+        let backup_dir = temp_dir.path().join("backups");
+        std::fs::create_dir(&backup_dir).unwrap();
+        let notified = temp_dir.path().join("notified.txt");
+
+        let mut config = Config::default();
+        config.source.folder.enabled = true;
+        config.source.folder.dir = temp_dir.path().join("sensitive_data");
+        std::fs::create_dir(&config.source.folder.dir).unwrap();
+        std::fs::write(config.source.folder.dir.join("secret.txt"), "sensitive").unwrap();
+        config.source.folder.options.pre_backup = vec![Hook {
+            command: "exit 1".to_string(),
+            timeout_secs: 10,
+            on_failure: HookFailure::Abort,
+        }];
+        config.source.folder.options.post_backup = vec![Hook {
+            command: format!("printf '%s' \"$BRIEFCASE_STATUS\" > {:?}", notified),
+            timeout_secs: 10,
+            on_failure: HookFailure::Abort,
+        }];
+
+        let service = BackupService::new(Arc::new(Mutex::new(config)), backup_dir.clone());
+        let report = service.perform_backup("testpassword").await.unwrap();
+        assert!(matches!(
+            report.sources[0].outcome,
+            SourceOutcome::Failed(_)
+        ));
+        assert_eq!(std::fs::read_to_string(&notified).unwrap(), "failed");
+    }
+
+    #[tokio::test]
+    async fn test_sources_are_backed_up_concurrently_up_to_the_limit() {
+        let temp_dir = tempdir().unwrap();
+        let backup_dir = temp_dir.path().join("backups");
+        std::fs::create_dir(&backup_dir).unwrap();
+
+        // Each source marks that it started and waits up to two seconds for the other
+        let rendezvous = |own: &str, other: &str| {
+            format!(
+                "touch '{dir}/{own}'; for i in $(seq 20); do [ -e '{dir}/{other}' ] && exit 0; sleep 0.1; done; exit 1",
+                dir = temp_dir.path().display()
+            )
+        };
+
+        let mut config = Config::default();
+        config.source.folder.enabled = true;
+        config.source.folder.dir = temp_dir.path().join("sensitive_data");
+        std::fs::create_dir(&config.source.folder.dir).unwrap();
+        std::fs::write(config.source.folder.dir.join("secret.txt"), "sensitive").unwrap();
+        config.source.folder.options.pre_backup = vec![Hook {
+            command: rendezvous("folder", "command"),
+            timeout_secs: 10,
+            on_failure: HookFailure::Abort,
+        }];
+        config.source.command.enabled = true;
+        config.source.command.commands = vec![CommandOutput {
+            command: format!("{} && echo done", rendezvous("command", "folder")),
+            output: "done.txt".to_string(),
+            timeout_secs: None,
+        }];
+
+        config.general.max_concurrent_backups = 2;
+        let service = BackupService::new(Arc::new(Mutex::new(config.clone())), backup_dir.clone());
+        let report = service.perform_backup("testpassword").await.unwrap();
+        assert_eq!(report.failures().count(), 0);
+        assert_eq!(report.backup_files().len(), 2);
+
+        // One at a time, the first source gives up waiting for the second
+        for marker in ["folder", "command"] {
+            std::fs::remove_file(temp_dir.path().join(marker)).unwrap();
+        }
+        config.general.max_concurrent_backups = 1;
+        let service = BackupService::new(Arc::new(Mutex::new(config)), backup_dir.clone());
+        let report = service.perform_backup("testpassword").await.unwrap();
+        assert_eq!(report.sources[0].source_type, SourceType::Folder);
+        assert!(matches!(
+            report.sources[0].outcome,
+            SourceOutcome::Failed(_)
+        ));
+        assert!(matches!(
+            report.sources[1].outcome,
+            SourceOutcome::Created(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_preview_matches_the_backup_it_describes() {
+        let temp_dir = tempdir().unwrap();
+        let backup_dir = temp_dir.path().join("backups");
+        std::fs::create_dir(&backup_dir).unwrap();
+        for file_name in [
+            "Folder_2026-04-22_08-00-00.7z",
+            "Folder_2026-04-22_09-00-00.7z",
+        ] {
+            std::fs::write(backup_dir.join(file_name), b"old backup").unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+
+        let mut config = Config::default();
+        config.general.max_retention = 2;
+        config.source.folder.enabled = true;
+        config.source.folder.dir = temp_dir.path().join("sensitive_data");
+        config.source.folder.options.max_size = Some(1024 * 1024);
+        std::fs::create_dir_all(config.source.folder.dir.join("nested")).unwrap();
+        std::fs::write(config.source.folder.dir.join("secret.txt"), "sensitive").unwrap();
+        std::fs::write(config.source.folder.dir.join("nested/more.txt"), "more").unwrap();
+
+        let service = BackupService::new(Arc::new(Mutex::new(config)), backup_dir.clone());
+        let previews = service.preview_backup_with_key(&[7u8; 32]).await;
+        assert_eq!(previews.len(), 1);
+        let (source_type, preview) = &previews[0];
+        assert_eq!(*source_type, SourceType::Folder);
+        let preview = preview.as_ref().unwrap();
+        assert_eq!(preview.inputs, Some((2, 13)));
+        assert_eq!(preview.max_size, Some(1024 * 1024));
+        assert!(preview.actions.is_empty());
+        assert!(!preview.incremental);
+        assert_eq!(
+            preview.estimated_size,
+            Some(briefcase::crypto::stream::encrypted_len(13))
+        );
+        assert_eq!(
+            preview.expired,
+            vec![backup_dir.join("Folder_2026-04-22_08-00-00.7z")]
+        );
+        // Nothing was written or deleted
+        assert_eq!(std::fs::read_dir(&backup_dir).unwrap().count(), 2);
+
+        service.perform_backup_with_key(&[7u8; 32]).await.unwrap();
+        assert!(!backup_dir.join("Folder_2026-04-22_08-00-00.7z").exists());
+    }
+
+    #[tokio::test]
+    async fn test_preview_lists_commands_without_running_them() {
+        let temp_dir = tempdir().unwrap();
+        let backup_dir = temp_dir.path().join("backups");
+        std::fs::create_dir(&backup_dir).unwrap();
+        let marker = temp_dir.path().join("ran");
+
+        let mut config = Config::default();
+        config.source.command.enabled = true;
+        config.source.command.commands = vec![CommandOutput {
+            command: format!("touch {}", marker.display()),
+            output: "touched.txt".to_string(),
+            timeout_secs: None,
+        }];
+        config.source.git.enabled = true;
+        config.source.git.repos = vec![temp_dir.path().join("repo")];
+
+        let service = BackupService::new(Arc::new(Mutex::new(config)), backup_dir.clone());
+        let previews = service.preview_backup_with_key(&[7u8; 32]).await;
+        assert_eq!(previews.len(), 2);
+        for (source_type, preview) in &previews {
+            let preview = preview.as_ref().unwrap();
+            assert_eq!(preview.inputs, None);
+            assert_eq!(preview.estimated_size, None);
+            let expected = match source_type {
+                SourceType::Command => {
+                    format!("would run `touch {}` into touched.txt", marker.display())
+                }
+                _ => format!("would bundle {:?}", temp_dir.path().join("repo")),
+            };
+            assert_eq!(preview.actions, vec![expected]);
+        }
+        assert!(!marker.exists());
+        assert_eq!(std::fs::read_dir(&backup_dir).unwrap().count(), 0);
+    }
 }
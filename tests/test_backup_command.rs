@@ -21,13 +21,19 @@ mod tests {
 
     #[test]
     fn test_backup_args_dry_run() {
-        let args = BackupArgs { dry_run: true };
+        let args = BackupArgs {
+            dry_run: true,
+            wait: false,
+        };
         assert!(args.dry_run);
     }
 
     #[test]
     fn test_backup_args_default() {
-        let args = BackupArgs { dry_run: false };
+        let args = BackupArgs {
+            dry_run: false,
+            wait: false,
+        };
         assert!(!args.dry_run);
     }
 
@@ -56,12 +62,114 @@ mod tests {
         let config_path = config::get_config_path().unwrap();
         config::save_config(&config, &config_path).unwrap();
 
-        briefcase::cli::backup::run(BackupArgs { dry_run: false })
-            .await
-            .unwrap();
+        briefcase::cli::backup::run(BackupArgs {
+            dry_run: false,
+            wait: false,
+        })
+        .await
+        .unwrap();
 
         let updated = config::load_config(&config_path).unwrap();
         assert!(updated.source.folder.last_backup.is_some());
         assert!(updated.source.firefox.last_backup.is_none());
     }
+
+    #[tokio::test]
+    async fn test_backup_command_fails_but_records_successful_sources() {
+        let _guard = env_lock().lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        configure_test_env(temp_dir.path());
+
+        let source_dir = temp_dir.path().join("source");
+        std::fs::create_dir_all(&source_dir).unwrap();
+        std::fs::write(source_dir.join("secret.txt"), "classified").unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("data-home/briefcase/data")).unwrap();
+
+        let mut config = Config::default();
+        config.general.encryption_key = general_purpose::STANDARD.encode([7u8; 32]);
+        config.source.firefox.enabled = true;
+        config.source.firefox.dir = temp_dir.path().join("firefox-profile");
+        std::fs::create_dir_all(&config.source.firefox.dir).unwrap();
+        std::fs::write(
+            config.source.firefox.dir.join("places.sqlite"),
+            "not a database",
+        )
+        .unwrap();
+        config.source.folder.enabled = true;
+        config.source.folder.dir = source_dir;
+
+        let config_path = config::get_config_path().unwrap();
+        config::save_config(&config, &config_path).unwrap();
+
+        let error = briefcase::cli::backup::run(BackupArgs {
+            dry_run: false,
+            wait: false,
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(error.to_string(), "1 of 2 sources failed to back up");
+
+        let updated = config::load_config(&config_path).unwrap();
+        assert!(updated.source.folder.last_backup.is_some());
+        assert!(updated.source.firefox.last_backup.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_backup_dry_run_writes_nothing() {
+        let _guard = env_lock().lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        configure_test_env(temp_dir.path());
+
+        let source_dir = temp_dir.path().join("source");
+        std::fs::create_dir_all(&source_dir).unwrap();
+        std::fs::write(source_dir.join("secret.txt"), "classified").unwrap();
+        let data_dir = temp_dir.path().join("data-home/briefcase/data");
+        std::fs::create_dir_all(&data_dir).unwrap();
+
+        let mut config = Config::default();
+        config.general.encryption_key = general_purpose::STANDARD.encode([7u8; 32]);
+        config.source.folder.enabled = true;
+        config.source.folder.dir = source_dir;
+
+        let config_path = config::get_config_path().unwrap();
+        config::save_config(&config, &config_path).unwrap();
+
+        briefcase::cli::backup::run(BackupArgs {
+            dry_run: true,
+            wait: false,
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(std::fs::read_dir(&data_dir).unwrap().count(), 0);
+        let updated = config::load_config(&config_path).unwrap();
+        assert!(updated.source.folder.last_backup.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_backup_command_fails_fast_while_locked() {
+        let _guard = env_lock().lock().await;
+        let temp_dir = tempfile::tempdir().unwrap();
+        configure_test_env(temp_dir.path());
+
+        let mut config = Config::default();
+        config.general.encryption_key = general_purpose::STANDARD.encode([7u8; 32]);
+        let config_path = config::get_config_path().unwrap();
+        config::save_config(&config, &config_path).unwrap();
+
+        let data_dir = temp_dir.path().join("data-home/briefcase/data");
+        let _lock = briefcase::lock::acquire(&data_dir, briefcase::lock::LockMode::FailFast)
+            .await
+            .unwrap();
+
+        let error = briefcase::cli::backup::run(BackupArgs {
+            dry_run: false,
+            wait: false,
+        })
+        .await
+        .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains(&format!("(PID {})", std::process::id())));
+    }
 }
@@ -49,6 +49,31 @@ mod tests {
         assert!(matches!(cli.command, cli::Commands::Install(_)));
     }
 
+    #[test]
+    fn test_cli_pin_commands() {
+        let args = [
+            "briefcase",
+            "pin",
+            "Folder_laptop_2026-04-22_10-00-00.7z",
+            "--note",
+            "before the migration",
+        ];
+        let cli = cli::Cli::parse_from(args);
+        let cli::Commands::Pin(pin) = cli.command else {
+            panic!("expected pin");
+        };
+        assert_eq!(pin.backup, "Folder_laptop_2026-04-22_10-00-00.7z");
+        assert_eq!(pin.note, "before the migration");
+        assert!(!pin.wait);
+
+        let args = ["briefcase", "unpin", "Folder_laptop_2026-04-22_10-00-00.7z"];
+        let cli = cli::Cli::parse_from(args);
+        assert!(matches!(cli.command, cli::Commands::Unpin(_)));
+
+        let cli = cli::Cli::parse_from(["briefcase", "list"]);
+        assert!(matches!(cli.command, cli::Commands::List));
+    }
+
     #[tokio::test]
     async fn test_cli_config_validate_rejects_invalid_remote_config() {
         let temp_dir = tempfile::tempdir().unwrap();
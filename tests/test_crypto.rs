@@ -1,5 +1,9 @@
-use briefcase::crypto::encrypt::{decrypt_file, encrypt_file};
+use briefcase::crypto::encrypt::{
+    decrypt_file, decrypt_from_file_with_derived_key, encrypt_file, encrypt_stream_to_file,
+    encrypt_to_file_with_derived_key, open_decrypted,
+};
 use std::fs;
+use std::io::{Read, Write};
 use tempfile::tempdir;
 
 #[cfg(test)]
@@ -48,4 +52,78 @@ mod tests {
         let result = decrypt_file(&encrypted_path, &decrypted_path, "wrongpassword");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_encrypt_to_file_replaces_output_atomically() {
+        let temp_dir = tempdir().unwrap();
+        let output_path = temp_dir.path().join("Folder_2026-04-22_10-00-00.7z");
+        let key = [9u8; 32];
+        fs::write(&output_path, b"older archive").unwrap();
+
+        encrypt_to_file_with_derived_key(b"new archive", &output_path, &key).unwrap();
+
+        // Only the final file remains, and it holds the complete ciphertext
+        let names: Vec<_> = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["Folder_2026-04-22_10-00-00.7z"]);
+        let plaintext = decrypt_from_file_with_derived_key(&output_path, &key).unwrap();
+        assert_eq!(plaintext.as_slice(), b"new archive");
+    }
+
+    #[test]
+    fn test_stream_encryption_leaves_nothing_behind_on_failure() {
+        let temp_dir = tempdir().unwrap();
+        let output_path = temp_dir.path().join("Folder_2026-04-22_10-00-00.7z");
+        let key = [9u8; 32];
+        let data: Vec<u8> = (0..200_000u32).map(|i| (i % 7) as u8).collect();
+
+        encrypt_stream_to_file(&output_path, &key, |encryptor| {
+            encryptor.write_all(&data)?;
+            Ok(())
+        })
+        .unwrap();
+        let mut plaintext = Vec::new();
+        open_decrypted(&output_path, &key)
+            .unwrap()
+            .read_to_end(&mut plaintext)
+            .unwrap();
+        assert_eq!(plaintext, data);
+        assert_eq!(
+            decrypt_from_file_with_derived_key(&output_path, &key)
+                .unwrap()
+                .as_slice(),
+            data.as_slice()
+        );
+
+        let failed_path = temp_dir.path().join("Folder_2026-04-22_11-00-00.7z");
+        let result = encrypt_stream_to_file(&failed_path, &key, |encryptor| {
+            encryptor.write_all(&data)?;
+            Err(anyhow::anyhow!("source vanished"))
+        });
+        assert!(result.is_err());
+        let names: Vec<_> = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["Folder_2026-04-22_10-00-00.7z"]);
+    }
+
+    #[test]
+    fn test_single_shot_files_still_open() {
+        let temp_dir = tempdir().unwrap();
+        let output_path = temp_dir.path().join("Folder_2026-04-22_10-00-00.7z");
+        let key = [9u8; 32];
+        encrypt_to_file_with_derived_key(b"archive from an older version", &output_path, &key)
+            .unwrap();
+
+        let mut plaintext = Vec::new();
+        open_decrypted(&output_path, &key)
+            .unwrap()
+            .read_to_end(&mut plaintext)
+            .unwrap();
+        assert_eq!(plaintext, b"archive from an older version");
+        assert!(open_decrypted(&output_path, &[1u8; 32]).is_err());
+    }
 }
@@ -54,7 +54,11 @@ mod tests {
         // Perform backup
         let config = std::sync::Arc::new(tokio::sync::Mutex::new(config));
         let backup_service = BackupService::new(config.clone(), backup_dir.clone());
-        let backup_files = backup_service.perform_backup("testpassword").await.unwrap();
+        let backup_files = backup_service
+            .perform_backup("testpassword")
+            .await
+            .unwrap()
+            .into_backup_files();
         assert_eq!(backup_files.len(), 1);
         assert!(config.lock().await.source.firefox.last_backup.is_some());
 
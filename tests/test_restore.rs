@@ -1,5 +1,6 @@
 use briefcase::backup::compress;
 use briefcase::crypto::encrypt;
+use briefcase::models::config::ArchiveSettings;
 use std::fs;
 use tempfile::tempdir;
 
@@ -22,7 +23,8 @@ mod tests {
         fs::write(source_dir.join("subdir/test3.txt"), "Hello World 3").unwrap();
 
         // Test compression without password
-        compress::compress_directory(&source_dir, &compressed_file, None).unwrap();
+        compress::compress_directory(&source_dir, &compressed_file, ArchiveSettings::default())
+            .unwrap();
         assert!(compressed_file.exists());
 
         // Test extraction
@@ -57,7 +59,8 @@ mod tests {
         fs::write(source_dir.join("secret.txt"), "This is secret data").unwrap();
 
         // Test compression (no password in 7Zip itself)
-        compress::compress_directory(&source_dir, &compressed_file, None).unwrap();
+        compress::compress_directory(&source_dir, &compressed_file, ArchiveSettings::default())
+            .unwrap();
         assert!(compressed_file.exists());
 
         // Encrypt the compressed file
@@ -93,7 +96,8 @@ mod tests {
 
         // Step 1: Compress directory
         let temp_compressed = temp_dir.path().join("temp.7z");
-        compress::compress_directory(&source_dir, &temp_compressed, None).unwrap();
+        compress::compress_directory(&source_dir, &temp_compressed, ArchiveSettings::default())
+            .unwrap();
 
         // Step 2: Encrypt the compressed file
         encrypt::encrypt_file(&temp_compressed, &encrypted_file, password).unwrap();
@@ -41,13 +41,19 @@ mod tests {
 
     #[test]
     fn test_sync_args_valid() {
-        let args = SyncArgs { dry_run: false };
+        let args = SyncArgs {
+            dry_run: false,
+            wait: false,
+        };
         assert!(!args.dry_run);
     }
 
     #[test]
     fn test_sync_args_with_dry_run() {
-        let args = SyncArgs { dry_run: true };
+        let args = SyncArgs {
+            dry_run: true,
+            wait: false,
+        };
         assert!(args.dry_run);
     }
 
@@ -81,9 +87,12 @@ mod tests {
         )
         .unwrap();
 
-        briefcase::cli::sync::run(SyncArgs { dry_run: false })
-            .await
-            .unwrap();
+        briefcase::cli::sync::run(SyncArgs {
+            dry_run: false,
+            wait: false,
+        })
+        .await
+        .unwrap();
 
         let updated = config::load_config(&config_path).unwrap();
         assert!(updated
@@ -126,7 +135,11 @@ mod tests {
         )
         .unwrap();
 
-        let result = briefcase::cli::sync::run(SyncArgs { dry_run: false }).await;
+        let result = briefcase::cli::sync::run(SyncArgs {
+            dry_run: false,
+            wait: false,
+        })
+        .await;
         std::env::set_var("PATH", &old_path);
 
         result.unwrap();